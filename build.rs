@@ -0,0 +1,4 @@
+// migrations 目录通过 sqlx::migrate! 嵌入到二进制中，新增迁移时需要重新编译
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
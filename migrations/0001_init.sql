@@ -1,8 +1,5 @@
-drop
-database if exists whatsoo;
-create
-database whatsoo;
-create table whatsoo.user
+-- 初始表结构，与最早的 schema.sql 保持一致，已经按 schema.sql 建过库的环境会跳过建表
+create table if not exists user
 (
     pk_id           bigint unsigned auto_increment comment '主键ID'
         primary key,
@@ -26,7 +23,7 @@ create table whatsoo.user
         unique (uk_username)
 ) comment '用户表';
 
-create table whatsoo.topic
+create table if not exists topic
 (
     pk_id       bigint unsigned auto_increment comment '主键id'
         primary key,
@@ -43,7 +40,7 @@ create table whatsoo.topic
     update_user bigint unsigned not null comment '更新人'
 ) comment '帖子表';
 
-create table whatsoo.tag
+create table if not exists tag
 (
     pk_id         bigint unsigned auto_increment comment '主键id'
         primary key,
@@ -57,21 +54,18 @@ create table whatsoo.tag
         unique (tag_name)
 ) comment '标签表';
 
-create table whatsoo.tag_topic_relation
+create table if not exists tag_topic_relation
 (
     pk_id       bigint unsigned auto_increment comment '主键id'
         primary key,
     tag_id      bigint unsigned not null comment '标签id',
     topic_id    bigint unsigned not null comment '主题id',
     create_time datetime not null comment '创建时间',
-    create_user bigint unsigned not null comment '创建用户'
+    create_user bigint unsigned not null comment '创建用户',
+    index tag_topic_relation_tag_id_index (tag_id)
 ) comment '主题标签关系表';
 
-create
-    index tag_topic_relation_tag_id_index
-    on whatsoo.tag_topic_relation (tag_id);
-
-create table whatsoo.comment
+create table if not exists comment
 (
     pk_id       bigint unsigned auto_increment comment '主键id'
         primary key,
@@ -80,14 +74,11 @@ create table whatsoo.comment
     content     varchar(5000) not null comment '评论内容',
     like_amount int unsigned null comment '点赞数量',
     create_time datetime      not null comment '创建时间',
-    create_user bigint unsigned not null comment '创建人'
+    create_user bigint unsigned not null comment '创建人',
+    index comment_post_id_index (post_id)
 ) comment '评论表';
 
-create
-    index comment_post_id_index
-    on whatsoo.comment (post_id);
-
-create table whatsoo.star
+create table if not exists star
 (
     pk_id       bigint unsigned auto_increment comment '主键id'
         primary key,
@@ -101,7 +92,7 @@ create table whatsoo.star
         unique (star_type, user_id, star_id)
 ) comment '综合点赞，收藏，关注表';
 
-create table whatsoo.notice
+create table if not exists notice
 (
     pk_id            bigint unsigned auto_increment comment '主键id'
         primary key,
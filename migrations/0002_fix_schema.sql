@@ -0,0 +1,46 @@
+-- star 表的 unique(star_type) 导致每种类型只能有一条记录
+alter table star
+    drop index star_star_type_uindex,
+    modify star_type tinyint unsigned not null comment '用户关注-1，帖子收藏-2，评论点赞-3',
+    modify create_time datetime default CURRENT_TIMESTAMP not null comment '创建时间',
+    add index star_star_type_star_id_index (star_type, star_id);
+
+-- 评论表的帖子字段统一命名为 topic_id
+update comment
+set like_amount = 0
+where like_amount is null;
+
+alter table comment
+    change post_id topic_id bigint unsigned not null comment '帖子id',
+    rename index comment_post_id_index to comment_topic_id_index,
+    modify like_amount bigint unsigned default 0 not null comment '点赞数量',
+    modify create_time datetime default CURRENT_TIMESTAMP not null comment '创建时间';
+
+-- 标签表缺少更新时间和更新人，顶层标签的 parent_tag 为 0
+alter table tag
+    modify parent_tag bigint unsigned default 0 not null comment '父主题id，最多两个层级，顶层为0',
+    modify create_time datetime default CURRENT_TIMESTAMP not null comment '创建时间',
+    add update_time datetime default CURRENT_TIMESTAMP not null on update CURRENT_TIMESTAMP comment '更新时间',
+    add update_user bigint unsigned not null comment '更新人',
+    add index tag_parent_tag_index (parent_tag);
+
+update tag
+set update_user = create_user;
+
+-- 同一个帖子不能重复关联同一个标签，先清理重复数据
+delete r1
+from tag_topic_relation r1
+         join tag_topic_relation r2
+              on r1.tag_id = r2.tag_id and r1.topic_id = r2.topic_id and r1.pk_id > r2.pk_id;
+
+alter table tag_topic_relation
+    drop index tag_topic_relation_tag_id_index,
+    modify create_time datetime default CURRENT_TIMESTAMP not null comment '创建时间',
+    add constraint tag_topic_relation_tag_id_topic_id_uindex unique (tag_id, topic_id),
+    add index tag_topic_relation_topic_id_index (topic_id);
+
+alter table topic
+    modify tags varchar(50) not null comment '帖子相关标签id，-分隔，例如1-2-3-4，以tag_topic_relation为准';
+
+alter table notice
+    modify create_time datetime default CURRENT_TIMESTAMP not null comment '创建时间';
@@ -0,0 +1,11 @@
+alter table topic
+    add deleted bit default b'0' not null comment '帖子是否已删除' after top;
+
+alter table comment
+    add parent_id   bigint unsigned null comment '回复的评论id，直接评论帖子时为空' after topic_id,
+    add root_id     bigint unsigned null comment '所属楼层的顶层评论id，直接评论帖子时为空' after parent_id,
+    add depth       tinyint unsigned default 0 not null comment '回复层级，直接评论帖子为0' after root_id,
+    add deleted     bit default b'0' not null comment '评论是否已删除' after like_amount,
+    add update_time datetime default CURRENT_TIMESTAMP not null on update CURRENT_TIMESTAMP comment '更新时间',
+    add index comment_root_id_index (root_id),
+    add index comment_user_id_index (user_id);
@@ -0,0 +1,9 @@
+alter table notice
+    add notice_type tinyint unsigned not null comment '评论帖子-1，回复评论-2，关注用户-3，@提及-4' after pk_id,
+    add target_id   bigint unsigned not null comment '通知关联的帖子id，关注通知为关注人id' after notice_type,
+    add index notice_notified_user_id_viewed_index (notified_user_id, viewed);
+
+-- 之前的通知没有类型，无法跳转到关联内容
+delete
+from notice
+where notice_type = 0;
@@ -0,0 +1,6 @@
+-- ngram 分词用于支持中文搜索
+alter table topic
+    add fulltext index topic_title_content_fulltext (title, content) with parser ngram;
+
+alter table comment
+    add fulltext index comment_content_fulltext (content) with parser ngram;
@@ -0,0 +1,3 @@
+alter table user
+    add role          tinyint unsigned default 1 not null comment '角色 1普通用户 2版主 3管理员' after github_uid,
+    add token_version int unsigned default 0 not null comment 'token版本，修改密码或退出所有设备时增加' after role;
@@ -0,0 +1,3 @@
+-- 上传的头像地址包含存储地址前缀和文件名，100个字符放不下
+alter table user
+    modify avatar varchar(500) default 'https://avatars3.githubusercontent.com/u/18442141' null comment '头像';
@@ -0,0 +1,3 @@
+-- 未读通知汇总邮件按创建时间查找有新的未读通知的用户
+alter table notice
+    add index notice_viewed_create_time_index (viewed, create_time);
@@ -0,0 +1,20 @@
+-- 保存用户的语言偏好，未读通知汇总邮件按该语言发送
+alter table user
+    add locale varchar(10) default 'zh-CN' not null comment '语言偏好，登录时根据 Accept-Language 更新' after token_version;
+
+-- 通知文本改为显示时根据通知类型和语言生成，只保存生成文本所需的参数
+alter table notice
+    add actor_name   varchar(50)  default '' not null comment '触发通知的用户名' after target_id,
+    add target_title varchar(100) default '' not null comment '通知关联的主题标题，关注通知为空' after actor_name;
+
+update notice n
+    join user u on u.pk_id = n.create_user
+set n.actor_name = u.uk_username;
+
+update notice n
+    join topic t on t.pk_id = n.target_id
+set n.target_title = t.title
+where n.notice_type <> 3;
+
+alter table notice
+    drop column content;
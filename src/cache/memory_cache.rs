@@ -0,0 +1,243 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::cache::Cache;
+use crate::AppResult;
+
+/// 每写入这么多次清理一次过期数据，没有再被访问的 key 也能被回收
+const PURGE_INTERVAL: u32 = 1024;
+
+enum Value {
+    String(String),
+    /// 滑动窗口内每次请求的时间
+    Window(VecDeque<Instant>),
+}
+
+struct Entry {
+    value: Value,
+    expires_at: Option<Instant>,
+}
+
+impl Entry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.map_or(false, |at| at <= now)
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<String, Entry>,
+    writes: u32,
+}
+
+impl Entries {
+    /// 读取未过期的数据，已过期的数据顺便删除
+    fn get_mut(&mut self, key: &str, now: Instant) -> Option<&mut Entry> {
+        if self.map.get(key).map_or(false, |e| e.is_expired(now)) {
+            self.map.remove(key);
+        }
+        self.map.get_mut(key)
+    }
+
+    fn insert(&mut self, key: &str, entry: Entry, now: Instant) {
+        self.writes += 1;
+        if self.writes >= PURGE_INTERVAL {
+            self.writes = 0;
+            self.map.retain(|_, e| !e.is_expired(now));
+        }
+        self.map.insert(String::from(key), entry);
+    }
+}
+
+/// 进程内缓存，数据不会在多个实例之间共享，重启后丢失
+///
+/// 用于单实例部署、开发环境和不依赖 Redis 的测试，行为与 [`RedisCache`](crate::cache::RedisCache) 一致。
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: Mutex<Entries>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        // 持有锁期间不会 panic，锁被污染时数据仍然可用
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl Cache for MemoryCache {
+    async fn get_raw(&self, key: &str) -> AppResult<Option<String>> {
+        let mut entries = self.entries();
+        match entries.get_mut(key, Instant::now()) {
+            Some(Entry {
+                value: Value::String(value),
+                ..
+            }) => Ok(Some(value.clone())),
+            _ => Ok(None),
+        }
+    }
+
+    async fn set_raw(&self, key: &str, value: String, ttl: Option<Duration>) -> AppResult<()> {
+        let now = Instant::now();
+        let entry = Entry {
+            value: Value::String(value),
+            expires_at: ttl.map(|ttl| now + ttl),
+        };
+        self.entries().insert(key, entry, now);
+        Ok(())
+    }
+
+    async fn take_raw(&self, key: &str) -> AppResult<Option<String>> {
+        let mut entries = self.entries();
+        if entries.get_mut(key, Instant::now()).is_none() {
+            return Ok(None);
+        }
+        match entries.map.remove(key) {
+            Some(Entry {
+                value: Value::String(value),
+                ..
+            }) => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn del(&self, key: &str) -> AppResult<bool> {
+        let mut entries = self.entries();
+        let exists = entries.get_mut(key, Instant::now()).is_some();
+        entries.map.remove(key);
+        Ok(exists)
+    }
+
+    async fn exists(&self, key: &str) -> AppResult<bool> {
+        Ok(self.entries().get_mut(key, Instant::now()).is_some())
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> AppResult<bool> {
+        let now = Instant::now();
+        match self.entries().get_mut(key, now) {
+            Some(entry) => {
+                entry.expires_at = Some(now + ttl);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn ttl(&self, key: &str) -> AppResult<Option<Duration>> {
+        let now = Instant::now();
+        Ok(self
+            .entries()
+            .get_mut(key, now)
+            .and_then(|entry| entry.expires_at)
+            .map(|at| at - now))
+    }
+
+    async fn incr(&self, key: &str, ttl: Duration) -> AppResult<i64> {
+        let now = Instant::now();
+        let mut entries = self.entries();
+        if let Some(Entry {
+            value: Value::String(value),
+            ..
+        }) = entries.get_mut(key, now)
+        {
+            let count = value.parse::<i64>().unwrap_or_default() + 1;
+            *value = count.to_string();
+            return Ok(count);
+        }
+        let entry = Entry {
+            value: Value::String(String::from("1")),
+            expires_at: Some(now + ttl),
+        };
+        entries.insert(key, entry, now);
+        Ok(1)
+    }
+
+    async fn hit(&self, key: &str, limit: u32, window: Duration) -> AppResult<Option<Duration>> {
+        let now = Instant::now();
+        let mut entries = self.entries();
+        if let Some(Entry {
+            value: Value::Window(hits),
+            expires_at,
+        }) = entries.get_mut(key, now)
+        {
+            while hits.front().map_or(false, |&at| at + window <= now) {
+                hits.pop_front();
+            }
+            if hits.len() >= limit as usize {
+                // 窗口内已有记录，`front` 一定存在
+                return Ok(hits.front().map(|&oldest| oldest + window - now));
+            }
+            hits.push_back(now);
+            *expires_at = Some(now + window);
+            return Ok(None);
+        }
+        if limit == 0 {
+            return Ok(Some(window));
+        }
+        let entry = Entry {
+            value: Value::Window(VecDeque::from(vec![now])),
+            expires_at: Some(now + window),
+        };
+        entries.insert(key, entry, now);
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheExt;
+    use crate::model::user::Role;
+
+    const TTL: Duration = Duration::from_millis(100);
+
+    #[tokio::test]
+    async fn typed_values_round_trip() {
+        let cache = MemoryCache::new();
+        cache.set("role", &Role::Moderator, None).await.unwrap();
+        assert_eq!(cache.get::<Role>("role").await.unwrap(), Some(Role::Moderator));
+        assert_eq!(cache.ttl("role").await.unwrap(), None);
+        assert_eq!(cache.take::<Role>("role").await.unwrap(), Some(Role::Moderator));
+        assert_eq!(cache.take::<Role>("role").await.unwrap(), None);
+        assert!(!cache.del("role").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn values_expire() {
+        let cache = MemoryCache::new();
+        cache.set("code", &"123456", Some(TTL)).await.unwrap();
+        assert!(cache.exists("code").await.unwrap());
+        assert!(cache.ttl("code").await.unwrap().unwrap() <= TTL);
+        tokio::time::sleep(TTL).await;
+        assert!(!cache.exists("code").await.unwrap());
+        assert_eq!(cache.get::<String>("code").await.unwrap(), None);
+        assert!(!cache.expire("code", TTL).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn incr_keeps_ttl_of_first_write() {
+        let cache = MemoryCache::new();
+        assert_eq!(cache.incr("failures", TTL).await.unwrap(), 1);
+        tokio::time::sleep(TTL / 2).await;
+        // 后续计数不延长有效期，计数从第一次失败开始统计
+        assert_eq!(cache.incr("failures", Duration::from_secs(60)).await.unwrap(), 2);
+        assert!(cache.ttl("failures").await.unwrap().unwrap() <= TTL / 2);
+        tokio::time::sleep(TTL / 2).await;
+        assert_eq!(cache.incr("failures", TTL).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn hit_with_zero_limit_always_rejects() {
+        let cache = MemoryCache::new();
+        assert_eq!(cache.hit("window", 0, TTL).await.unwrap(), Some(TTL));
+        assert!(!cache.exists("window").await.unwrap());
+        assert_eq!(cache.hit("window", 1, TTL).await.unwrap(), None);
+        // 滑动窗口不是字符串，按字符串读取时视为不存在
+        assert_eq!(cache.get_raw("window").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::AppResult;
+
+pub use memory_cache::MemoryCache;
+pub use redis_cache::RedisCache;
+
+mod memory_cache;
+mod redis_cache;
+
+/// 缓存，保存验证码、会话、计数器等带有效期的数据
+///
+/// 业务代码应使用 [`CacheExt`] 中带类型的方法，值以 JSON 格式保存。
+/// 生产环境使用 [`RedisCache`]，[`MemoryCache`] 用于单实例部署、开发和测试。
+/// 邮件发件箱和通知推送不经过缓存，使用 [`MemoryCache`] 时仍然需要连接 Redis。
+#[async_trait]
+pub trait Cache: Send + Sync {
+    async fn get_raw(&self, key: &str) -> AppResult<Option<String>>;
+
+    /// `ttl` 为 `None` 时永不过期
+    async fn set_raw(&self, key: &str, value: String, ttl: Option<Duration>) -> AppResult<()>;
+
+    /// 读取并删除，并发调用时只有一个调用者能读到值
+    async fn take_raw(&self, key: &str) -> AppResult<Option<String>>;
+
+    /// key 存在并被删除时返回 `true`
+    async fn del(&self, key: &str) -> AppResult<bool>;
+
+    async fn exists(&self, key: &str) -> AppResult<bool>;
+
+    /// key 存在并设置了有效期时返回 `true`
+    async fn expire(&self, key: &str, ttl: Duration) -> AppResult<bool>;
+
+    /// 剩余有效期，key 不存在或永不过期时返回 `None`
+    async fn ttl(&self, key: &str) -> AppResult<Option<Duration>>;
+
+    /// 计数器加一并返回新的值，计数器创建时设置有效期为 `ttl`
+    async fn incr(&self, key: &str, ttl: Duration) -> AppResult<i64>;
+
+    /// 滑动窗口计数：`window` 时间内的记录数小于 `limit` 时记录本次请求并返回 `None`，
+    /// 否则返回窗口内最早一条记录过期还需要等待的时间
+    async fn hit(&self, key: &str, limit: u32, window: Duration) -> AppResult<Option<Duration>>;
+}
+
+/// 带类型的缓存操作
+#[async_trait]
+pub trait CacheExt: Cache {
+    async fn get<T: DeserializeOwned>(&self, key: &str) -> AppResult<Option<T>> {
+        match self.get_raw(key).await? {
+            Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set<T: Serialize + Sync>(&self, key: &str, value: &T, ttl: Option<Duration>) -> AppResult<()> {
+        self.set_raw(key, serde_json::to_string(value)?, ttl).await
+    }
+
+    async fn take<T: DeserializeOwned>(&self, key: &str) -> AppResult<Option<T>> {
+        match self.take_raw(key).await? {
+            Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<C: Cache + ?Sized> CacheExt for C {}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use chrono::Local;
+use redis::aio::ConnectionManager;
+use redis::{Client, Script};
+use uuid::Uuid;
+
+use crate::cache::Cache;
+use crate::AppResult;
+
+lazy_static! {
+    static ref TAKE_SCRIPT: Script = Script::new(
+        r"
+        local value = redis.call('GET', KEYS[1])
+        if value then
+            redis.call('DEL', KEYS[1])
+        end
+        return value
+        "
+    );
+    static ref INCR_SCRIPT: Script = Script::new(
+        r"
+        local count = redis.call('INCR', KEYS[1])
+        if count == 1 then
+            redis.call('PEXPIRE', KEYS[1], ARGV[1])
+        end
+        return count
+        "
+    );
+    // 有序集合保存窗口内每次请求的时间（毫秒），先清理窗口外的记录再计数；
+    // 超出限制时返回最早一条记录离开窗口还需要等待的毫秒数，否则返回0
+    static ref HIT_SCRIPT: Script = Script::new(
+        r"
+        local now = tonumber(ARGV[1])
+        local window = tonumber(ARGV[2])
+        redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - window)
+        if redis.call('ZCARD', KEYS[1]) < tonumber(ARGV[3]) then
+            redis.call('ZADD', KEYS[1], now, ARGV[4])
+            redis.call('PEXPIRE', KEYS[1], window)
+            return 0
+        end
+        local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+        return math.max(tonumber(oldest[2]) + window - now, 1)
+        "
+    );
+}
+
+/// 基于 Redis 的缓存，所有请求共用一个多路复用的异步连接，连接断开后自动重连
+#[derive(Clone)]
+pub struct RedisCache {
+    conn: ConnectionManager,
+}
+
+impl RedisCache {
+    pub async fn connect(client: Client) -> AppResult<Self> {
+        let conn = ConnectionManager::new(client).await?;
+        Ok(Self { conn })
+    }
+
+    // ConnectionManager 克隆后共用同一个连接，克隆的开销很小
+    fn conn(&self) -> ConnectionManager {
+        self.conn.clone()
+    }
+}
+
+#[async_trait]
+impl Cache for RedisCache {
+    async fn get_raw(&self, key: &str) -> AppResult<Option<String>> {
+        Ok(redis::cmd("GET").arg(key).query_async(&mut self.conn()).await?)
+    }
+
+    async fn set_raw(&self, key: &str, value: String, ttl: Option<Duration>) -> AppResult<()> {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(key).arg(value);
+        if let Some(ttl) = ttl {
+            cmd.arg("PX").arg(ttl.as_millis() as u64);
+        }
+        Ok(cmd.query_async(&mut self.conn()).await?)
+    }
+
+    async fn take_raw(&self, key: &str) -> AppResult<Option<String>> {
+        Ok(TAKE_SCRIPT.key(key).invoke_async(&mut self.conn()).await?)
+    }
+
+    async fn del(&self, key: &str) -> AppResult<bool> {
+        let deleted: i64 = redis::cmd("DEL").arg(key).query_async(&mut self.conn()).await?;
+        Ok(deleted > 0)
+    }
+
+    async fn exists(&self, key: &str) -> AppResult<bool> {
+        Ok(redis::cmd("EXISTS").arg(key).query_async(&mut self.conn()).await?)
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> AppResult<bool> {
+        Ok(redis::cmd("PEXPIRE")
+            .arg(key)
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut self.conn())
+            .await?)
+    }
+
+    async fn ttl(&self, key: &str) -> AppResult<Option<Duration>> {
+        // key 不存在时返回 -2，永不过期时返回 -1
+        let millis: i64 = redis::cmd("PTTL").arg(key).query_async(&mut self.conn()).await?;
+        Ok((millis > 0).then(|| Duration::from_millis(millis as u64)))
+    }
+
+    async fn incr(&self, key: &str, ttl: Duration) -> AppResult<i64> {
+        Ok(INCR_SCRIPT
+            .key(key)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async(&mut self.conn())
+            .await?)
+    }
+
+    async fn hit(&self, key: &str, limit: u32, window: Duration) -> AppResult<Option<Duration>> {
+        let now = Local::now().timestamp_millis();
+        let wait_millis: u64 = HIT_SCRIPT
+            .key(key)
+            .arg(now)
+            .arg(window.as_millis() as u64)
+            .arg(limit)
+            .arg(format!("{}-{}", now, Uuid::new_v4().to_simple()))
+            .invoke_async(&mut self.conn())
+            .await?;
+        Ok((wait_millis > 0).then(|| Duration::from_millis(wait_millis)))
+    }
+}
@@ -10,6 +10,9 @@ use serde::Serialize;
 
 use crate::AppResult;
 
+/// 单页最多返回的记录数
+const MAX_PAGE_SIZE: u32 = 100;
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ApiResult<T: Serialize> {
     code: Option<u16>,
@@ -58,6 +61,42 @@ impl<T: Serialize> ApiResult<T> {
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Pagination {
+    pub current_page: u32,
+    pub page_size: u32,
+}
+
+impl Pagination {
+    pub fn limit(&self) -> u32 {
+        self.page_size.clamp(1, MAX_PAGE_SIZE)
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.current_page.max(1).saturating_sub(1).saturating_mul(self.limit())
+    }
+}
+
+/// 分页查询结果
+#[derive(Debug, Serialize)]
+pub struct Page<T: Serialize> {
+    pub total: i64,
+    pub current_page: u32,
+    pub page_size: u32,
+    pub records: Vec<T>,
+}
+
+impl<T: Serialize> Page<T> {
+    pub fn new(total: i64, pagination: &Pagination, records: Vec<T>) -> Self {
+        Self {
+            total,
+            current_page: pagination.current_page.max(1),
+            page_size: pagination.limit(),
+            records,
+        }
+    }
+}
+
 impl<T: Debug + Serialize> Display for ApiResult<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -1,3 +1,63 @@
 pub const TOKEN_HEADER_NAME: &str = "authorization";
 
-pub const TOKEN_SECRET: &[u8] = "whatsoo".as_bytes();
+/// 图形验证码有效期，单位为秒
+pub const CAPTCHA_EXPIRE_SECS: i32 = 60 * 5;
+
+/// 图形验证码最多可以尝试的次数，输错后需要重新获取
+pub const CAPTCHA_MAX_ATTEMPTS: u32 = 1;
+
+/// 邮箱验证码有效期，单位为秒
+pub const EMAIL_CODE_EXPIRE_SECS: i32 = 60 * 50;
+
+/// 邮箱验证码最多可以尝试的次数
+pub const EMAIL_CODE_MAX_ATTEMPTS: u32 = 5;
+
+/// 访问令牌有效期，单位为秒
+pub const ACCESS_TOKEN_EXPIRE_SECS: i32 = 60 * 30;
+
+/// 刷新令牌有效期，单位为秒
+pub const REFRESH_TOKEN_EXPIRE_SECS: i32 = 60 * 60 * 24 * 7;
+
+/// 勾选“永久登录”时刷新令牌的有效期，单位为秒
+pub const REFRESH_TOKEN_FOREVER_EXPIRE_SECS: i32 = 60 * 60 * 24 * 365;
+
+/// 通知推送票据有效期，单位为秒，票据只能使用一次
+pub const STREAM_TICKET_EXPIRE_SECS: i32 = 30;
+
+/// 以下长度限制与数据库表字段长度一致
+pub const MAX_EMAIL_LENGTH: usize = 50;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+pub const MAX_PASSWORD_LENGTH: usize = 64;
+
+pub const MAX_TOPIC_TITLE_LENGTH: usize = 100;
+
+pub const MAX_TOPIC_CONTENT_LENGTH: usize = 1000;
+
+pub const MAX_COMMENT_LENGTH: usize = 5000;
+
+pub const MAX_TAG_NAME_LENGTH: usize = 50;
+
+pub const MAX_TAG_LOGO_LENGTH: usize = 500;
+
+pub const MAX_AVATAR_LENGTH: usize = 500;
+
+pub const MAX_BLOG_URL_LENGTH: usize = 100;
+
+pub const MAX_INTRODUCE_LENGTH: usize = 500;
+
+/// GitHub 用户名最长39个字符，比表字段短
+pub const MAX_GITHUB_UID_LENGTH: usize = 39;
+
+/// 本地存储的上传文件的访问路由
+pub const UPLOAD_ROUTE: &str = "/uploads";
+
+/// 评论最多可以嵌套回复的层级
+pub const MAX_COMMENT_DEPTH: u8 = 5;
+
+/// 一个主题最多关联的标签数
+pub const MAX_TOPIC_TAGS: usize = 5;
+
+/// 主题表中冗余存储的标签id，`-` 分隔后的最大长度
+pub const MAX_TOPIC_TAGS_STR_LENGTH: usize = 50;
@@ -1,16 +1,21 @@
 use std::convert::Infallible;
 
 use axum::body::{Bytes, Full};
-use axum::http::{Response, StatusCode};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderMap, HeaderValue, Response, StatusCode};
 use axum::response::IntoResponse;
 use axum::Json;
-use serde_json::json;
 use thiserror::Error;
 
+use crate::common::locale::Locale;
+use crate::middleware::request_context::RequestContext;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("databaseError: {0}")]
     DatabaseError(#[from] sqlx::Error),
+    #[error("Migrate error: {0}")]
+    MigrateError(#[from] sqlx::migrate::MigrateError),
     #[error("Error on encode or decode password")]
     PwdHashError(#[from] argon2::password_hash::Error),
     #[error("jwt error")]
@@ -19,8 +24,6 @@ pub enum AppError {
     IoError(#[from] std::io::Error),
     #[error("Environment variable must be set")]
     EnvironmentVariableNotSet(#[from] std::env::VarError),
-    #[error("Redis Connection Error: {0}")]
-    RedisConnectionError(#[from] r2d2::Error),
     #[error("Redis error")]
     RedisGetError(#[from] redis::RedisError),
     #[error("Serde Error: {0}")]
@@ -29,35 +32,365 @@ pub enum AppError {
     AxumHttpError(#[from] axum::http::Error),
     #[error("Serde json error")]
     SerdeJsonError(#[from] serde_json::Error),
-    #[error("business error")]
-    BusinessError(i32, &'static str),
+    #[error("Mail error: {0}")]
+    MailError(String),
+    #[error("Invalid config: {0}")]
+    ConfigError(String),
+    /// 请求过于频繁，参数为客户端需要等待的秒数
+    #[error("Too many requests, retry after {0}s")]
+    TooManyRequests(u64),
+    /// 请求参数校验失败，包含每个字段的错误
+    #[error("Validation failed: {0:?}")]
+    ValidationError(Vec<FieldError>),
+    #[error("Business error: {}", .0.code())]
+    BusinessError(ErrorCode),
+}
+
+macro_rules! error_codes {
+    ($($name:ident => ($status:ident, $code:literal, $zh:literal, $en:literal),)*) => {
+        /// 业务错误码，客户端应根据字符串形式的 `code` 而不是提示信息判断错误类型
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub enum ErrorCode {
+            $($name,)*
+        }
+
+        impl ErrorCode {
+            #[cfg(test)]
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$name,)*];
+
+            pub fn code(&self) -> &'static str {
+                match self {
+                    $(ErrorCode::$name => $code,)*
+                }
+            }
+
+            pub fn status(&self) -> StatusCode {
+                match self {
+                    $(ErrorCode::$name => StatusCode::$status,)*
+                }
+            }
+
+            pub fn message(&self, locale: Locale) -> &'static str {
+                match (self, locale) {
+                    $(
+                        (ErrorCode::$name, Locale::ZhCn) => $zh,
+                        (ErrorCode::$name, Locale::En) => $en,
+                    )*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    // 400
+    BadRequest => (BAD_REQUEST, "BAD_REQUEST", "请求参数错误", "Bad request"),
+    CaptchaInvalid => (BAD_REQUEST, "CAPTCHA_INVALID", "验证码错误，请重新输入", "Incorrect verification code"),
+    CaptchaRetry => (
+        BAD_REQUEST,
+        "CAPTCHA_RETRY",
+        "验证码错误，请刷新后重新输入",
+        "Incorrect captcha, please refresh and try again"
+    ),
+    VerifyCodeExpired => (
+        BAD_REQUEST,
+        "VERIFY_CODE_EXPIRED",
+        "验证码已失效，请重新获取",
+        "Verification code has expired, please request a new one"
+    ),
+    VerifyCodeAttemptsExceeded => (
+        BAD_REQUEST,
+        "VERIFY_CODE_ATTEMPTS_EXCEEDED",
+        "验证码错误次数过多，请重新获取",
+        "Too many incorrect attempts, please request a new code"
+    ),
+    SearchKeywordEmpty => (BAD_REQUEST, "SEARCH_KEYWORD_EMPTY", "搜索关键词不能为空", "Search keyword is required"),
+    SearchKeywordTooLong => (
+        BAD_REQUEST,
+        "SEARCH_KEYWORD_TOO_LONG",
+        "搜索关键词不能超过50个字符",
+        "Search keyword must not exceed 50 characters"
+    ),
+    InvalidImage => (BAD_REQUEST, "INVALID_IMAGE", "图片已损坏或无法解析", "The image is corrupted or cannot be decoded"),
+    CannotFollowSelf => (BAD_REQUEST, "CANNOT_FOLLOW_SELF", "不能关注自己", "You cannot follow yourself"),
+    ReplyNotInTopic => (
+        BAD_REQUEST,
+        "REPLY_NOT_IN_TOPIC",
+        "回复的评论不属于该主题",
+        "The comment being replied to does not belong to this topic"
+    ),
+    ReplyTooDeep => (
+        BAD_REQUEST,
+        "REPLY_TOO_DEEP",
+        "回复层级过深，无法继续回复",
+        "Replies are nested too deeply"
+    ),
+    TopicTagRequired => (BAD_REQUEST, "TOPIC_TAG_REQUIRED", "主题至少需要一个标签", "A topic needs at least one tag"),
+    TopicTooManyTags => (BAD_REQUEST, "TOPIC_TOO_MANY_TAGS", "主题关联的标签过多", "Too many tags for a topic"),
+    TagParentSelf => (BAD_REQUEST, "TAG_PARENT_SELF", "父标签不能是自己", "A tag cannot be its own parent"),
+    TagTooDeep => (BAD_REQUEST, "TAG_TOO_DEEP", "标签最多只能有两个层级", "Tags can only be nested two levels deep"),
+    // 401
+    InvalidCredentials => (
+        UNAUTHORIZED,
+        "INVALID_CREDENTIALS",
+        "邮箱或密码错误",
+        "Incorrect email or password"
+    ),
+    NotLoggedIn => (UNAUTHORIZED, "NOT_LOGGED_IN", "用户未登录，请登录后操作", "Please log in first"),
+    TokenInvalid => (
+        UNAUTHORIZED,
+        "TOKEN_INVALID",
+        "TOKEN已失效，请重新登录",
+        "Token is invalid or expired, please log in again"
+    ),
+    SessionExpired => (
+        UNAUTHORIZED,
+        "SESSION_EXPIRED",
+        "登录已过期，请重新登录",
+        "Session has expired, please log in again"
+    ),
+    // 403
+    Forbidden => (FORBIDDEN, "FORBIDDEN", "权限不足", "Permission denied"),
+    NotTopicAuthor => (
+        FORBIDDEN,
+        "NOT_TOPIC_AUTHOR",
+        "只有作者才能操作该主题",
+        "Only the author can modify this topic"
+    ),
+    NotCommentOwner => (
+        FORBIDDEN,
+        "NOT_COMMENT_OWNER",
+        "只能操作自己的评论",
+        "You can only modify your own comments"
+    ),
+    CannotChangeOwnRole => (FORBIDDEN, "CANNOT_CHANGE_OWN_ROLE", "不能修改自己的角色", "You cannot change your own role"),
+    // 404
+    UserNotFound => (NOT_FOUND, "USER_NOT_FOUND", "用户不存在", "User not found"),
+    TopicNotFound => (NOT_FOUND, "TOPIC_NOT_FOUND", "主题不存在", "Topic not found"),
+    CommentNotFound => (NOT_FOUND, "COMMENT_NOT_FOUND", "评论不存在", "Comment not found"),
+    TagNotFound => (NOT_FOUND, "TAG_NOT_FOUND", "标签不存在", "Tag not found"),
+    NoticeNotFound => (NOT_FOUND, "NOTICE_NOT_FOUND", "通知不存在", "Notice not found"),
+    // 409
+    UserExists => (CONFLICT, "USER_EXISTS", "用户名或邮箱已被注册", "Username or email is already registered"),
+    ProfileConflict => (
+        CONFLICT,
+        "PROFILE_CONFLICT",
+        "博客网址或GitHub用户名已被其他用户使用",
+        "Blog URL or GitHub username is already used by another user"
+    ),
+    TagNameExists => (CONFLICT, "TAG_NAME_EXISTS", "标签名称已存在", "Tag name already exists"),
+    TagHasChildren => (
+        CONFLICT,
+        "TAG_HAS_CHILDREN",
+        "请先删除该标签下的子标签",
+        "Delete the child tags first"
+    ),
+    // 通过存在性和权限检查后修改或删除仍然失败，说明数据已被同时修改
+    UpdateConflict => (CONFLICT, "UPDATE_CONFLICT", "修改失败，请刷新后重试", "Update failed, please refresh and try again"),
+    DeleteConflict => (CONFLICT, "DELETE_CONFLICT", "删除失败，请刷新后重试", "Delete failed, please refresh and try again"),
+    // 413
+    PayloadTooLarge => (PAYLOAD_TOO_LARGE, "PAYLOAD_TOO_LARGE", "请求内容过大", "Request body is too large"),
+    ImageDimensionTooLarge => (
+        PAYLOAD_TOO_LARGE,
+        "IMAGE_DIMENSION_TOO_LARGE",
+        "图片尺寸过大",
+        "Image dimensions are too large"
+    ),
+    // 415
+    UnsupportedImageType => (
+        UNSUPPORTED_MEDIA_TYPE,
+        "UNSUPPORTED_IMAGE_TYPE",
+        "只支持 PNG、JPEG、GIF 和 WebP 格式的图片",
+        "Only PNG, JPEG, GIF and WebP images are supported"
+    ),
+    // 422
+    ValidationFailed => (UNPROCESSABLE_ENTITY, "VALIDATION_FAILED", "请求参数校验失败", "Validation failed"),
+    Required => (UNPROCESSABLE_ENTITY, "REQUIRED", "不能为空", "This field is required"),
+    InvalidEmail => (UNPROCESSABLE_ENTITY, "INVALID_EMAIL", "邮箱格式不正确", "Invalid email address"),
+    InvalidUsername => (
+        UNPROCESSABLE_ENTITY,
+        "INVALID_USERNAME",
+        "用户名只能包含字母、数字、下划线和短横线，长度为5到20个字符",
+        "Username must be 5 to 20 letters, digits, underscores or hyphens"
+    ),
+    WeakPassword => (
+        UNPROCESSABLE_ENTITY,
+        "WEAK_PASSWORD",
+        "密码长度为8到64个字符，且必须同时包含字母和数字",
+        "Password must be 8 to 64 characters and contain both letters and digits"
+    ),
+    TitleLength => (
+        UNPROCESSABLE_ENTITY,
+        "TITLE_LENGTH",
+        "标题长度为1到100个字符",
+        "Title must be 1 to 100 characters"
+    ),
+    TopicContentLength => (
+        UNPROCESSABLE_ENTITY,
+        "TOPIC_CONTENT_LENGTH",
+        "内容长度为1到1000个字符",
+        "Content must be 1 to 1000 characters"
+    ),
+    CommentContentLength => (
+        UNPROCESSABLE_ENTITY,
+        "COMMENT_CONTENT_LENGTH",
+        "评论长度为1到5000个字符",
+        "Comment must be 1 to 5000 characters"
+    ),
+    TagNameLength => (
+        UNPROCESSABLE_ENTITY,
+        "TAG_NAME_LENGTH",
+        "标签名称长度为1到50个字符",
+        "Tag name must be 1 to 50 characters"
+    ),
+    InvalidUrl => (UNPROCESSABLE_ENTITY, "INVALID_URL", "链接格式不正确", "Invalid URL"),
+    InvalidGithubUid => (
+        UNPROCESSABLE_ENTITY,
+        "INVALID_GITHUB_UID",
+        "GitHub用户名格式不正确",
+        "Invalid GitHub username"
+    ),
+    IntroduceLength => (
+        UNPROCESSABLE_ENTITY,
+        "INTRODUCE_LENGTH",
+        "自我介绍最多500个字符",
+        "Introduction must be at most 500 characters"
+    ),
+    // 429
+    TooManyRequests => (TOO_MANY_REQUESTS, "TOO_MANY_REQUESTS", "操作过于频繁，请稍后再试", "Too many requests, please try again later"),
+    // 500
+    InternalError => (
+        INTERNAL_SERVER_ERROR,
+        "INTERNAL_ERROR",
+        "服务器内部错误，请稍后重试",
+        "Internal server error, please try again later"
+    ),
+}
+
+/// 单个字段的校验错误
+#[derive(Debug, Clone)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub code: ErrorCode,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, code: ErrorCode) -> Self {
+        Self { field, code }
+    }
 }
 
 impl AppError {
-    pub fn message(&self) -> String {
+    pub fn error_code(&self) -> ErrorCode {
         match self {
-            AppError::BusinessError(_, msg) => msg.to_string(),
-            _ => String::from("Internal server error"),
+            AppError::BusinessError(code) => *code,
+            AppError::TooManyRequests(_) => ErrorCode::TooManyRequests,
+            AppError::ValidationError(_) => ErrorCode::ValidationFailed,
+            _ => ErrorCode::InternalError,
         }
     }
 }
 
 #[derive(Serialize)]
-struct ErrorResponseWrapper {
-    code: i32,
-    msg: String,
+struct ErrorResponse {
+    code: &'static str,
+    msg: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    details: Vec<FieldErrorResponse>,
+}
+
+#[derive(Serialize)]
+struct FieldErrorResponse {
+    field: &'static str,
+    code: &'static str,
+    msg: &'static str,
 }
 
 impl IntoResponse for AppError {
     type Body = Full<Bytes>;
     type BodyError = Infallible;
 
+    /// 提示信息按请求的语言返回；内部错误只返回请求ID，具体原因记录在日志中
     fn into_response(self) -> Response<Self::Body> {
-        let body = Json(json!({
-            "code": 500,
-            "msg": self.message(),
-        }));
+        let context = RequestContext::current();
+        let locale = context.as_ref().map(|c| c.locale).unwrap_or_default();
+        let request_id = context.map(|c| c.request_id);
+        let code = self.error_code();
+        if code == ErrorCode::InternalError {
+            error!(
+                "请求处理出错，请求ID: {}，报错信息: {:?}",
+                request_id.as_deref().unwrap_or("-"),
+                self
+            );
+        }
+        let details = match &self {
+            AppError::ValidationError(errors) => errors
+                .iter()
+                .map(|e| FieldErrorResponse {
+                    field: e.field,
+                    code: e.code.code(),
+                    msg: e.code.message(locale),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let body = Json(ErrorResponse {
+            code: code.code(),
+            msg: code.message(locale),
+            request_id,
+            details,
+        });
+        let mut headers = HeaderMap::new();
+        if let AppError::TooManyRequests(retry_after) = self {
+            headers.insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        (code.status(), headers, body).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
 
-        (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+    use super::*;
+
+    #[test]
+    fn error_code_maps_status() {
+        let cases = vec![
+            (AppError::BusinessError(ErrorCode::UserNotFound), StatusCode::NOT_FOUND),
+            (AppError::TooManyRequests(30), StatusCode::TOO_MANY_REQUESTS),
+            (
+                AppError::ValidationError(vec![FieldError::new("email", ErrorCode::InvalidEmail)]),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                AppError::DatabaseError(sqlx::Error::RowNotFound),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                AppError::ConfigError(String::from("jwt.secret")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        ];
+        for (error, status) in cases {
+            assert_eq!(error.error_code().status(), status, "{:?}", error);
+            assert_eq!(error.into_response().status(), status);
+        }
+    }
+
+    #[test]
+    fn codes_are_unique_and_localized() {
+        let mut codes = HashSet::new();
+        for code in ErrorCode::ALL {
+            // 客户端根据 code 判断错误类型，必须是稳定且不重复的大写标识
+            assert!(codes.insert(code.code()), "{}", code.code());
+            assert!(code.code().chars().all(|c| c.is_ascii_uppercase() || c == '_'));
+            assert!(code.status().is_client_error() || code.status().is_server_error());
+            assert!(!code.message(Locale::ZhCn).is_empty());
+            assert!(!code.message(Locale::En).is_empty());
+            assert_ne!(code.message(Locale::ZhCn), code.message(Locale::En));
+        }
     }
 }
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use once_cell::sync::OnceCell;
+
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::settings::JwtSettings;
+use crate::model::user::UserToken;
+use crate::AppResult;
+
+static JWT_KEYS: OnceCell<JwtKeys> = OnceCell::new();
+
+/// 启动时根据配置加载密钥
+pub fn init(settings: &JwtSettings) -> AppResult<()> {
+    let keys = JwtKeys::from_settings(settings)?;
+    JWT_KEYS
+        .set(keys)
+        .map_err(|_| AppError::ConfigError(String::from("JWT 密钥重复初始化")))
+}
+
+pub fn keys() -> &'static JwtKeys {
+    JWT_KEYS.get().expect("JWT 密钥未初始化")
+}
+
+/// 测试使用固定的 HS256 密钥，可以重复调用
+#[cfg(test)]
+pub fn init_for_test() {
+    JWT_KEYS.get_or_init(|| {
+        let settings = JwtSettings {
+            algorithm: String::from("HS256"),
+            kid: String::from("test"),
+            secret: Some(String::from("whatsoo-test-secret-of-at-least-32-characters")),
+            private_key: None,
+            public_key: None,
+            retired_keys: Vec::new(),
+        };
+        JwtKeys::from_settings(&settings).expect("测试 JWT 密钥初始化失败")
+    });
+}
+
+struct VerifyKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// JWT 签名和校验密钥
+///
+/// 签发时使用当前密钥，并在 header 中写入其 `kid`；校验时根据 header 中的 `kid` 选择密钥，
+/// 轮换密钥时把旧密钥放入 `jwt.retired_keys`，已签发的 token 在过期前仍然有效。
+pub struct JwtKeys {
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    verify_keys: HashMap<String, VerifyKey>,
+}
+
+impl JwtKeys {
+    pub fn from_settings(settings: &JwtSettings) -> AppResult<Self> {
+        let algorithm = parse_algorithm(&settings.algorithm)?;
+        let (encoding_key, decoding_key) = match algorithm {
+            Algorithm::HS256 => {
+                let secret = settings.secret.as_deref().unwrap_or_default();
+                (
+                    EncodingKey::from_secret(secret.as_bytes()),
+                    DecodingKey::from_secret(secret.as_bytes()),
+                )
+            }
+            _ => {
+                let private_pem = read_pem(&settings.private_key)?;
+                let public_pem = read_pem(&settings.public_key)?;
+                let encoding_key = match algorithm {
+                    Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_pem)?,
+                    _ => EncodingKey::from_ed_pem(&private_pem)?,
+                };
+                (encoding_key, pem_decoding_key(algorithm, &public_pem)?)
+            }
+        };
+        let mut keys = Self::new(settings.kid.clone(), algorithm, encoding_key, decoding_key);
+        for retired in &settings.retired_keys {
+            let algorithm = parse_algorithm(&retired.algorithm)?;
+            let key = match algorithm {
+                Algorithm::HS256 => DecodingKey::from_secret(retired.key.as_bytes()),
+                _ => pem_decoding_key(algorithm, &fs::read(&retired.key)?)?,
+            };
+            keys.retire(&retired.kid, algorithm, key)?;
+        }
+        info!(
+            "JWT 密钥加载完成，当前密钥: {} [{:?}]，旧密钥数: {}",
+            keys.kid,
+            keys.algorithm,
+            keys.verify_keys.len() - 1
+        );
+        Ok(keys)
+    }
+
+    pub fn new(kid: String, algorithm: Algorithm, encoding_key: EncodingKey, decoding_key: DecodingKey) -> Self {
+        let mut verify_keys = HashMap::new();
+        verify_keys.insert(
+            kid.clone(),
+            VerifyKey {
+                algorithm,
+                key: decoding_key,
+            },
+        );
+        Self {
+            kid,
+            algorithm,
+            encoding_key,
+            verify_keys,
+        }
+    }
+
+    /// 添加只用于校验的旧密钥
+    pub fn retire(&mut self, kid: &str, algorithm: Algorithm, key: DecodingKey) -> AppResult<()> {
+        if self.verify_keys.contains_key(kid) {
+            return Err(AppError::ConfigError(format!("JWT 密钥ID重复: {}", kid)));
+        }
+        self.verify_keys.insert(String::from(kid), VerifyKey { algorithm, key });
+        Ok(())
+    }
+
+    pub fn encode(&self, user_token: &UserToken) -> AppResult<String> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+        Ok(encode(&header, user_token, &self.encoding_key)?)
+    }
+
+    /// 没有 `kid` 的 token 使用当前密钥校验
+    pub fn decode(&self, token: &str) -> AppResult<UserToken> {
+        let header = decode_header(token)?;
+        let kid = header.kid.as_deref().unwrap_or(&self.kid);
+        let verify_key = self
+            .verify_keys
+            .get(kid)
+            .ok_or(AppError::BusinessError(ErrorCode::TokenInvalid))?;
+        let token_data = decode::<UserToken>(token, &verify_key.key, &Validation::new(verify_key.algorithm))?;
+        Ok(token_data.claims)
+    }
+}
+
+fn parse_algorithm(algorithm: &str) -> AppResult<Algorithm> {
+    match algorithm {
+        "HS256" => Ok(Algorithm::HS256),
+        "RS256" => Ok(Algorithm::RS256),
+        "EdDSA" => Ok(Algorithm::EdDSA),
+        _ => Err(AppError::ConfigError(format!("不支持的 JWT 签名算法: {}", algorithm))),
+    }
+}
+
+fn read_pem(path: &Option<PathBuf>) -> AppResult<Vec<u8>> {
+    let path = path
+        .as_ref()
+        .ok_or_else(|| AppError::ConfigError(String::from("缺少 PEM 密钥文件路径")))?;
+    Ok(fs::read(path)?)
+}
+
+fn pem_decoding_key(algorithm: Algorithm, pem: &[u8]) -> AppResult<DecodingKey> {
+    match algorithm {
+        Algorithm::RS256 => Ok(DecodingKey::from_rsa_pem(pem)?),
+        _ => Ok(DecodingKey::from_ed_pem(pem)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+
+    use super::*;
+    use crate::common::settings::RetiredKey;
+    use crate::common::test_util::user_token;
+    use crate::model::user::Role;
+
+    fn hs256_settings(kid: &str, secret: &str, retired_keys: Vec<RetiredKey>) -> JwtSettings {
+        JwtSettings {
+            algorithm: String::from("HS256"),
+            kid: String::from(kid),
+            secret: Some(String::from(secret)),
+            private_key: None,
+            public_key: None,
+            retired_keys,
+        }
+    }
+
+    fn retired(kid: &str, secret: &str) -> RetiredKey {
+        RetiredKey {
+            kid: String::from(kid),
+            algorithm: String::from("HS256"),
+            key: String::from(secret),
+        }
+    }
+
+    fn claims() -> UserToken {
+        let mut claims = user_token(1, Role::User);
+        claims.exp = (Local::now().timestamp() + 60) as usize;
+        claims
+    }
+
+    fn is_config_error<T>(result: AppResult<T>) -> bool {
+        matches!(result, Err(AppError::ConfigError(_)))
+    }
+
+    #[test]
+    fn encode_writes_kid_header() {
+        let keys = JwtKeys::from_settings(&hs256_settings("2021-09", "current-secret", Vec::new())).unwrap();
+        let token = keys.encode(&claims()).unwrap();
+        let header = decode_header(&token).unwrap();
+        assert_eq!(header.kid.as_deref(), Some("2021-09"));
+        assert_eq!(header.alg, Algorithm::HS256);
+        assert_eq!(keys.decode(&token).unwrap().user_id, 1);
+    }
+
+    #[test]
+    fn retired_key_still_verifies_after_rotation() {
+        let old_keys = JwtKeys::from_settings(&hs256_settings("2021-08", "old-secret", Vec::new())).unwrap();
+        let token = old_keys.encode(&claims()).unwrap();
+
+        let rotated = hs256_settings("2021-09", "new-secret", vec![retired("2021-08", "old-secret")]);
+        let new_keys = JwtKeys::from_settings(&rotated).unwrap();
+        assert_eq!(new_keys.decode(&token).unwrap().user_id, 1);
+        // 新签发的 token 使用当前密钥
+        let header = decode_header(&new_keys.encode(&claims()).unwrap()).unwrap();
+        assert_eq!(header.kid.as_deref(), Some("2021-09"));
+
+        // 旧密钥移除后，旧 token 失效
+        let new_keys = JwtKeys::from_settings(&hs256_settings("2021-09", "new-secret", Vec::new())).unwrap();
+        let result = new_keys.decode(&token);
+        assert!(matches!(result, Err(AppError::BusinessError(ErrorCode::TokenInvalid))));
+    }
+
+    #[test]
+    fn decode_rejects_wrong_secret_for_kid() {
+        let forged = JwtKeys::from_settings(&hs256_settings("2021-09", "guessed-secret", Vec::new())).unwrap();
+        let token = forged.encode(&claims()).unwrap();
+        let keys = JwtKeys::from_settings(&hs256_settings("2021-09", "current-secret", Vec::new())).unwrap();
+        assert!(matches!(keys.decode(&token), Err(AppError::JWTError(_))));
+    }
+
+    #[test]
+    fn token_without_kid_uses_current_key() {
+        let keys = JwtKeys::from_settings(&hs256_settings("2021-09", "current-secret", Vec::new())).unwrap();
+        let token = encode(
+            &Header::default(),
+            &claims(),
+            &EncodingKey::from_secret(b"current-secret"),
+        )
+        .unwrap();
+        assert_eq!(keys.decode(&token).unwrap().user_id, 1);
+    }
+
+    #[test]
+    fn invalid_key_settings_are_config_errors() {
+        let duplicated = hs256_settings("2021-09", "current-secret", vec![retired("2021-09", "old-secret")]);
+        assert!(is_config_error(JwtKeys::from_settings(&duplicated)));
+
+        let mut unsupported = hs256_settings("2021-09", "current-secret", Vec::new());
+        unsupported.algorithm = String::from("HS512");
+        assert!(is_config_error(JwtKeys::from_settings(&unsupported)));
+
+        let mut missing_pem = hs256_settings("2021-09", "current-secret", Vec::new());
+        missing_pem.algorithm = String::from("RS256");
+        assert!(is_config_error(JwtKeys::from_settings(&missing_pem)));
+    }
+}
@@ -0,0 +1,95 @@
+use std::convert::Infallible;
+
+use axum::extract::{FromRequest, RequestParts};
+use axum::http::header::ACCEPT_LANGUAGE;
+
+/// 支持的语言，根据请求头 `Accept-Language` 选择，默认为简体中文
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Locale {
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+    #[serde(rename = "en")]
+    En,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::ZhCn
+    }
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::ZhCn, Locale::En];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::ZhCn => "zh-CN",
+            Locale::En => "en",
+        }
+    }
+
+    /// 解析单个语言标签，例如 `zh-CN`、`en-US`
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.trim().to_ascii_lowercase();
+        if tag.starts_with("zh") {
+            Some(Locale::ZhCn)
+        } else if tag.starts_with("en") {
+            Some(Locale::En)
+        } else {
+            None
+        }
+    }
+
+    /// 按 `q` 值从高到低选出第一个支持的语言，例如 `en-US,en;q=0.9,zh-CN;q=0.8` 选择英文
+    pub fn from_accept_language(value: &str) -> Self {
+        let mut best: Option<(Locale, f32)> = None;
+        for item in value.split(',') {
+            let mut parts = item.split(';');
+            let locale = match parts.next().and_then(Locale::from_tag) {
+                Some(locale) => locale,
+                None => continue,
+            };
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if best.map_or(true, |(_, best_q)| q > best_q) {
+                best = Some((locale, q));
+            }
+        }
+        best.map(|(locale, _)| locale).unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for Locale
+where
+    B: Send,
+{
+    type Rejection = Infallible;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        Ok(req
+            .headers()
+            .and_then(|headers| headers.get(ACCEPT_LANGUAGE))
+            .and_then(|value| value.to_str().ok())
+            .map(Locale::from_accept_language)
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_language_picks_highest_quality() {
+        assert_eq!(Locale::from_accept_language("en-US,en;q=0.9,zh-CN;q=0.8"), Locale::En);
+        assert_eq!(Locale::from_accept_language("en;q=0.5, zh-TW;q=0.8"), Locale::ZhCn);
+        // 不支持的语言跳过，都不支持时使用默认语言
+        assert_eq!(Locale::from_accept_language("fr-FR, en;q=0.1"), Locale::En);
+        assert_eq!(Locale::from_accept_language("fr-FR, de"), Locale::ZhCn);
+        assert_eq!(Locale::from_accept_language(""), Locale::ZhCn);
+        assert_eq!(Locale::from_accept_language("en;q=abc"), Locale::En);
+    }
+}
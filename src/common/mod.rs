@@ -2,4 +2,12 @@ pub mod api;
 pub mod constant;
 pub mod date_format;
 pub mod err;
+pub mod jwt;
+pub mod locale;
+pub mod notice_hub;
+pub mod settings;
+#[cfg(test)]
+pub mod test_util;
 pub mod util;
+pub mod validate;
+pub mod verify_code;
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use redis::aio::ConnectionManager;
+use redis::Client;
+use tokio::sync::broadcast;
+
+use crate::common::err::AppError;
+use crate::model::notice::Notice;
+use crate::AppResult;
+
+/// 新通知发布到的 Redis 频道
+const NOTICE_CHANNEL: &str = "whatsoo:notice";
+
+/// 本实例内缓存的待推送通知数，订阅者处理过慢时会丢弃最早的通知
+const NOTICE_BUFFER_SIZE: usize = 1024;
+
+/// Redis 订阅断开后重连的间隔
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// 通知实时推送中心
+///
+/// 新通知通过 Redis pub/sub 广播给所有服务实例，每个实例再把通知分发给本机上的推送连接，
+/// 这样多实例部署在负载均衡后面时，用户连接到任意实例都能收到通知。
+#[derive(Clone)]
+pub struct NoticeHub {
+    client: Client,
+    /// 发布通知使用的异步连接
+    conn: ConnectionManager,
+    sender: broadcast::Sender<Arc<Notice>>,
+}
+
+impl NoticeHub {
+    pub async fn new(client: Client) -> AppResult<Self> {
+        let (sender, _) = broadcast::channel(NOTICE_BUFFER_SIZE);
+        let conn = ConnectionManager::new(client.clone()).await?;
+        Ok(Self { client, conn, sender })
+    }
+
+    /// 启动后台线程订阅 Redis 频道，连接断开后自动重连
+    pub fn start(&self) {
+        let hub = self.clone();
+        std::thread::Builder::new()
+            .name(String::from("notice-subscriber"))
+            .spawn(move || {
+                loop {
+                    if let Err(e) = hub.listen() {
+                        error!("通知订阅连接断开，稍后重连，错误信息: {}", e.to_string());
+                    }
+                    std::thread::sleep(RESUBSCRIBE_INTERVAL);
+                }
+            })
+            .expect("通知订阅线程启动失败");
+    }
+
+    fn listen(&self) -> AppResult<()> {
+        // 订阅会独占连接，因此使用单独的同步连接
+        let mut conn = self.client.get_connection()?;
+        let mut pubsub = conn.as_pubsub();
+        pubsub.subscribe(NOTICE_CHANNEL)?;
+        info!("已订阅通知频道: {}", NOTICE_CHANNEL);
+        loop {
+            let payload: String = pubsub.get_message()?.get_payload()?;
+            match serde_json::from_str::<Notice>(&payload) {
+                // 没有推送连接时发送会失败，直接忽略
+                Ok(notice) => {
+                    let _ = self.sender.send(Arc::new(notice));
+                }
+                Err(e) => error!("通知消息解析失败: {}，消息内容: {}", e.to_string(), payload),
+            }
+        }
+    }
+
+    /// 发布通知到所有服务实例
+    pub async fn publish(&self, notice: &Notice) -> AppResult<()> {
+        let payload = serde_json::to_string(notice)?;
+        redis::cmd("PUBLISH")
+            .arg(NOTICE_CHANNEL)
+            .arg(payload)
+            .query_async::<_, i64>(&mut self.conn.clone())
+            .await
+            .map_err(AppError::RedisGetError)?;
+        Ok(())
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Notice>> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,472 @@
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use config::{Config, Environment, File, FileFormat};
+use uuid::Uuid;
+
+use crate::common::constant::MAX_AVATAR_LENGTH;
+use crate::common::err::AppError;
+use crate::model::upload::ImageKind;
+use crate::AppResult;
+
+/// 指定配置文件路径的环境变量
+const CONFIG_FILE_ENV: &str = "WHATSOO_CONFIG";
+
+const DEFAULT_CONFIG_FILE: &str = "whatsoo.toml";
+
+/// HS256 密钥的最小长度，过短的密钥容易被暴力破解
+const MIN_JWT_SECRET_LENGTH: usize = 32;
+
+/// `.env.example` 中的占位密钥，所有人都知道，不能用于签发 token
+const JWT_SECRET_PLACEHOLDER: &str = "change-me-to-a-random-secret-of-at-least-32-characters";
+
+/// 应用配置
+///
+/// 先读取 TOML 配置文件（默认为 `whatsoo.toml`，可通过 `WHATSOO_CONFIG` 指定），
+/// 再用 `WHATSOO_` 开头的环境变量覆盖，层级之间用 `__` 分隔，例如 `WHATSOO_SMTP__PASSWORD`。
+/// 未设置 `WHATSOO_DATABASE__URL` 时使用 sqlx 编译期检查同样使用的 `DATABASE_URL`。
+#[derive(Debug, Deserialize)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub redis: RedisSettings,
+    pub cache: CacheSettings,
+    pub smtp: SmtpSettings,
+    pub mail: MailSettings,
+    pub jwt: JwtSettings,
+    pub rate_limit: RateLimitSettings,
+    pub upload: UploadSettings,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ServerSettings {
+    pub host: String,
+    pub port: u16,
+    /// 请求超时时间，单位为秒
+    pub timeout_secs: u64,
+    /// 同时处理的最大请求数
+    pub concurrency_limit: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DatabaseSettings {
+    pub backend: DatabaseBackend,
+    pub url: String,
+    /// 启动时自动执行未应用的迁移，关闭后需要手动执行 `whatsoo migrate`
+    pub auto_migrate: bool,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// 连接最长存活时间，单位为秒
+    pub max_lifetime_secs: u64,
+    /// 空闲连接回收时间，单位为秒
+    pub idle_timeout_secs: u64,
+}
+
+/// 数据存储，`memory` 的数据保存在进程内，重启后丢失，只适合开发和测试
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    Mysql,
+    Memory,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RedisSettings {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CacheSettings {
+    pub backend: CacheBackend,
+}
+
+/// 缓存实现，`memory` 的数据不在多个实例之间共享，只适合单实例部署和开发环境
+///
+/// 只替换验证码、会话、限流计数等缓存数据，邮件发件箱和通知推送仍然使用 `redis.url` 连接的 Redis。
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    Redis,
+    Memory,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SmtpSettings {
+    pub server: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MailSettings {
+    pub transport: MailTransportKind,
+    /// 发件人地址
+    pub from: String,
+    /// 邮件模板目录，每种语言一个子目录
+    pub template_dir: PathBuf,
+    /// `file` 发送方式保存邮件的目录
+    pub file_dir: PathBuf,
+    /// 发送线程数
+    pub workers: usize,
+    /// 最多发送次数，超过后移入死信列表
+    pub max_attempts: u32,
+    /// 第一次重试的等待时间，之后每次翻倍，单位为秒
+    pub retry_base_secs: u64,
+    /// 重试等待时间的上限，单位为秒
+    pub retry_max_secs: u64,
+    /// 未读通知汇总邮件的发送间隔，单位为秒，0 表示不发送
+    pub digest_interval_secs: u64,
+}
+
+/// 邮件发送方式，`file` 和 `stdout` 用于没有邮件服务器的开发和测试环境
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransportKind {
+    Smtp,
+    File,
+    Stdout,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JwtSettings {
+    /// `HS256`、`RS256` 或 `EdDSA`
+    pub algorithm: String,
+    /// 当前签名密钥的ID
+    pub kid: String,
+    /// HS256 密钥
+    pub secret: Option<String>,
+    /// RS256 / EdDSA 的 PEM 私钥文件路径
+    pub private_key: Option<PathBuf>,
+    /// RS256 / EdDSA 的 PEM 公钥文件路径
+    pub public_key: Option<PathBuf>,
+    /// 只用于校验的旧密钥，轮换密钥后已签发的 token 在过期前仍然有效
+    #[serde(default)]
+    pub retired_keys: Vec<RetiredKey>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetiredKey {
+    pub kid: String,
+    pub algorithm: String,
+    /// HS256 为密钥本身，RS256 / EdDSA 为 PEM 公钥文件路径
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RateLimitSettings {
+    /// 关闭后不做任何限流，仅用于开发和测试环境
+    pub enabled: bool,
+    /// 客户端与服务之间的反向代理层数，大于0时从 `X-Forwarded-For` 读取客户端IP，为0时使用连接的对端地址
+    pub trusted_proxies: usize,
+    /// 连续登录失败多少次后锁定账号
+    pub login_max_failures: u32,
+    /// 账号锁定时间，同时也是登录失败次数的统计时间，单位为秒
+    pub login_lockout_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadSettings {
+    pub backend: StorageBackend,
+    /// `local` 存储保存文件的目录
+    pub dir: PathBuf,
+    /// 上传文件的访问地址前缀，`local` 存储对应 `/uploads` 路由，使用 CDN 时改为 CDN 的地址
+    pub public_url: String,
+    /// 单个文件的大小上限，单位为字节
+    pub max_file_size: usize,
+    /// 图片宽高的上限，超过时不解码，避免超大图片耗尽内存
+    pub max_image_dimension: u32,
+    /// 图片总像素数的上限，宽高都没有超限的图片也可能解码出很大的位图
+    pub max_image_pixels: u64,
+}
+
+/// 上传文件的存储位置
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Local,
+}
+
+impl Settings {
+    pub fn load() -> AppResult<Self> {
+        let path = env::var(CONFIG_FILE_ENV).unwrap_or_else(|_| String::from(DEFAULT_CONFIG_FILE));
+        let database_url = env::var("WHATSOO_DATABASE__URL")
+            .or_else(|_| env::var("DATABASE_URL"))
+            .ok();
+        let settings = Config::builder()
+            .add_source(File::new(&path, FileFormat::Toml))
+            .add_source(
+                Environment::with_prefix("WHATSOO")
+                    .prefix_separator("_")
+                    .separator("__"),
+            )
+            .set_override_option("database.url", database_url)
+            .and_then(|builder| builder.build())
+            .and_then(|config| config.try_deserialize::<Settings>())
+            .map_err(|e| AppError::ConfigError(format!("读取配置文件 {} 失败: {}", path, e)))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// 校验所有配置项，把发现的问题一次性全部返回
+    fn validate(&self) -> AppResult<()> {
+        let mut errors = Vec::new();
+        if self.server.host.parse::<IpAddr>().is_err() {
+            errors.push(format!("server.host 不是合法的IP地址: {}", self.server.host));
+        }
+        if self.server.port == 0 {
+            errors.push(String::from("server.port 不能为0"));
+        }
+        if self.server.timeout_secs == 0 {
+            errors.push(String::from("server.timeout_secs 必须大于0"));
+        }
+        if self.server.concurrency_limit == 0 {
+            errors.push(String::from("server.concurrency_limit 必须大于0"));
+        }
+        if self.database.backend == DatabaseBackend::Mysql && self.database.url.is_empty() {
+            errors.push(String::from("database.url 不能为空"));
+        }
+        if self.database.max_connections == 0 || self.database.min_connections > self.database.max_connections {
+            errors.push(String::from(
+                "database.max_connections 必须大于0且不小于 min_connections",
+            ));
+        }
+        if self.redis.url.is_empty() {
+            errors.push(String::from("redis.url 不能为空"));
+        }
+        if self.mail.transport == MailTransportKind::Smtp && self.smtp.server.is_empty() {
+            errors.push(String::from("smtp.server 不能为空"));
+        }
+        self.mail.validate(&mut errors);
+        self.jwt.validate(&mut errors);
+        self.upload.validate(&mut errors);
+        if self.rate_limit.login_max_failures == 0 {
+            errors.push(String::from("rate_limit.login_max_failures 必须大于0"));
+        }
+        if self.rate_limit.login_lockout_secs == 0 {
+            errors.push(String::from("rate_limit.login_lockout_secs 必须大于0"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::ConfigError(errors.join("; ")))
+        }
+    }
+}
+
+impl ServerSettings {
+    pub fn addr(&self) -> SocketAddr {
+        // 启动时已校验过
+        SocketAddr::new(self.host.parse().unwrap(), self.port)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+impl MailSettings {
+    fn validate(&self, errors: &mut Vec<String>) {
+        if !crate::MAILE_RE.is_match(&self.from) {
+            errors.push(format!("mail.from 不是合法的邮箱地址: {}", self.from));
+        }
+        if self.workers == 0 {
+            errors.push(String::from("mail.workers 必须大于0"));
+        }
+        if self.max_attempts == 0 {
+            errors.push(String::from("mail.max_attempts 必须大于0"));
+        }
+        if self.retry_base_secs == 0 || self.retry_max_secs < self.retry_base_secs {
+            errors.push(String::from("mail.retry_base_secs 必须大于0且不大于 retry_max_secs"));
+        }
+    }
+}
+
+impl UploadSettings {
+    fn validate(&self, errors: &mut Vec<String>) {
+        if !self.public_url.starts_with("http://") && !self.public_url.starts_with("https://") {
+            errors.push(format!("upload.public_url 不是合法的地址: {}", self.public_url));
+        }
+        if self.max_file_size == 0 {
+            errors.push(String::from("upload.max_file_size 必须大于0"));
+        }
+        if self.max_image_dimension == 0 {
+            errors.push(String::from("upload.max_image_dimension 必须大于0"));
+        }
+        if self.max_image_pixels == 0 {
+            errors.push(String::from("upload.max_image_pixels 必须大于0"));
+        }
+        // 头像地址保存在用户表中，按最长的文件名估算，避免上传成功后无法设置为头像
+        let avatar_url = format!(
+            "{}/{}/yyyymm/{}_{}.jpg",
+            self.public_url.trim_end_matches('/'),
+            ImageKind::Avatar.dir(),
+            Uuid::nil().to_simple(),
+            ImageKind::Avatar.max_size()
+        );
+        if avatar_url.chars().count() > MAX_AVATAR_LENGTH {
+            errors.push(format!(
+                "upload.public_url 过长，生成的头像地址超过{}个字符",
+                MAX_AVATAR_LENGTH
+            ));
+        }
+    }
+}
+
+impl JwtSettings {
+    fn validate(&self, errors: &mut Vec<String>) {
+        match self.algorithm.as_str() {
+            "HS256" => match &self.secret {
+                Some(secret) if secret == JWT_SECRET_PLACEHOLDER => errors.push(String::from(
+                    "jwt.secret 仍是 .env.example 中的占位值，请替换为随机生成的密钥",
+                )),
+                Some(secret) if secret.len() >= MIN_JWT_SECRET_LENGTH => {}
+                Some(_) => errors.push(format!("jwt.secret 长度不能少于{}个字符", MIN_JWT_SECRET_LENGTH)),
+                None => errors.push(String::from("jwt.secret 未设置，请通过 WHATSOO_JWT__SECRET 设置")),
+            },
+            "RS256" | "EdDSA" => {
+                for &(name, path) in &[
+                    ("jwt.private_key", &self.private_key),
+                    ("jwt.public_key", &self.public_key),
+                ] {
+                    match path {
+                        Some(path) if path.is_file() => {}
+                        Some(path) => errors.push(format!("{} 文件不存在: {}", name, path.display())),
+                        None => errors.push(format!("{} 不能为空", name)),
+                    }
+                }
+            }
+            algorithm => errors.push(format!("jwt.algorithm 不支持: {}", algorithm)),
+        }
+        for key in &self.retired_keys {
+            if key.kid == self.kid {
+                errors.push(format!("jwt.retired_keys 中的密钥ID与当前密钥重复: {}", key.kid));
+            }
+            match key.algorithm.as_str() {
+                "HS256" if key.key == JWT_SECRET_PLACEHOLDER => {
+                    errors.push(format!("jwt.retired_keys 中密钥 {} 仍是占位值", key.kid))
+                }
+                "HS256" if key.key.len() < MIN_JWT_SECRET_LENGTH => errors.push(format!(
+                    "jwt.retired_keys 中密钥 {} 的长度不能少于{}个字符",
+                    key.kid, MIN_JWT_SECRET_LENGTH
+                )),
+                "HS256" | "RS256" | "EdDSA" => {}
+                algorithm => errors.push(format!(
+                    "jwt.retired_keys 中密钥 {} 的算法不支持: {}",
+                    key.kid, algorithm
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_SECRET: &str = "whatsoo-test-secret-of-at-least-32-characters";
+
+    /// 仓库中的 `whatsoo.toml`，补上部署时通过环境变量设置的配置项
+    fn settings() -> Settings {
+        Config::builder()
+            .add_source(File::from_str(include_str!("../../whatsoo.toml"), FileFormat::Toml))
+            .set_override("database.url", "mysql://root@127.0.0.1/whatsoo")
+            .and_then(|builder| builder.set_override("jwt.secret", TEST_SECRET))
+            .and_then(|builder| builder.build())
+            .and_then(|config| config.try_deserialize::<Settings>())
+            .unwrap()
+    }
+
+    fn validate_errors(settings: &Settings) -> String {
+        match settings.validate() {
+            Err(AppError::ConfigError(errors)) => errors,
+            _ => String::new(),
+        }
+    }
+
+    fn retired(kid: &str, key: &str) -> RetiredKey {
+        RetiredKey {
+            kid: String::from(kid),
+            algorithm: String::from("HS256"),
+            key: String::from(key),
+        }
+    }
+
+    #[test]
+    fn shipped_config_is_valid() {
+        assert!(settings().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_collects_all_errors() {
+        let mut settings = settings();
+        settings.server.host = String::from("localhost");
+        settings.server.port = 0;
+        settings.mail.workers = 0;
+        settings.rate_limit.login_max_failures = 0;
+        let errors = validate_errors(&settings);
+        for field in &[
+            "server.host",
+            "server.port",
+            "mail.workers",
+            "rate_limit.login_max_failures",
+        ] {
+            assert!(errors.contains(field), "{}", errors);
+        }
+    }
+
+    #[test]
+    fn database_url_is_only_required_for_mysql() {
+        let mut settings = settings();
+        settings.database.url = String::new();
+        assert!(validate_errors(&settings).contains("database.url"));
+        settings.database.backend = DatabaseBackend::Memory;
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn jwt_secret_must_be_set_and_strong() {
+        let mut settings = settings();
+        for secret in &[None, Some("too-short"), Some(JWT_SECRET_PLACEHOLDER)] {
+            settings.jwt.secret = secret.map(String::from);
+            assert!(validate_errors(&settings).contains("jwt.secret"), "{:?}", secret);
+        }
+    }
+
+    #[test]
+    fn jwt_asymmetric_keys_must_exist() {
+        let mut settings = settings();
+        settings.jwt.algorithm = String::from("RS256");
+        settings.jwt.private_key = Some(PathBuf::from("keys/missing.pem"));
+        let errors = validate_errors(&settings);
+        assert!(errors.contains("jwt.private_key 文件不存在"), "{}", errors);
+        assert!(errors.contains("jwt.public_key 不能为空"), "{}", errors);
+    }
+
+    #[test]
+    fn jwt_retired_keys_are_checked() {
+        let mut settings = settings();
+        settings.jwt.retired_keys = vec![retired("2021-01", TEST_SECRET)];
+        assert!(settings.validate().is_ok());
+
+        settings.jwt.retired_keys = vec![
+            retired(&settings.jwt.kid, TEST_SECRET),
+            retired("2021-02", "too-short"),
+            retired("2021-03", JWT_SECRET_PLACEHOLDER),
+        ];
+        let errors = validate_errors(&settings);
+        assert!(errors.contains("密钥ID与当前密钥重复"), "{}", errors);
+        assert!(errors.contains("2021-02 的长度"), "{}", errors);
+        assert!(errors.contains("2021-03 仍是占位值"), "{}", errors);
+    }
+
+    #[test]
+    fn upload_public_url_must_fit_avatar_column() {
+        let mut settings = settings();
+        settings.upload.public_url = String::from("ftp://127.0.0.1/uploads");
+        assert!(validate_errors(&settings).contains("upload.public_url 不是合法的地址"));
+        settings.upload.public_url = format!("https://{}.whatsoo.org/uploads", "a".repeat(MAX_AVATAR_LENGTH));
+        assert!(validate_errors(&settings).contains("upload.public_url 过长"));
+    }
+}
@@ -0,0 +1,76 @@
+//! 各模块单元测试共用的数据和辅助函数
+
+use crate::common::api::Pagination;
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::locale::Locale;
+use crate::common::settings::RateLimitSettings;
+use crate::model::topic::TopicFront;
+use crate::model::user::{RegisterUser, Role, User, UserToken};
+use crate::repository::Repositories;
+use crate::AppResult;
+
+pub const EMAIL: &str = "tester@whatsoo.org";
+
+pub fn rate_limit_settings() -> RateLimitSettings {
+    RateLimitSettings {
+        enabled: true,
+        trusted_proxies: 0,
+        login_max_failures: 3,
+        login_lockout_secs: 60,
+    }
+}
+
+/// 直接写入仓库的用户，不经过注册流程，密码为空
+pub async fn create_user(username: &str, email: &str, repos: &Repositories) -> User {
+    let user_id = repos
+        .users
+        .insert_one_user(RegisterUser {
+            uk_username: String::from(username),
+            uk_email: String::from(email),
+            email_verify_code: String::new(),
+            user_password: String::new(),
+            locale: Locale::default(),
+        })
+        .await
+        .unwrap();
+    repos.users.find_user_by_id(user_id).await.unwrap()
+}
+
+/// 业务错误的错误码，成功或其他错误返回 `None`
+pub fn error_code<T>(result: AppResult<T>) -> Option<ErrorCode> {
+    match result {
+        Err(AppError::BusinessError(code)) => Some(code),
+        _ => None,
+    }
+}
+
+/// 指定用户和角色的登录信息，不需要对应的用户记录
+pub fn user_token(user_id: u64, role: Role) -> UserToken {
+    UserToken {
+        user_id,
+        uk_username: format!("user{}", user_id),
+        email: format!("user{}@whatsoo.org", user_id),
+        role,
+        ver: 0,
+        sid: String::new(),
+        exp: 0,
+    }
+}
+
+/// 直接写入仓库的主题，不关联标签
+pub async fn create_topic(user_id: u64, title: &str, repos: &Repositories) -> u64 {
+    let topic = TopicFront {
+        user_id: Some(user_id),
+        title: String::from(title),
+        content: format!("{}的内容", title),
+        tags: Vec::new(),
+    };
+    repos.topics.insert_one_topic(&topic).await.unwrap()
+}
+
+pub fn pagination(current_page: u32, page_size: u32) -> Pagination {
+    Pagination {
+        current_page,
+        page_size,
+    }
+}
@@ -0,0 +1,377 @@
+use std::ops::Deref;
+
+use axum::body::HttpBody;
+use axum::extract::{Form, FromRequest, RequestParts};
+use axum::Json;
+use serde::de::DeserializeOwned;
+use tower::BoxError;
+
+use crate::common::constant::{MAX_EMAIL_LENGTH, MAX_GITHUB_UID_LENGTH, MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH};
+use crate::common::err::{AppError, ErrorCode, FieldError};
+use crate::AppResult;
+
+/// 请求参数校验，配合 [`ValidatedForm`] 和 [`ValidatedJson`] 提取器使用
+pub trait Validate {
+    /// 校验前去掉文本字段的首尾空白，保存的也是去掉空白后的值
+    fn trim(&mut self) {}
+
+    fn validate(&self) -> AppResult<()>;
+}
+
+pub fn trim_string(value: &mut String) {
+    let trimmed = value.trim();
+    if trimmed.len() != value.len() {
+        *value = String::from(trimmed);
+    }
+}
+
+/// 收集所有字段的校验错误，最后一次性返回，每个字段只保留第一个错误
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(&mut self, field: &'static str, valid: bool, code: ErrorCode) -> &mut Self {
+        if !valid && !self.errors.iter().any(|e| e.field == field) {
+            self.errors.push(FieldError::new(field, code));
+        }
+        self
+    }
+
+    /// 去掉首尾空白后的字符数在 `[min, max]` 之间，与 MySQL varchar 一样按字符而不是字节计算
+    pub fn length(&mut self, field: &'static str, value: &str, min: usize, max: usize, code: ErrorCode) -> &mut Self {
+        let length = value.trim().chars().count();
+        self.check(field, (min..=max).contains(&length), code)
+    }
+
+    pub fn required(&mut self, field: &'static str, value: &str) -> &mut Self {
+        self.check(field, !value.trim().is_empty(), ErrorCode::Required)
+    }
+
+    pub fn email(&mut self, field: &'static str, value: &str) -> &mut Self {
+        let valid = value.len() <= MAX_EMAIL_LENGTH && crate::MAILE_RE.is_match(value);
+        self.check(field, valid, ErrorCode::InvalidEmail)
+    }
+
+    pub fn username(&mut self, field: &'static str, value: &str) -> &mut Self {
+        self.check(field, crate::USERNAME_RE.is_match(value), ErrorCode::InvalidUsername)
+    }
+
+    pub fn github_uid(&mut self, field: &'static str, value: &str) -> &mut Self {
+        let valid = value.len() <= MAX_GITHUB_UID_LENGTH && crate::GITHUB_UID_RE.is_match(value);
+        self.check(field, valid, ErrorCode::InvalidGithubUid)
+    }
+
+    /// 密码需要同时包含字母和数字
+    pub fn password(&mut self, field: &'static str, value: &str) -> &mut Self {
+        let length = value.chars().count();
+        let valid = (MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&length)
+            && value.chars().any(|c| c.is_ascii_alphabetic())
+            && value.chars().any(|c| c.is_ascii_digit());
+        self.check(field, valid, ErrorCode::WeakPassword)
+    }
+
+    pub fn url(&mut self, field: &'static str, value: &str, max: usize) -> &mut Self {
+        let valid = (value.starts_with("https://") || value.starts_with("http://"))
+            && value.len() <= max
+            && !value.chars().any(char::is_whitespace);
+        self.check(field, valid, ErrorCode::InvalidUrl)
+    }
+
+    pub fn finish(&mut self) -> AppResult<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::ValidationError(std::mem::take(&mut self.errors)))
+        }
+    }
+}
+
+/// 解析表单并校验，解析失败返回 400，校验失败返回 422 和每个字段的错误
+#[derive(Debug)]
+pub struct ValidatedForm<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for ValidatedForm<T>
+where
+    T: DeserializeOwned + Validate,
+    B: HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Form(mut value) = Form::<T>::from_request(req).await.map_err(|e| {
+            info!("表单解析失败: {:?}", e);
+            AppError::BusinessError(ErrorCode::BadRequest)
+        })?;
+        value.trim();
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
+impl<T> Deref for ValidatedForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// 解析 JSON 并校验，解析失败返回 400，校验失败返回 422 和每个字段的错误
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    B: HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Json(mut value) = Json::<T>::from_request(req).await.map_err(|e| {
+            info!("JSON 解析失败: {:?}", e);
+            AppError::BusinessError(ErrorCode::BadRequest)
+        })?;
+        value.trim();
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::response::IntoResponse;
+
+    use super::*;
+    use crate::common::constant::{MAX_INTRODUCE_LENGTH, MAX_TOPIC_CONTENT_LENGTH, MAX_TOPIC_TITLE_LENGTH};
+    use crate::model::topic::TopicFront;
+    use crate::model::user::ProfileFront;
+
+    fn field_errors(result: AppResult<()>) -> Vec<(&'static str, ErrorCode)> {
+        match result {
+            Ok(()) => Vec::new(),
+            Err(AppError::ValidationError(errors)) => errors.into_iter().map(|e| (e.field, e.code)).collect(),
+            Err(e) => panic!("不是校验错误: {:?}", e),
+        }
+    }
+
+    fn username_valid(username: &str) -> bool {
+        Validator::new().username("username", username).finish().is_ok()
+    }
+
+    fn password_valid(password: &str) -> bool {
+        Validator::new().password("password", password).finish().is_ok()
+    }
+
+    #[test]
+    fn username_length_boundaries() {
+        assert!(!username_valid(&"a".repeat(4)));
+        assert!(username_valid(&"a".repeat(5)));
+        assert!(username_valid(&"a".repeat(20)));
+        assert!(!username_valid(&"a".repeat(21)));
+    }
+
+    #[test]
+    fn username_must_match_whole_value() {
+        assert!(username_valid("tester_01-a"));
+        for username in &["tester name", "tester\n", "user@evil", "测试用户名称", "<script>tester"] {
+            assert!(!username_valid(username), "{:?}", username);
+        }
+        // 合法的部分不能让整个用户名通过校验
+        assert!(!username_valid(&format!("tester{}", "!".repeat(20))));
+    }
+
+    #[test]
+    fn password_rules() {
+        assert!(password_valid("abcdefg1"));
+        assert!(!password_valid("abcdef1"));
+        assert!(!password_valid("abcdefgh"));
+        assert!(!password_valid("12345678"));
+        assert!(password_valid(&format!("{}1", "a".repeat(MAX_PASSWORD_LENGTH - 1))));
+        assert!(!password_valid(&format!("{}1", "a".repeat(MAX_PASSWORD_LENGTH))));
+    }
+
+    fn profile(blog_url: &str, introduce: &str, github_uid: &str) -> ProfileFront {
+        ProfileFront {
+            avatar: None,
+            blog_url: Some(String::from(blog_url)),
+            introduce: Some(String::from(introduce)),
+            github_uid: Some(String::from(github_uid)),
+        }
+    }
+
+    #[test]
+    fn profile_rules() {
+        let introduce = "介".repeat(MAX_INTRODUCE_LENGTH);
+        assert!(field_errors(profile("https://whatsoo.org", &introduce, "nova-me").validate()).is_empty());
+        // 空字符串按未填写处理
+        assert!(field_errors(profile("  ", "", "").validate()).is_empty());
+        let introduce = "介".repeat(MAX_INTRODUCE_LENGTH + 1);
+        assert_eq!(
+            field_errors(profile("javascript:alert(1)", &introduce, "-nova").validate()),
+            vec![
+                ("blog_url", ErrorCode::InvalidUrl),
+                ("introduce", ErrorCode::IntroduceLength),
+                ("github_uid", ErrorCode::InvalidGithubUid)
+            ]
+        );
+    }
+
+    fn topic(title: &str, content: &str) -> TopicFront {
+        TopicFront {
+            user_id: None,
+            title: String::from(title),
+            content: String::from(content),
+            tags: vec![1],
+        }
+    }
+
+    #[test]
+    fn topic_length_counts_chars_not_bytes() {
+        let title = "标".repeat(MAX_TOPIC_TITLE_LENGTH);
+        let content = "😀".repeat(MAX_TOPIC_CONTENT_LENGTH);
+        assert!(field_errors(topic(&title, &content).validate()).is_empty());
+        let title = "标".repeat(MAX_TOPIC_TITLE_LENGTH + 1);
+        let content = "😀".repeat(MAX_TOPIC_CONTENT_LENGTH + 1);
+        assert_eq!(
+            field_errors(topic(&title, &content).validate()),
+            vec![
+                ("title", ErrorCode::TitleLength),
+                ("content", ErrorCode::TopicContentLength)
+            ]
+        );
+        // 只有空白的标题按空计算
+        assert_eq!(
+            field_errors(topic("   ", "内容").validate()),
+            vec![("title", ErrorCode::TitleLength)]
+        );
+    }
+
+    #[test]
+    fn topic_tags_fit_in_column() {
+        let mut front = topic("标题", "内容");
+        front.tags = vec![1, 2, 3, 4, 5];
+        assert!(field_errors(front.validate()).is_empty());
+        // 标签数量没有超过限制，但拼接后超出主题表标签字段的长度
+        front.tags = vec![u64::MAX, u64::MAX, u64::MAX];
+        assert_eq!(
+            field_errors(front.validate()),
+            vec![("tags", ErrorCode::TopicTooManyTags)]
+        );
+    }
+
+    #[tokio::test]
+    async fn validated_json_saves_trimmed_values() {
+        let body = r#"{"title":"  标题 ","content":"\n 内容\t","tags":[1]}"#;
+        let mut req = request(JSON, body);
+        let ValidatedJson(front) = ValidatedJson::<TopicFront>::from_request(&mut req).await.unwrap();
+        assert_eq!(front.title, "标题");
+        assert_eq!(front.content, "内容");
+    }
+
+    #[test]
+    fn validator_keeps_first_error_per_field() {
+        let result = Validator::new()
+            .required("email", "")
+            .email("email", "")
+            .password("password", "short")
+            .finish();
+        assert_eq!(
+            field_errors(result),
+            vec![("email", ErrorCode::Required), ("password", ErrorCode::WeakPassword)]
+        );
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Signup {
+        username: String,
+        password: String,
+    }
+
+    impl Validate for Signup {
+        fn validate(&self) -> AppResult<()> {
+            Validator::new()
+                .username("username", &self.username)
+                .password("password", &self.password)
+                .finish()
+        }
+    }
+
+    fn request(content_type: &str, body: &str) -> RequestParts<Body> {
+        let request = Request::post("/signup")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(String::from(body)))
+            .unwrap();
+        RequestParts::new(request)
+    }
+
+    async fn rejection(error: AppError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let body = response.into_body().data().await.unwrap().unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    const FORM: &str = "application/x-www-form-urlencoded";
+    const JSON: &str = "application/json";
+
+    #[tokio::test]
+    async fn validated_form_rejections() {
+        let mut req = request(FORM, "username=tester&password=abcdefg1");
+        let form = ValidatedForm::<Signup>::from_request(&mut req).await.unwrap();
+        assert_eq!(form.username, "tester");
+
+        // 缺少字段无法解析，返回 400
+        let mut req = request(FORM, "username=tester");
+        let error = ValidatedForm::<Signup>::from_request(&mut req).await.unwrap_err();
+        let (status, body) = rejection(error).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "BAD_REQUEST");
+        assert!(body.get("details").is_none());
+
+        // 校验失败返回 422 和每个字段的错误
+        let mut req = request(FORM, "username=abc&password=abcdefgh");
+        let error = ValidatedForm::<Signup>::from_request(&mut req).await.unwrap_err();
+        let (status, body) = rejection(error).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "VALIDATION_FAILED");
+        let details = body["details"].as_array().unwrap();
+        assert_eq!(details.len(), 2);
+        assert_eq!(details[0]["field"], "username");
+        assert_eq!(details[0]["code"], ErrorCode::InvalidUsername.code());
+        assert_eq!(details[1]["field"], "password");
+        assert_eq!(details[1]["code"], ErrorCode::WeakPassword.code());
+    }
+
+    #[tokio::test]
+    async fn validated_json_rejections() {
+        let mut req = request(JSON, r#"{"username":"tester","password":"abcdefg1"}"#);
+        assert!(ValidatedJson::<Signup>::from_request(&mut req).await.is_ok());
+
+        for &(content_type, body) in &[(JSON, "{\"username\":"), (FORM, "username=tester&password=abcdefg1")] {
+            let mut req = request(content_type, body);
+            let error = ValidatedJson::<Signup>::from_request(&mut req).await.unwrap_err();
+            assert_eq!(rejection(error).await.0, StatusCode::BAD_REQUEST);
+        }
+
+        let mut req = request(JSON, r#"{"username":"tester","password":"12345678"}"#);
+        let error = ValidatedJson::<Signup>::from_request(&mut req).await.unwrap_err();
+        let (status, body) = rejection(error).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "password");
+    }
+}
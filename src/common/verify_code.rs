@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use crate::cache::{Cache, CacheExt};
+use crate::common::constant::{CAPTCHA_EXPIRE_SECS, CAPTCHA_MAX_ATTEMPTS, EMAIL_CODE_EXPIRE_SECS, EMAIL_CODE_MAX_ATTEMPTS};
+use crate::common::err::{AppError, ErrorCode};
+use crate::AppResult;
+
+/// 验证码用途，不同用途的验证码放在不同的 key 前缀下，不能混用
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VerifyCodeKind {
+    /// 图形验证码，key 为获取验证码时返回的 `captcha-key`
+    Captcha,
+    /// 注册邮箱验证码，key 为邮箱
+    Register,
+    /// 找回密码邮箱验证码，key 为邮箱
+    ResetPassword,
+}
+
+impl VerifyCodeKind {
+    fn key(&self, subject: &str) -> String {
+        match self {
+            VerifyCodeKind::Captcha => format!("captcha:{}", subject),
+            VerifyCodeKind::Register => format!("email_code:register:{}", subject.to_lowercase()),
+            VerifyCodeKind::ResetPassword => format!("email_code:reset:{}", subject.to_lowercase()),
+        }
+    }
+
+    fn expire(&self) -> Duration {
+        let secs = match self {
+            VerifyCodeKind::Captcha => CAPTCHA_EXPIRE_SECS,
+            _ => EMAIL_CODE_EXPIRE_SECS,
+        };
+        Duration::from_secs(secs as u64)
+    }
+
+    fn max_attempts(&self) -> u32 {
+        match self {
+            VerifyCodeKind::Captcha => CAPTCHA_MAX_ATTEMPTS,
+            _ => EMAIL_CODE_MAX_ATTEMPTS,
+        }
+    }
+}
+
+/// 保存验证码，同一用途同一对象重新获取验证码时覆盖旧的验证码并重置错误次数
+pub async fn save(kind: VerifyCodeKind, subject: &str, code: &str, cache: &dyn Cache) -> AppResult<()> {
+    let key = kind.key(subject);
+    cache.del(&attempts_key(&key)).await?;
+    cache.set(&key, &code, Some(kind.expire())).await
+}
+
+/// 校验验证码，校验通过后验证码立即失效，错误次数达到上限后同样失效
+pub async fn verify(kind: VerifyCodeKind, subject: &str, code: &str, cache: &dyn Cache) -> AppResult<()> {
+    let key = kind.key(subject);
+    let expired = || AppError::BusinessError(ErrorCode::VerifyCodeExpired);
+    let expected = cache.get::<String>(&key).await?.ok_or_else(expired)?;
+    if expected == code {
+        // 同一个验证码被并发校验时只有一个请求能取到
+        return match cache.take::<String>(&key).await? {
+            Some(taken) if taken == code => {
+                cache.del(&attempts_key(&key)).await?;
+                Ok(())
+            }
+            _ => Err(expired()),
+        };
+    }
+    let attempts = cache.incr(&attempts_key(&key), kind.expire()).await?;
+    if attempts < kind.max_attempts() as i64 {
+        return Err(AppError::BusinessError(ErrorCode::CaptchaInvalid));
+    }
+    cache.del(&key).await?;
+    cache.del(&attempts_key(&key)).await?;
+    match kind {
+        // 图形验证码只能尝试一次
+        VerifyCodeKind::Captcha => Err(AppError::BusinessError(ErrorCode::CaptchaRetry)),
+        _ => Err(AppError::BusinessError(ErrorCode::VerifyCodeAttemptsExceeded)),
+    }
+}
+
+fn attempts_key(key: &str) -> String {
+    format!("{}:attempts", key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::MemoryCache;
+    use crate::common::test_util::{error_code, EMAIL};
+
+    fn is_error(result: AppResult<()>, code: ErrorCode) -> bool {
+        error_code(result) == Some(code)
+    }
+
+    #[tokio::test]
+    async fn verify_code_can_only_be_used_once() {
+        let cache = MemoryCache::new();
+        save(VerifyCodeKind::Register, EMAIL, "123456", &cache).await.unwrap();
+        assert!(verify(VerifyCodeKind::Register, EMAIL, "123456", &cache).await.is_ok());
+        assert!(is_error(
+            verify(VerifyCodeKind::Register, EMAIL, "123456", &cache).await,
+            ErrorCode::VerifyCodeExpired
+        ));
+    }
+
+    #[tokio::test]
+    async fn verify_code_is_case_insensitive_on_email() {
+        let cache = MemoryCache::new();
+        save(VerifyCodeKind::Register, EMAIL, "123456", &cache).await.unwrap();
+        assert!(
+            verify(VerifyCodeKind::Register, &EMAIL.to_uppercase(), "123456", &cache)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn verify_code_invalid_after_max_attempts() {
+        let cache = MemoryCache::new();
+        save(VerifyCodeKind::Register, EMAIL, "123456", &cache).await.unwrap();
+        for _ in 1..EMAIL_CODE_MAX_ATTEMPTS {
+            assert!(is_error(
+                verify(VerifyCodeKind::Register, EMAIL, "000000", &cache).await,
+                ErrorCode::CaptchaInvalid
+            ));
+        }
+        assert!(is_error(
+            verify(VerifyCodeKind::Register, EMAIL, "000000", &cache).await,
+            ErrorCode::VerifyCodeAttemptsExceeded
+        ));
+        // 达到上限后正确的验证码同样失效
+        assert!(is_error(
+            verify(VerifyCodeKind::Register, EMAIL, "123456", &cache).await,
+            ErrorCode::VerifyCodeExpired
+        ));
+    }
+
+    #[tokio::test]
+    async fn captcha_allows_single_attempt() {
+        let cache = MemoryCache::new();
+        save(VerifyCodeKind::Captcha, "key", "abcd", &cache).await.unwrap();
+        assert!(is_error(
+            verify(VerifyCodeKind::Captcha, "key", "dcba", &cache).await,
+            ErrorCode::CaptchaRetry
+        ));
+        assert!(is_error(
+            verify(VerifyCodeKind::Captcha, "key", "abcd", &cache).await,
+            ErrorCode::VerifyCodeExpired
+        ));
+    }
+
+    #[tokio::test]
+    async fn save_resets_attempts() {
+        let cache = MemoryCache::new();
+        save(VerifyCodeKind::Register, EMAIL, "123456", &cache).await.unwrap();
+        for _ in 1..EMAIL_CODE_MAX_ATTEMPTS {
+            let _ = verify(VerifyCodeKind::Register, EMAIL, "000000", &cache).await;
+        }
+        save(VerifyCodeKind::Register, EMAIL, "654321", &cache).await.unwrap();
+        assert!(is_error(
+            verify(VerifyCodeKind::Register, EMAIL, "000000", &cache).await,
+            ErrorCode::CaptchaInvalid
+        ));
+        assert!(verify(VerifyCodeKind::Register, EMAIL, "654321", &cache).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn register_and_reset_codes_are_separated() {
+        let cache = MemoryCache::new();
+        save(VerifyCodeKind::Register, EMAIL, "123456", &cache).await.unwrap();
+        assert!(is_error(
+            verify(VerifyCodeKind::ResetPassword, EMAIL, "123456", &cache).await,
+            ErrorCode::VerifyCodeExpired
+        ));
+        save(VerifyCodeKind::ResetPassword, EMAIL, "654321", &cache)
+            .await
+            .unwrap();
+        assert!(is_error(
+            verify(VerifyCodeKind::Register, EMAIL, "654321", &cache).await,
+            ErrorCode::CaptchaInvalid
+        ));
+        assert!(
+            verify(VerifyCodeKind::ResetPassword, EMAIL, "654321", &cache)
+                .await
+                .is_ok()
+        );
+        assert!(verify(VerifyCodeKind::Register, EMAIL, "123456", &cache).await.is_ok());
+    }
+}
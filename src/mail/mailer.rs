@@ -0,0 +1,322 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Local;
+use redis::aio::ConnectionManager;
+use redis::{Client, Connection, Script};
+use tera::Context;
+use uuid::Uuid;
+
+use crate::common::err::AppError;
+use crate::common::locale::Locale;
+use crate::common::settings::{MailSettings, Settings};
+use crate::mail::template::{MailTemplate, MailTemplates};
+use crate::mail::transport::{self, MailTransport};
+use crate::mail::Mail;
+use crate::AppResult;
+
+/// 发件箱，有序集合，score 为邮件下次可以发送的时间（毫秒）
+const OUTBOX_KEY: &str = "mail:outbox";
+
+/// 超过最大重试次数的邮件，只保留最近的记录
+const DEAD_LETTER_KEY: &str = "mail:dead";
+
+const DEAD_LETTER_MAX_LEN: isize = 1000;
+
+/// 死信列表最后一次写入后的保留时间，单位为秒
+const DEAD_LETTER_TTL_SECS: usize = 60 * 60 * 24 * 30;
+
+/// 发件箱为空时的轮询间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 取出的邮件在该时间内对其他发送线程不可见，发送线程异常退出时邮件会在超时后被重新发送
+const LEASE_MILLIS: i64 = 5 * 60 * 1000;
+
+lazy_static! {
+    // 取出一封已到发送时间的邮件，并把它的发送时间推迟到租约结束，保证同一时间只有一个发送线程处理
+    static ref CLAIM_SCRIPT: Script = Script::new(
+        r"
+        local jobs = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1], 'LIMIT', 0, 1)
+        if #jobs == 0 then
+            return false
+        end
+        redis.call('ZADD', KEYS[1], ARGV[2], jobs[1])
+        return jobs[1]
+        "
+    );
+}
+
+/// 发件箱中的一封邮件
+#[derive(Debug, Serialize, Deserialize)]
+struct MailJob {
+    id: String,
+    mail: Mail,
+    attempts: u32,
+    last_error: Option<String>,
+}
+
+/// 死信列表中的记录，邮件正文中可能包含验证码，只保留排查问题需要的信息
+#[derive(Debug, Serialize)]
+struct DeadLetter<'a> {
+    id: Option<&'a str>,
+    to: Option<&'a str>,
+    subject: Option<&'a str>,
+    attempts: u32,
+    error: &'a str,
+    failed_at: String,
+}
+
+impl<'a> DeadLetter<'a> {
+    fn new(job: Option<&'a MailJob>, error: &'a str) -> Self {
+        DeadLetter {
+            id: job.map(|j| j.id.as_str()),
+            to: job.map(|j| j.mail.to.as_str()),
+            subject: job.map(|j| j.mail.subject.as_str()),
+            attempts: job.map_or(0, |j| j.attempts),
+            error,
+            failed_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    client: Client,
+    /// 请求处理过程中把邮件放入发件箱使用的异步连接
+    conn: ConnectionManager,
+    settings: Arc<Settings>,
+    templates: Arc<MailTemplates>,
+}
+
+impl Mailer {
+    pub async fn new(client: Client, settings: Arc<Settings>) -> AppResult<Self> {
+        let templates = Arc::new(MailTemplates::load(&settings.mail.template_dir)?);
+        let conn = ConnectionManager::new(client.clone()).await?;
+        Ok(Self {
+            client,
+            conn,
+            settings,
+            templates,
+        })
+    }
+
+    /// 启动发送线程，每个线程使用各自的发送方式实例和 Redis 连接
+    pub fn start(&self) -> AppResult<()> {
+        for index in 0..self.settings.mail.workers {
+            let transport = transport::build(&self.settings)?;
+            let mailer = self.clone();
+            std::thread::Builder::new()
+                .name(format!("mail-worker-{}", index))
+                .spawn(move || mailer.run(transport))?;
+        }
+        info!("邮件发送线程已启动，线程数: {}", self.settings.mail.workers);
+        Ok(())
+    }
+
+    /// 按收件人的语言渲染模板后放入发件箱
+    pub async fn send_template(
+        &self,
+        template: MailTemplate,
+        locale: Locale,
+        to: &str,
+        context: &Context,
+    ) -> AppResult<()> {
+        let mail = self.templates.render(template, locale, to, context)?;
+        self.send(mail).await
+    }
+
+    /// 把邮件放入发件箱，由后台线程异步发送
+    pub async fn send(&self, mail: Mail) -> AppResult<()> {
+        let job = MailJob {
+            id: Uuid::new_v4().to_simple().to_string(),
+            mail,
+            attempts: 0,
+            last_error: None,
+        };
+        redis::cmd("ZADD")
+            .arg(OUTBOX_KEY)
+            .arg(now_millis())
+            .arg(serde_json::to_string(&job)?)
+            .query_async::<_, i64>(&mut self.conn.clone())
+            .await?;
+        Ok(())
+    }
+
+    fn run(&self, mut transport: Box<dyn MailTransport>) {
+        let mut conn: Option<Connection> = None;
+        loop {
+            let result = match conn.as_mut() {
+                Some(c) => self.deliver_next(c, transport.as_mut()),
+                None => self.client.get_connection().map_err(AppError::from).and_then(|c| {
+                    let c = conn.get_or_insert(c);
+                    self.deliver_next(c, transport.as_mut())
+                }),
+            };
+            match result {
+                Ok(true) => {}
+                Ok(false) => std::thread::sleep(POLL_INTERVAL),
+                Err(e) => {
+                    error!("处理发件箱出错，报错信息: {}", e.to_string());
+                    // 连接可能已断开，下次重新连接
+                    conn = None;
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    /// 发送一封邮件，发件箱中没有到发送时间的邮件时返回 `false`
+    fn deliver_next(&self, conn: &mut Connection, transport: &mut dyn MailTransport) -> AppResult<bool> {
+        let now = now_millis();
+        let member: Option<String> = CLAIM_SCRIPT
+            .key(OUTBOX_KEY)
+            .arg(now)
+            .arg(now + LEASE_MILLIS)
+            .invoke(conn)?;
+        let member = match member {
+            Some(member) => member,
+            None => return Ok(false),
+        };
+        let mut job = match serde_json::from_str::<MailJob>(&member) {
+            Ok(job) => job,
+            Err(e) => {
+                error!("发件箱中的邮件解析失败: {}，移入死信列表", e.to_string());
+                let error = e.to_string();
+                self.move_to_dead_letter(conn, &member, &DeadLetter::new(None, &error))?;
+                return Ok(true);
+            }
+        };
+        match transport.send(&job.mail) {
+            Ok(()) => {
+                redis::cmd("ZREM").arg(OUTBOX_KEY).arg(&member).query::<i64>(conn)?;
+                info!("邮件 {} 已发送至 {}", job.id, job.mail.to);
+            }
+            Err(e) => {
+                job.attempts += 1;
+                job.last_error = Some(e.to_string());
+                if job.attempts >= self.settings.mail.max_attempts {
+                    error!(
+                        "邮件 {} 发送失败 {} 次，移入死信列表: {}",
+                        job.id,
+                        job.attempts,
+                        e.to_string()
+                    );
+                    let error = e.to_string();
+                    self.move_to_dead_letter(conn, &member, &DeadLetter::new(Some(&job), &error))?;
+                } else {
+                    let payload = serde_json::to_string(&job)?;
+                    let delay = retry_delay(&self.settings.mail, job.attempts);
+                    warn!(
+                        "邮件 {} 第 {} 次发送失败，{} 秒后重试: {}",
+                        job.id,
+                        job.attempts,
+                        delay,
+                        e.to_string()
+                    );
+                    redis::pipe()
+                        .atomic()
+                        .zrem(OUTBOX_KEY, &member)
+                        .zadd(OUTBOX_KEY, &payload, now_millis() + delay as i64 * 1000)
+                        .query::<()>(conn)?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn move_to_dead_letter(&self, conn: &mut Connection, member: &str, dead_letter: &DeadLetter) -> AppResult<()> {
+        redis::pipe()
+            .atomic()
+            .zrem(OUTBOX_KEY, member)
+            .lpush(DEAD_LETTER_KEY, serde_json::to_string(dead_letter)?)
+            .ltrim(DEAD_LETTER_KEY, 0, DEAD_LETTER_MAX_LEN - 1)
+            .expire(DEAD_LETTER_KEY, DEAD_LETTER_TTL_SECS)
+            .query::<()>(conn)?;
+        Ok(())
+    }
+}
+
+// 指数退避，单位为秒
+fn retry_delay(settings: &MailSettings, attempts: u32) -> u64 {
+    let factor = 1u64.checked_shl(attempts.saturating_sub(1)).unwrap_or(u64::MAX);
+    settings
+        .retry_base_secs
+        .saturating_mul(factor)
+        .min(settings.retry_max_secs)
+}
+
+fn now_millis() -> i64 {
+    Local::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::common::settings::MailTransportKind;
+
+    fn mail_settings() -> MailSettings {
+        MailSettings {
+            transport: MailTransportKind::Stdout,
+            from: String::from("nova-me@whatsoo.org"),
+            template_dir: PathBuf::from("templates/mail"),
+            file_dir: PathBuf::from("mail"),
+            workers: 1,
+            max_attempts: 5,
+            retry_base_secs: 10,
+            retry_max_secs: 1800,
+            digest_interval_secs: 0,
+        }
+    }
+
+    fn job() -> MailJob {
+        MailJob {
+            id: String::from("job-1"),
+            mail: Mail {
+                to: String::from("tester@whatsoo.org"),
+                subject: String::from("注册验证码"),
+                html: String::from("<p>123456</p>"),
+                text: String::from("123456"),
+            },
+            attempts: 5,
+            last_error: Some(String::from("connection refused")),
+        }
+    }
+
+    #[test]
+    fn retry_delay_doubles_until_max() {
+        let settings = mail_settings();
+        let delays = (1..=9)
+            .map(|attempts| retry_delay(&settings, attempts))
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![10, 20, 40, 80, 160, 320, 640, 1280, 1800]);
+        // 次数很大时不溢出
+        assert_eq!(retry_delay(&settings, 64), 1800);
+        assert_eq!(retry_delay(&settings, u32::MAX), 1800);
+    }
+
+    #[test]
+    fn mail_job_survives_outbox_round_trip() {
+        let payload = serde_json::to_string(&job()).unwrap();
+        let restored = serde_json::from_str::<MailJob>(&payload).unwrap();
+        assert_eq!(restored.id, "job-1");
+        assert_eq!(restored.attempts, 5);
+        assert_eq!(restored.mail.text, "123456");
+        assert_eq!(restored.last_error.as_deref(), Some("connection refused"));
+    }
+
+    #[test]
+    fn dead_letter_omits_mail_body() {
+        let job = job();
+        let dead_letter = serde_json::to_value(DeadLetter::new(Some(&job), "connection refused")).unwrap();
+        assert_eq!(dead_letter["to"], "tester@whatsoo.org");
+        assert_eq!(dead_letter["attempts"], 5);
+        // 正文中可能包含验证码
+        assert!(!dead_letter.to_string().contains("123456"));
+
+        let dead_letter = serde_json::to_value(DeadLetter::new(None, "invalid json")).unwrap();
+        assert!(dead_letter["id"].is_null());
+        assert_eq!(dead_letter["attempts"], 0);
+    }
+}
@@ -0,0 +1,23 @@
+//! 邮件发送
+//!
+//! 业务代码通过 [`Mailer::send`] 把邮件写入 Redis 中的发件箱后立即返回，
+//! 后台的发送线程从发件箱取出邮件，通过配置的 [`transport::MailTransport`] 发送，失败后按指数退避重试，
+//! 超过最大重试次数的邮件移入死信列表，等待人工处理。
+//! 邮件内容由模板目录中按语言区分的模板渲染，见 [`MailTemplate`]。
+//! 模板的渲染结果保存在 `src/mail/snapshots` 中，由快照测试比较，修改模板后设置 `UPDATE_SNAPSHOTS=1` 运行测试重新生成。
+
+pub use self::mailer::Mailer;
+pub use self::template::MailTemplate;
+
+mod mailer;
+mod template;
+mod transport;
+
+/// 待发送的邮件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
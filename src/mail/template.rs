@@ -0,0 +1,192 @@
+use std::path::Path;
+
+use tera::{Context, Tera};
+
+use crate::common::err::AppError;
+use crate::common::locale::Locale;
+use crate::mail::Mail;
+use crate::AppResult;
+
+/// 邮件模板
+///
+/// 每个模板在每种语言的目录下都有三个文件：`{name}.subject.txt` 为标题，
+/// `{name}.html` 和 `{name}.txt` 分别为 HTML 和纯文本正文，例如 `zh-CN/register.html`。
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MailTemplate {
+    /// 注册邮箱验证码，参数: `code`、`expire_minutes`
+    Register,
+    /// 找回密码验证码，参数: `code`、`expire_minutes`
+    ResetPassword,
+    /// 未读通知汇总，参数: `username`、`unread`、`notices`（包含 `content` 和 `create_time`）
+    NoticeDigest,
+}
+
+impl MailTemplate {
+    pub const ALL: [MailTemplate; 3] = [
+        MailTemplate::Register,
+        MailTemplate::ResetPassword,
+        MailTemplate::NoticeDigest,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MailTemplate::Register => "register",
+            MailTemplate::ResetPassword => "reset_password",
+            MailTemplate::NoticeDigest => "notice_digest",
+        }
+    }
+}
+
+/// 启动时从模板目录加载的所有邮件模板
+pub struct MailTemplates {
+    tera: Tera,
+}
+
+impl MailTemplates {
+    /// 加载模板目录，缺少任何语言的任何模板文件都会返回错误
+    pub fn load(dir: &Path) -> AppResult<Self> {
+        let glob = format!("{}/**/*", dir.display());
+        let tera = Tera::new(&glob).map_err(|e| AppError::ConfigError(format!("加载邮件模板失败: {:?}", e)))?;
+        let names: Vec<&str> = tera.get_template_names().collect();
+        let missing: Vec<String> = Locale::ALL
+            .iter()
+            .flat_map(|locale| MailTemplate::ALL.iter().flat_map(move |t| template_files(*locale, *t)))
+            .filter(|file| !names.contains(&file.as_str()))
+            .collect();
+        if !missing.is_empty() {
+            return Err(AppError::ConfigError(format!(
+                "邮件模板目录 {} 缺少模板: {}",
+                dir.display(),
+                missing.join(", ")
+            )));
+        }
+        Ok(Self { tera })
+    }
+
+    pub fn render(&self, template: MailTemplate, locale: Locale, to: &str, context: &Context) -> AppResult<Mail> {
+        let [subject, html, text] = template_files(locale, template);
+        Ok(Mail {
+            to: String::from(to),
+            subject: self.render_file(&subject, context)?.trim().to_string(),
+            html: self.render_file(&html, context)?,
+            text: self.render_file(&text, context)?,
+        })
+    }
+
+    fn render_file(&self, file: &str, context: &Context) -> AppResult<String> {
+        self.tera.render(file, context).map_err(|e| {
+            error!("渲染邮件模板 {} 失败: {:?}", file, e);
+            AppError::MailError(format!("渲染邮件模板 {} 失败", file))
+        })
+    }
+}
+
+fn template_files(locale: Locale, template: MailTemplate) -> [String; 3] {
+    let prefix = format!("{}/{}", locale.as_str(), template.name());
+    [
+        format!("{}.subject.txt", prefix),
+        format!("{}.html", prefix),
+        format!("{}.txt", prefix),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::model::notice::{LocalizedNotice, Notice, NoticeType};
+
+    /// 设置该环境变量后重新生成快照，而不是和快照比较
+    const UPDATE_SNAPSHOTS_ENV: &str = "UPDATE_SNAPSHOTS";
+
+    fn manifest_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn context(template: MailTemplate, locale: Locale) -> Context {
+        let mut context = Context::new();
+        match template {
+            MailTemplate::Register | MailTemplate::ResetPassword => {
+                context.insert("code", "Xk3fP9qL");
+                context.insert("expire_minutes", &50);
+            }
+            MailTemplate::NoticeDigest => {
+                let create_time = NaiveDate::from_ymd_opt(2021, 9, 1)
+                    .unwrap()
+                    .and_hms_opt(8, 30, 0)
+                    .unwrap();
+                // 包含需要转义的字符，HTML 正文应当转义，纯文本正文保持原样
+                let notices: Vec<LocalizedNotice> = [
+                    (NoticeType::Comment, "alice", "Rust & <Tera>"),
+                    (NoticeType::Follow, "bob", ""),
+                ]
+                .iter()
+                .enumerate()
+                .map(|(i, &(notice_type, actor_name, target_title))| {
+                    Notice {
+                        pk_id: i as u64 + 1,
+                        notice_type,
+                        target_id: 1,
+                        actor_name: String::from(actor_name),
+                        target_title: String::from(target_title),
+                        notified_user_id: 1,
+                        viewed: false,
+                        create_time,
+                        create_user: 2,
+                    }
+                    .localize(locale)
+                })
+                .collect();
+                context.insert("username", "tester");
+                context.insert("unread", &3);
+                context.insert("notices", &notices);
+            }
+        }
+        context
+    }
+
+    #[test]
+    fn render_matches_snapshots() {
+        let templates = MailTemplates::load(&manifest_dir().join("templates/mail")).unwrap();
+        let snapshot_dir = manifest_dir().join("src/mail/snapshots");
+        let update = std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some();
+        let mut mismatched = Vec::new();
+        for &locale in Locale::ALL.iter() {
+            for &template in MailTemplate::ALL.iter() {
+                let mail = templates
+                    .render(template, locale, "tester@whatsoo.org", &context(template, locale))
+                    .unwrap();
+                let [subject, html, text] = template_files(locale, template);
+                for (file, rendered) in vec![(subject, &mail.subject), (html, &mail.html), (text, &mail.text)] {
+                    let path = snapshot_dir.join(&file);
+                    if update {
+                        fs::create_dir_all(path.parent().unwrap()).unwrap();
+                        fs::write(&path, rendered).unwrap();
+                    } else if fs::read_to_string(&path).ok().as_ref() != Some(rendered) {
+                        mismatched.push(file);
+                    }
+                }
+            }
+        }
+        assert!(
+            mismatched.is_empty(),
+            "邮件渲染结果与快照不一致，确认改动后设置 {}=1 重新生成: {:?}",
+            UPDATE_SNAPSHOTS_ENV,
+            mismatched
+        );
+    }
+
+    #[test]
+    fn load_fails_when_template_missing() {
+        let dir = std::env::temp_dir().join(format!("whatsoo-mail-{}", std::process::id()));
+        fs::create_dir_all(dir.join("zh-CN")).unwrap();
+        fs::write(dir.join("zh-CN/register.html"), "{{ code }}").unwrap();
+        let result = MailTemplates::load(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, Err(AppError::ConfigError(_))));
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Local;
+use lettre::smtp::authentication::Credentials;
+use lettre::{SmtpClient, SmtpTransport, Transport};
+use lettre_email::Email;
+use uuid::Uuid;
+
+use crate::common::err::AppError;
+use crate::common::settings::{MailTransportKind, Settings};
+use crate::mail::Mail;
+use crate::AppResult;
+
+/// 邮件发送方式，每个发送线程持有各自的实例
+pub trait MailTransport: Send {
+    fn send(&mut self, mail: &Mail) -> AppResult<()>;
+}
+
+/// 根据配置创建发送方式
+pub fn build(settings: &Settings) -> AppResult<Box<dyn MailTransport>> {
+    let from = settings.mail.from.clone();
+    match settings.mail.transport {
+        MailTransportKind::Smtp => {
+            let smtp = &settings.smtp;
+            let credentials = Credentials::new(smtp.username.clone(), smtp.password.clone());
+            let transport = SmtpClient::new_simple(&smtp.server)
+                .map_err(|e| AppError::MailError(format!("连接邮件服务器 {} 失败: {}", smtp.server, e)))?
+                .credentials(credentials)
+                .transport();
+            Ok(Box::new(SmtpMailTransport { from, transport }))
+        }
+        MailTransportKind::File => {
+            let dir = settings.mail.file_dir.clone();
+            fs::create_dir_all(&dir)?;
+            Ok(Box::new(FileMailTransport { from, dir }))
+        }
+        MailTransportKind::Stdout => Ok(Box::new(StdoutMailTransport { from })),
+    }
+}
+
+pub struct SmtpMailTransport {
+    from: String,
+    transport: SmtpTransport,
+}
+
+impl MailTransport for SmtpMailTransport {
+    fn send(&mut self, mail: &Mail) -> AppResult<()> {
+        let email = Email::builder()
+            .to(mail.to.as_str())
+            .from(self.from.as_str())
+            .subject(mail.subject.as_str())
+            .alternative(mail.html.as_str(), mail.text.as_str())
+            .build()
+            .map_err(|e| AppError::MailError(e.to_string()))?;
+        self.transport
+            .send(email.into())
+            .map_err(|e| AppError::MailError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// 把邮件写入目录，每封邮件一个文件，用于开发和测试环境
+pub struct FileMailTransport {
+    from: String,
+    dir: PathBuf,
+}
+
+impl MailTransport for FileMailTransport {
+    fn send(&mut self, mail: &Mail) -> AppResult<()> {
+        let file_name = format!(
+            "{}-{}.eml",
+            Local::now().format("%Y%m%d%H%M%S"),
+            Uuid::new_v4().to_simple()
+        );
+        fs::write(self.dir.join(file_name), render(&self.from, mail))?;
+        Ok(())
+    }
+}
+
+/// 把邮件打印到标准输出，用于开发和测试环境
+pub struct StdoutMailTransport {
+    from: String,
+}
+
+impl MailTransport for StdoutMailTransport {
+    fn send(&mut self, mail: &Mail) -> AppResult<()> {
+        println!("{}", render(&self.from, mail));
+        Ok(())
+    }
+}
+
+fn render(from: &str, mail: &Mail) -> String {
+    format!(
+        "From: {}\nTo: {}\nSubject: {}\nDate: {}\n\n{}\n\n{}\n",
+        from,
+        mail.to,
+        mail.subject,
+        Local::now().to_rfc2822(),
+        mail.text,
+        mail.html
+    )
+}
@@ -7,37 +7,39 @@ extern crate serde;
 #[macro_use]
 extern crate tracing;
 
-use std::convert::Infallible;
-use std::env;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use axum::body::Body;
-use axum::http::Request;
 use axum::AddExtensionLayer;
 use dotenv::dotenv;
-use lettre::smtp::authentication::Credentials;
-use lettre::{SmtpClient, SmtpTransport};
-use r2d2::{Pool, PooledConnection};
-use redis::Client;
 use regex::Regex;
 use sqlx::mysql::MySqlPoolOptions;
-use sqlx::pool::PoolConnection;
-use sqlx::{MySql, MySqlPool};
-use tokio::sync::Mutex;
-use tower::filter::AsyncFilterLayer;
 use tower::ServiceBuilder;
+use tower_http::auth::AsyncRequireAuthorizationLayer;
 use tower_http::compression::CompressionLayer;
 
+use crate::cache::{Cache, MemoryCache, RedisCache};
 use crate::common::err::AppError;
+use crate::common::jwt;
+use crate::common::settings::{CacheBackend, DatabaseBackend, Settings, StorageBackend};
+use crate::mail::Mailer;
+use crate::middleware::request_context::RequestContextLayer;
+use crate::repository::{migration, Repositories};
+use crate::common::notice_hub::NoticeHub;
 use crate::route::config;
+use crate::service::notice_service;
+use crate::storage::{LocalStorage, Storage};
 
+mod cache;
 mod common;
+mod mail;
+mod middleware;
 mod model;
 mod repository;
 mod route;
 mod service;
+mod storage;
 use mimalloc::MiMalloc;
 
 #[global_allocator]
@@ -45,27 +47,24 @@ static GLOBAL: MiMalloc = MiMalloc;
 
 lazy_static! {
     static ref MAILE_RE: Regex = Regex::new(r"^[a-zA-Z0-9_-]+@[a-zA-Z0-9_-]+(\.[a-zA-Z0-9_-]+)+$").unwrap();
-    static ref USERNAME_RE: Regex = Regex::new(r"^[a-zA-Z0-9](5,10)$").unwrap();
+    static ref USERNAME_RE: Regex = Regex::new(r"^[a-zA-Z0-9_-]{5,20}$").unwrap();
+    // GitHub 用户名只能包含字母、数字和不连续的短横线，且不能以短横线开头或结尾
+    static ref GITHUB_UID_RE: Regex = Regex::new(r"^[a-zA-Z0-9](-?[a-zA-Z0-9])*$").unwrap();
+    static ref MENTION_RE: Regex = Regex::new(r"@([a-zA-Z0-9_-]+)").unwrap();
 }
 
 #[derive(Clone)]
 struct ShareState {
-    pub db_pool: MySqlPool,
-    pub redis_pool: Pool<Client>,
-    pub smtp_transport: Arc<Mutex<SmtpTransport>>,
+    pub repos: Repositories,
+    pub cache: Arc<dyn Cache>,
+    pub mailer: Mailer,
+    pub notice_hub: NoticeHub,
+    pub storage: Arc<dyn Storage>,
+    pub settings: Arc<Settings>,
 }
 
 type AppResult<R> = std::result::Result<R, AppError>;
 
-impl ShareState {
-    pub async fn get_redis_conn(&self) -> AppResult<PooledConnection<Client>> {
-        Ok(self.redis_pool.get()?)
-    }
-    pub async fn get_mysql_conn(&self) -> AppResult<PoolConnection<MySql>> {
-        Ok(self.db_pool.acquire().await?)
-    }
-}
-
 #[tokio::main]
 async fn main() -> AppResult<()> {
     dotenv().ok();
@@ -74,53 +73,98 @@ async fn main() -> AppResult<()> {
         std::env::set_var("RUST_LOG", "whatsoo=info")
     }
     tracing_subscriber::fmt::init();
-    let db_url = env::var("DATABASE_URL").expect("数据库连接未设置");
-    let db_pool = MySqlPoolOptions::new()
-        .max_connections(20)
-        .min_connections(10)
-        .max_lifetime(Duration::from_millis(1800000))
-        .idle_timeout(Duration::from_millis(600000))
-        .connect(&db_url)
-        .await?;
-    let client = redis::Client::open("redis://127.0.0.1/")?;
-    let redis_pool = r2d2::Pool::builder().max_size(20).min_idle(Some(10)).build(client)?;
-
-    let mine_email = &env::var("MINE_EMAIL").expect("邮箱账户未设置");
-    let smtp_server = &env::var("SMTP_SERVER").expect("邮箱服务器未设置");
-    let password = &env::var("PASSWORD").expect("邮箱服务器密码未设置");
-    let credentials = Credentials::new(mine_email.to_string(), password.to_string());
+    // `whatsoo migrate` 只执行数据库迁移，不启动服务
+    let migrate_only = match std::env::args().nth(1).as_deref() {
+        None => false,
+        Some("migrate") => true,
+        Some(command) => {
+            error!("未知的命令: {}，可用的命令: migrate", command);
+            std::process::exit(1);
+        }
+    };
+    let settings = match Settings::load().and_then(|settings| jwt::init(&settings.jwt).map(|_| settings)) {
+        Ok(settings) => Arc::new(settings),
+        Err(e) => {
+            error!("配置加载失败: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let db_settings = &settings.database;
+    let repos = match db_settings.backend {
+        DatabaseBackend::Mysql => {
+            let db_pool = MySqlPoolOptions::new()
+                .max_connections(db_settings.max_connections)
+                .min_connections(db_settings.min_connections)
+                .max_lifetime(Duration::from_secs(db_settings.max_lifetime_secs))
+                .idle_timeout(Duration::from_secs(db_settings.idle_timeout_secs))
+                .connect(&db_settings.url)
+                .await?;
+            if migrate_only || db_settings.auto_migrate {
+                migration::run(&db_pool).await?;
+            }
+            if migrate_only {
+                return Ok(());
+            }
+            Repositories::mysql(db_pool)
+        }
+        DatabaseBackend::Memory => {
+            if migrate_only {
+                error!("内存数据库不需要执行迁移");
+                std::process::exit(1);
+            }
+            warn!("使用内存数据库，重启后数据全部丢失");
+            Repositories::memory()
+        }
+    };
+    let client = redis::Client::open(settings.redis.url.as_str())?;
+    let cache: Arc<dyn Cache> = match settings.cache.backend {
+        CacheBackend::Redis => Arc::new(RedisCache::connect(client.clone()).await?),
+        CacheBackend::Memory => {
+            warn!("使用进程内缓存，缓存数据不会在多个实例之间共享");
+            Arc::new(MemoryCache::new())
+        }
+    };
 
-    // Open connection to QQMail
-    let mailer = SmtpClient::new_simple(smtp_server)
-        .unwrap()
-        .credentials(credentials)
-        .transport();
-    let smtp_transport = Arc::new(Mutex::new(mailer));
+    let mailer = Mailer::new(client.clone(), Arc::clone(&settings)).await?;
+    mailer.start()?;
+    let notice_hub = NoticeHub::new(client).await?;
+    notice_hub.start();
+    let upload_settings = &settings.upload;
+    let storage: Arc<dyn Storage> = match upload_settings.backend {
+        StorageBackend::Local => Arc::new(LocalStorage::new(
+            upload_settings.dir.clone(),
+            &upload_settings.public_url,
+        )),
+    };
+    let app = config::init(upload_settings);
     let state = ShareState {
-        db_pool,
-        redis_pool,
-        smtp_transport,
+        repos,
+        cache,
+        mailer,
+        notice_hub,
+        storage,
+        settings: Arc::clone(&settings),
     };
-
+    notice_service::start_digest_job(state.clone());
+    let addr = settings.server.addr();
     let middleware_stack = ServiceBuilder::new()
-        .timeout(Duration::from_secs(30))
+        .timeout(settings.server.timeout())
         .load_shed()
-        .concurrency_limit(10000)
+        .concurrency_limit(settings.server.concurrency_limit)
         .layer(CompressionLayer::new().br(true))
-        .layer(AsyncFilterLayer::new(filter))
+        .layer(RequestContextLayer)
         .layer(AddExtensionLayer::new(state))
+        .layer(AsyncRequireAuthorizationLayer::new(config::auth()))
+        .layer(config::rate_limit())
         .into_inner();
 
-    let app = config::init().layer(middleware_stack);
+    let app = app.layer(middleware_stack);
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
     info!("listening on {}", addr);
-    axum::Server::bind(&addr).serve(app.into_make_service()).await.unwrap();
+    // 限流需要客户端地址
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr, _>())
+        .await
+        .unwrap();
     Ok(())
 }
-
-async fn filter(req: Request<Body>) -> Result<Request<Body>, Infallible> {
-    tracing::info!("path is : {} [{}]", req.method(), req.uri().path());
-    todo!("添加过滤条件，检查Token是否过期");
-    Ok(req)
-}
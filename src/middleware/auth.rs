@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use axum::body::{box_body, BoxBody};
+use axum::http::{Method, Request, Response};
+use axum::response::IntoResponse;
+use futures::future::BoxFuture;
+use tower_http::auth::AsyncAuthorizeRequest;
+
+use crate::common::constant::TOKEN_HEADER_NAME;
+use crate::common::err::{AppError, ErrorCode};
+use crate::middleware::route_pattern::RoutePattern;
+use crate::model::user::UserToken;
+use crate::service::session_service;
+use crate::ShareState;
+
+/// JWT 认证，白名单中的路由无需登录即可访问
+///
+/// 除了校验签名和有效期外，还会检查 token 所属会话是否已退出、token 版本是否已过期。
+/// 认证通过后解析出的 [`UserToken`] 会放入请求的 extensions 中，供 `UserToken` 提取器直接使用。
+#[derive(Debug, Clone, Default)]
+pub struct JwtAuth {
+    allowlist: Arc<Vec<RoutePattern>>,
+}
+
+impl JwtAuth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 允许任意请求方法无需登录访问该路由
+    pub fn allow(mut self, path: &str) -> Self {
+        Arc::make_mut(&mut self.allowlist).push(RoutePattern::new(None, path));
+        self
+    }
+
+    /// 仅允许指定请求方法无需登录访问该路由
+    pub fn allow_method(mut self, method: Method, path: &str) -> Self {
+        Arc::make_mut(&mut self.allowlist).push(RoutePattern::new(Some(method), path));
+        self
+    }
+
+    fn is_public(&self, method: &Method, path: &str) -> bool {
+        self.allowlist.iter().any(|p| p.matches(method, path))
+    }
+}
+
+/// 认证结果，白名单路由未携带合法 token 时为 `None`
+pub struct Authorized(Option<UserToken>);
+
+impl AsyncAuthorizeRequest for JwtAuth {
+    type Output = Authorized;
+    type Future = BoxFuture<'static, Option<Authorized>>;
+    type ResponseBody = BoxBody;
+
+    fn authorize<B>(&mut self, request: &Request<B>) -> Self::Future {
+        let is_public = self.is_public(request.method(), request.uri().path());
+        let token = request
+            .headers()
+            .get(TOKEN_HEADER_NAME)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let state = request.extensions().get::<ShareState>().cloned();
+        Box::pin(async move {
+            let user_token = match (token, state) {
+                (Some(t), Some(state)) => session_service::verify_access_token(&t, &state.repos, &*state.cache).await,
+                _ => None,
+            };
+            match user_token {
+                Some(t) => Some(Authorized(Some(t))),
+                None if is_public => Some(Authorized(None)),
+                None => None,
+            }
+        })
+    }
+
+    fn on_authorized<B>(&mut self, request: &mut Request<B>, output: Authorized) {
+        if let Authorized(Some(user_token)) = output {
+            request.extensions_mut().insert(user_token);
+        }
+    }
+
+    fn unauthorized_response<B>(&mut self, request: &Request<B>) -> Response<BoxBody> {
+        info!("拒绝未认证请求: {} [{}]", request.method(), request.uri().path());
+        let err = if request.headers().contains_key(TOKEN_HEADER_NAME) {
+            AppError::BusinessError(ErrorCode::TokenInvalid)
+        } else {
+            AppError::BusinessError(ErrorCode::NotLoggedIn)
+        };
+        err.into_response().map(box_body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::route::config;
+
+    fn request(method: Method, path: &str, token: Option<&str>) -> Request<()> {
+        let mut builder = Request::builder().method(method).uri(path);
+        if let Some(token) = token {
+            builder = builder.header(TOKEN_HEADER_NAME, token);
+        }
+        builder.body(()).unwrap()
+    }
+
+    async fn authorize(auth: &mut JwtAuth, request: &Request<()>) -> Option<Option<UserToken>> {
+        auth.authorize(request).await.map(|Authorized(user_token)| user_token)
+    }
+
+    #[test]
+    fn route_pattern_matching() {
+        let pattern = RoutePattern::new(Some(Method::GET), "/topic/:id");
+        assert!(pattern.matches(&Method::GET, "/topic/1"));
+        assert!(pattern.matches(&Method::GET, "/topic/1/"));
+        assert!(!pattern.matches(&Method::PUT, "/topic/1"));
+        assert!(!pattern.matches(&Method::GET, "/topic"));
+        assert!(!pattern.matches(&Method::GET, "/topic/1/comments"));
+
+        let pattern = RoutePattern::new(None, "/verify/*");
+        assert!(pattern.matches(&Method::POST, "/verify/email"));
+        assert!(pattern.matches(&Method::GET, "/verify/a/b"));
+        assert!(!pattern.matches(&Method::GET, "/verifyx/email"));
+    }
+
+    #[test]
+    fn allowlist_only_covers_public_routes() {
+        let auth = config::auth();
+        for (method, path) in &[
+            (Method::POST, "/login"),
+            (Method::GET, "/captcha"),
+            (Method::POST, "/verify/email"),
+            (Method::GET, "/user/validate/tester"),
+            (Method::GET, "/topic/1"),
+            (Method::GET, "/topics"),
+            (Method::GET, "/topic/1/comments/tree"),
+        ] {
+            assert!(auth.is_public(method, path), "{} {}", method, path);
+        }
+        for (method, path) in &[
+            (Method::PUT, "/topic/1"),
+            (Method::DELETE, "/topic/1"),
+            (Method::POST, "/topic"),
+            (Method::GET, "/notices"),
+            (Method::PUT, "/admin/user/1/role"),
+        ] {
+            assert!(!auth.is_public(method, path), "{} {}", method, path);
+        }
+    }
+
+    #[tokio::test]
+    async fn public_route_passes_without_token() {
+        let mut auth = JwtAuth::new().allow_method(Method::GET, "/topics");
+        let request = request(Method::GET, "/topics", None);
+        assert_eq!(authorize(&mut auth, &request).await.map(|t| t.is_none()), Some(true));
+    }
+
+    #[tokio::test]
+    async fn private_route_rejects_missing_or_invalid_token() {
+        let mut auth = JwtAuth::new().allow_method(Method::GET, "/topics");
+        for token in &[None, Some("invalid-token")] {
+            let request = request(Method::POST, "/topics", *token);
+            assert!(authorize(&mut auth, &request).await.is_none());
+        }
+    }
+
+    #[test]
+    fn unauthorized_response_codes() {
+        let mut auth = JwtAuth::new();
+        let response = auth.unauthorized_response(&request(Method::GET, "/notices", None));
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = auth.unauthorized_response(&request(Method::GET, "/notices", Some("expired")));
+        assert_eq!(response.status(), ErrorCode::TokenInvalid.status());
+    }
+}
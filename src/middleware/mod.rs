@@ -0,0 +1,5 @@
+pub mod auth;
+pub mod rate_limit;
+pub mod request_context;
+pub mod role;
+mod route_pattern;
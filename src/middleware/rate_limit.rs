@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::body::{Body, BoxBody, HttpBody, box_body};
+use axum::extract::ConnectInfo;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Method, Request, Response};
+use axum::response::IntoResponse;
+use bytes::BytesMut;
+use futures::future::BoxFuture;
+use tower::{Layer, Service};
+
+use crate::cache::Cache;
+use crate::common::err::{AppError, ErrorCode};
+use crate::middleware::route_pattern::RoutePattern;
+use crate::model::user::UserToken;
+use crate::{AppResult, ShareState};
+
+const RATE_LIMIT_PREFIX: &str = "rate_limit:";
+
+/// 按邮箱限流时读取的表单最大长度
+const MAX_FORM_BYTES: usize = 16 * 1024;
+
+/// 限流的统计维度
+#[derive(Debug, Clone, Copy)]
+pub enum LimitKey {
+    /// 客户端IP
+    Ip,
+    /// 表单中的 `email` 字段，同一邮箱无论从哪个IP请求都共享限额
+    Email,
+    /// 登录用户ID，未登录的请求不受该规则限制
+    User,
+}
+
+impl LimitKey {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LimitKey::Ip => "ip",
+            LimitKey::Email => "email",
+            LimitKey::User => "user",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LimitRule {
+    key: LimitKey,
+    limit: u32,
+    window: Duration,
+}
+
+/// 单个路由的限流策略，可以同时按多个维度限流，任意一个维度超出限制都会拒绝请求
+#[derive(Debug, Clone)]
+pub struct Policy {
+    name: &'static str,
+    pattern: RoutePattern,
+    rules: Vec<LimitRule>,
+}
+
+impl Policy {
+    /// `name` 用于区分不同策略的计数，同一个名称的策略共享限额
+    pub fn new(name: &'static str, method: Method, path: &str) -> Self {
+        Self {
+            name,
+            pattern: RoutePattern::new(Some(method), path),
+            rules: Vec::new(),
+        }
+    }
+
+    /// 在 `window_secs` 秒内最多允许 `limit` 次请求
+    pub fn limit(mut self, key: LimitKey, limit: u32, window_secs: u64) -> Self {
+        self.rules.push(LimitRule {
+            key,
+            limit,
+            window: Duration::from_secs(window_secs),
+        });
+        self
+    }
+
+    fn needs_email(&self) -> bool {
+        self.rules.iter().any(|r| matches!(r.key, LimitKey::Email))
+    }
+}
+
+/// 基于滑动窗口的限流，用于登录、验证码、邮件等容易被暴力破解和滥用的接口
+///
+/// 需要放在 JWT 认证之后，才能按登录用户限流。超出限制时返回 429 和 `Retry-After`，
+/// 缓存出错时放行请求，避免缓存故障导致整个服务不可用。
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitLayer {
+    policies: Arc<Vec<Policy>>,
+}
+
+impl RateLimitLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn policy(mut self, policy: Policy) -> Self {
+        Arc::make_mut(&mut self.policies).push(policy);
+        self
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            policies: Arc::clone(&self.policies),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    policies: Arc<Vec<Policy>>,
+}
+
+impl<S> Service<Request<Body>> for RateLimit<S>
+where
+    S: Service<Request<Body>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // 使用已经 poll_ready 过的服务处理本次请求，克隆出的服务留给下一次调用
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let policies = Arc::clone(&self.policies);
+        Box::pin(async move {
+            let state = request.extensions().get::<ShareState>().cloned();
+            let matched = policies
+                .iter()
+                .filter(|p| p.pattern.matches(request.method(), request.uri().path()))
+                .collect::<Vec<_>>();
+            let state = match state {
+                Some(state) if state.settings.rate_limit.enabled && !matched.is_empty() => state,
+                _ => return inner.call(request).await,
+            };
+            let (request, email) = if matched.iter().any(|p| p.needs_email()) {
+                match read_form_email(request).await {
+                    Ok(r) => r,
+                    Err(res) => return Ok(res),
+                }
+            } else {
+                (request, None)
+            };
+            let ip = client_ip(&request, state.settings.rate_limit.trusted_proxies);
+            let user_id = request.extensions().get::<UserToken>().map(|t| t.user_id);
+            for policy in matched {
+                for rule in &policy.rules {
+                    let value = match rule.key {
+                        LimitKey::Ip => ip.map(|ip| ip.to_string()),
+                        LimitKey::Email => email.clone(),
+                        LimitKey::User => user_id.map(|id| id.to_string()),
+                    };
+                    let value = match value {
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    // 同一维度可以有多个不同时间窗口的规则，key 中带上窗口长度以区分
+                    let key = format!(
+                        "{}{}:{}:{}:{}",
+                        RATE_LIMIT_PREFIX,
+                        policy.name,
+                        rule.key.as_str(),
+                        rule.window.as_secs(),
+                        value
+                    );
+                    match acquire(&key, rule, &*state.cache).await {
+                        Ok(None) => {}
+                        Ok(Some(retry_after)) => {
+                            warn!(
+                                "请求过于频繁: {} [{}]，限流维度: {}",
+                                request.method(),
+                                request.uri().path(),
+                                key
+                            );
+                            return Ok(AppError::TooManyRequests(retry_after).into_response().map(box_body));
+                        }
+                        Err(e) => error!("限流计数出错，放行请求，报错信息: {}", e.to_string()),
+                    }
+                }
+            }
+            inner.call(request).await
+        })
+    }
+}
+
+/// 记录一次请求，超出限制时返回需要等待的秒数
+async fn acquire(key: &str, rule: &LimitRule, cache: &dyn Cache) -> AppResult<Option<u64>> {
+    let retry_after = cache.hit(key, rule.limit, rule.window).await?;
+    Ok(retry_after.map(|wait| ((wait.as_millis() as u64 + 999) / 1000).max(1)))
+}
+
+/// 读取表单中的 `email` 字段，读取后把请求体放回请求中交给后续的处理函数
+async fn read_form_email(request: Request<Body>) -> Result<(Request<Body>, Option<String>), Response<BoxBody>> {
+    let is_form = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |v| v.starts_with(mime::APPLICATION_WWW_FORM_URLENCODED.as_ref()));
+    if !is_form {
+        return Ok((request, None));
+    }
+    let (parts, mut body) = request.into_parts();
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| reject(ErrorCode::BadRequest))?;
+        if buf.len() + chunk.len() > MAX_FORM_BYTES {
+            return Err(reject(ErrorCode::PayloadTooLarge));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    let bytes = buf.freeze();
+    let email = serde_urlencoded::from_bytes::<HashMap<String, String>>(&bytes)
+        .ok()
+        .and_then(|mut form| form.remove("email"))
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty());
+    Ok((Request::from_parts(parts, Body::from(bytes)), email))
+}
+
+fn reject(code: ErrorCode) -> Response<BoxBody> {
+    AppError::BusinessError(code).into_response().map(box_body)
+}
+
+/// 客户端IP，部署在 `trusted_proxies` 层反向代理后面时从 `X-Forwarded-For` 读取
+///
+/// 每层代理都把它收到的请求的来源地址追加到 `X-Forwarded-For` 末尾，客户端自己设置的值只会出现在最左边，
+/// 因此跳过右边由内层代理追加的地址，取从右数第 `trusted_proxies` 个地址。
+/// 没有配置代理，或者地址数量少于代理层数时，使用连接的对端地址。
+fn client_ip<B>(request: &Request<B>, trusted_proxies: usize) -> Option<IpAddr> {
+    if trusted_proxies > 0 {
+        let forwarded = request
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .collect::<Vec<_>>();
+        let ip = forwarded
+            .iter()
+            .rev()
+            .nth(trusted_proxies - 1)
+            .and_then(|ip| ip.trim().parse().ok());
+        if ip.is_some() {
+            return ip;
+        }
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::header::RETRY_AFTER;
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::cache::MemoryCache;
+
+    fn rule(limit: u32, window: Duration) -> LimitRule {
+        LimitRule {
+            key: LimitKey::Ip,
+            limit,
+            window,
+        }
+    }
+
+    fn form_request(body: &str) -> Request<Body> {
+        Request::post("/find/user")
+            .header(CONTENT_TYPE, mime::APPLICATION_WWW_FORM_URLENCODED.as_ref())
+            .body(Body::from(String::from(body)))
+            .unwrap()
+    }
+
+    fn request(forwarded: &[&str]) -> Request<()> {
+        let mut builder = Request::get("/login");
+        for value in forwarded {
+            builder = builder.header("x-forwarded-for", *value);
+        }
+        let mut request = builder.body(()).unwrap();
+        let peer: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(peer));
+        request
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn client_ip_ignores_forwarded_without_proxy() {
+        assert_eq!(client_ip(&request(&["1.1.1.1"]), 0), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn client_ip_skips_spoofed_entries() {
+        // 客户端伪造的地址在最左边，代理追加的真实地址在最右边
+        let request = request(&["6.6.6.6, 7.7.7.7, 1.1.1.1"]);
+        assert_eq!(client_ip(&request, 1), ip("1.1.1.1"));
+        assert_eq!(client_ip(&request, 2), ip("7.7.7.7"));
+    }
+
+    #[test]
+    fn client_ip_joins_repeated_headers() {
+        let request = request(&["6.6.6.6", "1.1.1.1, 192.168.0.2"]);
+        assert_eq!(client_ip(&request, 2), ip("1.1.1.1"));
+    }
+
+    #[test]
+    fn client_ip_falls_back_to_peer() {
+        assert_eq!(client_ip(&request(&[]), 1), ip("10.0.0.1"));
+        assert_eq!(client_ip(&request(&["1.1.1.1"]), 2), ip("10.0.0.1"));
+        assert_eq!(client_ip(&request(&["unknown"]), 1), ip("10.0.0.1"));
+    }
+
+    #[tokio::test]
+    async fn acquire_rejects_after_limit() {
+        let cache = MemoryCache::new();
+        let rule = rule(3, Duration::from_secs(60));
+        for _ in 0..3 {
+            assert_eq!(
+                acquire("rate_limit:login:ip:60:1.1.1.1", &rule, &cache).await.unwrap(),
+                None
+            );
+        }
+        let retry_after = acquire("rate_limit:login:ip:60:1.1.1.1", &rule, &cache).await.unwrap();
+        assert!(matches!(retry_after, Some(secs) if secs > 0 && secs <= 60));
+        // 不同的统计维度互不影响
+        assert_eq!(
+            acquire("rate_limit:login:ip:60:2.2.2.2", &rule, &cache).await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn acquire_slides_window() {
+        let cache = MemoryCache::new();
+        let rule = rule(2, Duration::from_millis(400));
+        let key = "rate_limit:captcha:ip:0:1.1.1.1";
+        acquire(key, &rule, &cache).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        acquire(key, &rule, &cache).await.unwrap();
+        // 不足一秒的等待时间向上取整为一秒
+        assert_eq!(acquire(key, &rule, &cache).await.unwrap(), Some(1));
+        // 第一次请求移出窗口后，空出一个名额
+        tokio::time::sleep(Duration::from_millis(320)).await;
+        assert_eq!(acquire(key, &rule, &cache).await.unwrap(), None);
+        assert_eq!(acquire(key, &rule, &cache).await.unwrap(), Some(1));
+    }
+
+    #[test]
+    fn too_many_requests_sets_retry_after() {
+        let response = AppError::TooManyRequests(30).into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "30");
+    }
+
+    #[test]
+    fn policy_needs_email_only_for_email_rules() {
+        let policy = Policy::new("find_user", Method::POST, "/find/user").limit(LimitKey::Ip, 10, 3600);
+        assert!(!policy.needs_email());
+        assert!(policy.limit(LimitKey::Email, 3, 3600).needs_email());
+    }
+
+    #[tokio::test]
+    async fn read_form_email_restores_body() {
+        let body = "email=%20Tester%40Whatsoo.org%20&captcha=1234";
+        let (request, email) = read_form_email(form_request(body)).await.unwrap();
+        assert_eq!(email.as_deref(), Some("tester@whatsoo.org"));
+        let mut restored = Vec::new();
+        let mut request_body = request.into_body();
+        while let Some(chunk) = request_body.data().await {
+            restored.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(restored, body.as_bytes());
+
+        let (_, email) = read_form_email(form_request("captcha=1234")).await.unwrap();
+        assert_eq!(email, None);
+    }
+
+    #[tokio::test]
+    async fn read_form_email_rejects_large_body() {
+        let body = format!("email={}", "a".repeat(MAX_FORM_BYTES));
+        let response = read_form_email(form_request(&body)).await.err().unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
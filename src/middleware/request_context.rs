@@ -0,0 +1,189 @@
+use std::task::{Context, Poll};
+
+use axum::http::header::ACCEPT_LANGUAGE;
+use axum::http::{HeaderValue, Request, Response};
+use futures::future::BoxFuture;
+use tower::{Layer, Service};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::common::locale::Locale;
+
+/// 请求ID的请求头和响应头，反向代理已经生成请求ID时沿用代理的请求ID
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// 沿用客户端传入的请求ID时允许的最大长度
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// 当前请求的上下文，在请求处理过程中的任意位置都可以通过 [`RequestContext::current`] 获取
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: String,
+    pub locale: Locale,
+}
+
+impl RequestContext {
+    /// 不在请求处理过程中（例如后台线程）时返回 `None`
+    pub fn current() -> Option<Self> {
+        REQUEST_CONTEXT.try_with(Clone::clone).ok()
+    }
+
+    fn from_request<B>(request: &Request<B>) -> Self {
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LENGTH
+                    && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            })
+            .map(String::from)
+            .unwrap_or_else(|| Uuid::new_v4().to_simple().to_string());
+        let locale = request
+            .headers()
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .map(Locale::from_accept_language)
+            .unwrap_or_default();
+        Self { request_id, locale }
+    }
+}
+
+/// 为每个请求生成请求ID并确定响应语言
+///
+/// 请求ID会写入响应头和日志，错误响应也会带上请求ID，便于根据用户反馈定位日志。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestContextLayer;
+
+impl<S> Layer<S> for RequestContextLayer {
+    type Service = RequestContextService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestContextService { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestContextService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RequestContextService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let context = RequestContext::from_request(&request);
+        let span = info_span!("request", request_id = %context.request_id);
+        let header = HeaderValue::from_str(&context.request_id).ok();
+        let future = REQUEST_CONTEXT.scope(context, self.inner.call(request));
+        Box::pin(
+            async move {
+                let mut response = future.await?;
+                if let Some(header) = header {
+                    response.headers_mut().insert(REQUEST_ID_HEADER, header);
+                }
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::HttpBody;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+    use crate::common::err::{AppError, ErrorCode, FieldError};
+
+    /// 经过 `RequestContextLayer` 返回错误响应，返回状态码、响应头中的请求ID和响应体
+    async fn respond(request: Request<()>, error: fn() -> AppError) -> (StatusCode, Option<String>, serde_json::Value) {
+        let service = RequestContextLayer.layer(service_fn(move |_: Request<()>| async move {
+            Ok::<_, std::convert::Infallible>(error().into_response())
+        }));
+        let response = service.oneshot(request).await.unwrap();
+        let status = response.status();
+        let request_id = response
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .map(|v| v.to_str().unwrap().to_string());
+        let mut body = response.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        (status, request_id, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    fn request(accept_language: &str, request_id: &str) -> Request<()> {
+        Request::get("/")
+            .header(ACCEPT_LANGUAGE, accept_language)
+            .header(REQUEST_ID_HEADER, request_id)
+            .body(())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn error_body_uses_request_locale_and_id() {
+        let not_found = || AppError::BusinessError(ErrorCode::TopicNotFound);
+        let (status, request_id, body) = respond(request("en-US,en;q=0.9", "req-1"), not_found).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(request_id.as_deref(), Some("req-1"));
+        assert_eq!(body["code"], "TOPIC_NOT_FOUND");
+        assert_eq!(body["msg"], "Topic not found");
+        assert_eq!(body["request_id"], "req-1");
+
+        let (_, _, body) = respond(request("zh-CN", "req-2"), not_found).await;
+        assert_eq!(body["msg"], "主题不存在");
+    }
+
+    #[tokio::test]
+    async fn validation_error_lists_fields() {
+        let invalid = || AppError::ValidationError(vec![FieldError::new("email", ErrorCode::InvalidEmail)]);
+        let (status, _, body) = respond(request("en", "req-1"), invalid).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "VALIDATION_FAILED");
+        assert_eq!(body["details"][0]["field"], "email");
+        assert_eq!(body["details"][0]["code"], "INVALID_EMAIL");
+        assert_eq!(body["details"][0]["msg"], "Invalid email address");
+    }
+
+    #[tokio::test]
+    async fn internal_error_hides_cause() {
+        let internal = || AppError::ConfigError(String::from("jwt.secret=secret-value"));
+        let (status, _, body) = respond(request("en", "req-1"), internal).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "INTERNAL_ERROR");
+        assert!(!body.to_string().contains("secret-value"));
+    }
+
+    #[tokio::test]
+    async fn invalid_request_id_is_replaced() {
+        let not_found = || AppError::BusinessError(ErrorCode::TopicNotFound);
+        for request_id in &["", "bad id", "<script>", &"a".repeat(MAX_REQUEST_ID_LENGTH + 1)] {
+            let (_, response_id, body) = respond(request("en", request_id), not_found).await;
+            let response_id = response_id.unwrap();
+            assert_ne!(&response_id, request_id);
+            assert_eq!(response_id.len(), 32);
+            assert_eq!(body["request_id"], response_id.as_str());
+        }
+    }
+}
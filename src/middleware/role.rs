@@ -0,0 +1,109 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use axum::extract::{FromRequest, RequestParts};
+
+use crate::common::err::{AppError, ErrorCode};
+use crate::model::user::{Role, UserToken};
+
+/// 访问路由所需的最低角色
+pub trait RoleLevel: Send + Sync {
+    const ROLE: Role;
+}
+
+/// 版主及以上角色
+pub struct Moderator;
+
+impl RoleLevel for Moderator {
+    const ROLE: Role = Role::Moderator;
+}
+
+/// 仅管理员
+pub struct Admin;
+
+impl RoleLevel for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+/// 要求当前用户至少拥有 `R` 对应角色的提取器，权限不足时返回 403
+///
+/// ```ignore
+/// async fn handler(admin: RequireRole<Admin>) { let user_id = admin.user_id; }
+/// ```
+pub struct RequireRole<R: RoleLevel> {
+    pub user_token: UserToken,
+    _role: PhantomData<R>,
+}
+
+impl<R: RoleLevel> Deref for RequireRole<R> {
+    type Target = UserToken;
+
+    fn deref(&self) -> &Self::Target {
+        &self.user_token
+    }
+}
+
+#[async_trait]
+impl<B, R> FromRequest<B> for RequireRole<R>
+where
+    B: Send,
+    R: RoleLevel,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let user_token = UserToken::from_request(req).await?;
+        if user_token.role < R::ROLE {
+            info!("用户 {} 权限不足，需要角色: {:?}", user_token.user_id, R::ROLE);
+            return Err(AppError::BusinessError(ErrorCode::Forbidden));
+        }
+        Ok(Self {
+            user_token,
+            _role: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+    use crate::common::test_util::user_token;
+
+    async fn extract<R: RoleLevel>(role: Option<Role>) -> Result<RequireRole<R>, AppError> {
+        let mut request = Request::get("/admin").body(()).unwrap();
+        if let Some(role) = role {
+            request.extensions_mut().insert(user_token(1, role));
+        }
+        RequireRole::<R>::from_request(&mut RequestParts::new(request)).await
+    }
+
+    fn error_code<R: RoleLevel>(result: Result<RequireRole<R>, AppError>) -> Option<ErrorCode> {
+        result.err().map(|e| e.error_code())
+    }
+
+    #[tokio::test]
+    async fn require_role_checks_minimum_role() {
+        assert_eq!(
+            error_code(extract::<Moderator>(Some(Role::User)).await),
+            Some(ErrorCode::Forbidden)
+        );
+        assert!(extract::<Moderator>(Some(Role::Moderator)).await.is_ok());
+        assert!(extract::<Moderator>(Some(Role::Admin)).await.is_ok());
+        assert_eq!(
+            error_code(extract::<Admin>(Some(Role::Moderator)).await),
+            Some(ErrorCode::Forbidden)
+        );
+        let admin = extract::<Admin>(Some(Role::Admin)).await.unwrap();
+        assert_eq!(admin.user_id, 1);
+    }
+
+    #[tokio::test]
+    async fn require_role_needs_login() {
+        assert_eq!(
+            error_code(extract::<Moderator>(None).await),
+            Some(ErrorCode::NotLoggedIn)
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use axum::http::Method;
+
+/// 路由匹配规则，`:name` 匹配单个路径段，结尾的 `*` 匹配剩余所有路径段
+#[derive(Debug, Clone)]
+pub(crate) struct RoutePattern {
+    method: Option<Method>,
+    segments: Vec<String>,
+}
+
+impl RoutePattern {
+    pub(crate) fn new(method: Option<Method>, path: &str) -> Self {
+        let segments = path.split('/').filter(|s| !s.is_empty()).map(String::from).collect();
+        Self { method, segments }
+    }
+
+    pub(crate) fn matches(&self, method: &Method, path: &str) -> bool {
+        if let Some(m) = &self.method {
+            if m != method {
+                return false;
+            }
+        }
+        let mut parts = path.split('/').filter(|s| !s.is_empty());
+        for segment in &self.segments {
+            if segment == "*" {
+                return true;
+            }
+            match parts.next() {
+                Some(part) if segment.starts_with(':') || segment == part => {}
+                _ => return false,
+            }
+        }
+        parts.next().is_none()
+    }
+}
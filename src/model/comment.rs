@@ -1,15 +1,68 @@
+use crate::common::constant::MAX_COMMENT_LENGTH;
 use crate::common::date_format;
+use crate::common::err::ErrorCode;
+use crate::common::validate::{trim_string, Validate, Validator};
+use crate::AppResult;
 use sqlx::types::chrono::NaiveDateTime;
 use sqlx::FromRow;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Comment {
     pub pk_id: u64,
     pub user_id: u64,
     pub topic_id: u64,
+    pub parent_id: Option<u64>,
+    pub root_id: Option<u64>,
+    pub depth: u8,
     pub content: String,
     pub like_amount: u64,
     #[serde(with = "date_format")]
     pub create_time: NaiveDateTime,
     pub create_user: u64,
+    #[serde(with = "date_format")]
+    pub update_time: NaiveDateTime,
+    #[serde(skip)]
+    pub deleted: bool,
+}
+
+/// 评论树中的节点，`replies` 为对该评论的回复
+#[derive(Debug, Serialize)]
+pub struct CommentNode {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub replies: Vec<CommentNode>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentFront {
+    pub parent_id: Option<u64>,
+    pub content: String,
+}
+
+impl Validate for CommentFront {
+    fn trim(&mut self) {
+        trim_string(&mut self.content);
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        Validator::new()
+            .length(
+                "content",
+                &self.content,
+                1,
+                MAX_COMMENT_LENGTH,
+                ErrorCode::CommentContentLength,
+            )
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct NewComment<'a> {
+    pub user_id: u64,
+    pub topic_id: u64,
+    pub parent_id: Option<u64>,
+    pub root_id: Option<u64>,
+    pub depth: u8,
+    pub content: &'a str,
 }
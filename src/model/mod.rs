@@ -1,6 +1,8 @@
 pub mod comment;
 pub mod notice;
+pub mod search;
 pub mod star;
 pub mod tag;
 pub mod topic;
+pub mod upload;
 pub mod user;
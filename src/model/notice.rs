@@ -1,14 +1,103 @@
+use crate::common::api::Page;
 use crate::common::date_format;
+use crate::common::locale::Locale;
 use sqlx::types::chrono::NaiveDateTime;
 use sqlx::FromRow;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Notice {
     pub pk_id: u64,
-    pub content: String,
+    pub notice_type: NoticeType,
+    pub target_id: u64,
+    /// 触发通知的用户名
+    pub actor_name: String,
+    /// 通知关联的主题标题，关注通知为空
+    pub target_title: String,
     pub notified_user_id: u64,
     pub viewed: bool,
     #[serde(with = "date_format")]
     pub create_time: NaiveDateTime,
     pub create_user: u64,
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[repr(u8)]
+pub enum NoticeType {
+    Comment = 1,
+    Reply = 2,
+    Follow = 3,
+    Mention = 4,
+}
+
+#[derive(Debug)]
+pub struct NewNotice {
+    pub notice_type: NoticeType,
+    pub target_id: u64,
+    pub actor_name: String,
+    pub target_title: String,
+    pub notified_user_id: u64,
+    pub create_user: u64,
+}
+
+impl NewNotice {
+    pub fn into_notice(self, pk_id: u64, create_time: NaiveDateTime) -> Notice {
+        Notice {
+            pk_id,
+            notice_type: self.notice_type,
+            target_id: self.target_id,
+            actor_name: self.actor_name,
+            target_title: self.target_title,
+            notified_user_id: self.notified_user_id,
+            viewed: false,
+            create_time,
+            create_user: self.create_user,
+        }
+    }
+}
+
+impl Notice {
+    /// 按通知类型生成指定语言的通知文本
+    pub fn content(&self, locale: Locale) -> String {
+        let (actor, title) = (&self.actor_name, &self.target_title);
+        match (self.notice_type, locale) {
+            (NoticeType::Comment, Locale::ZhCn) => format!("{} 评论了你的主题《{}》", actor, title),
+            (NoticeType::Comment, Locale::En) => format!("{} commented on your topic \"{}\"", actor, title),
+            (NoticeType::Reply, Locale::ZhCn) => format!("{} 在主题《{}》中回复了你的评论", actor, title),
+            (NoticeType::Reply, Locale::En) => format!("{} replied to your comment in \"{}\"", actor, title),
+            (NoticeType::Follow, Locale::ZhCn) => format!("{} 关注了你", actor),
+            (NoticeType::Follow, Locale::En) => format!("{} followed you", actor),
+            (NoticeType::Mention, Locale::ZhCn) => format!("{} 在主题《{}》中提到了你", actor, title),
+            (NoticeType::Mention, Locale::En) => format!("{} mentioned you in \"{}\"", actor, title),
+        }
+    }
+
+    pub fn localize(self, locale: Locale) -> LocalizedNotice {
+        LocalizedNotice {
+            content: self.content(locale),
+            notice: self,
+        }
+    }
+}
+
+/// 附带按语言生成的通知文本的通知
+#[derive(Debug, Serialize)]
+pub struct LocalizedNotice {
+    #[serde(flatten)]
+    pub notice: Notice,
+    pub content: String,
+}
+
+/// 建立通知推送连接用的一次性票据
+#[derive(Debug, Serialize)]
+pub struct StreamTicket {
+    pub ticket: String,
+    pub expires_in: i32,
+}
+
+/// 通知列表，附带未读通知总数
+#[derive(Debug, Serialize)]
+pub struct NoticePage {
+    pub unread: i64,
+    #[serde(flatten)]
+    pub page: Page<LocalizedNotice>,
+}
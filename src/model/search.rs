@@ -0,0 +1,37 @@
+use crate::common::date_format;
+use sqlx::types::chrono::NaiveDateTime;
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub tag_id: Option<u64>,
+    pub author_id: Option<u64>,
+}
+
+/// 全文检索命中的一条记录，`hit_type` 为 `topic` 或 `comment`
+#[derive(Debug, FromRow)]
+pub struct SearchRow {
+    pub hit_type: String,
+    pub topic_id: u64,
+    pub comment_id: Option<u64>,
+    pub title: String,
+    pub content: String,
+    pub user_id: u64,
+    pub score: f64,
+    pub create_time: NaiveDateTime,
+}
+
+/// 搜索结果，`title` 和 `snippet` 已转义 HTML，命中的关键词用 `<em>` 标签包裹
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub hit_type: String,
+    pub topic_id: u64,
+    pub comment_id: Option<u64>,
+    pub title: String,
+    pub snippet: String,
+    pub user_id: u64,
+    pub score: f64,
+    #[serde(with = "date_format")]
+    pub create_time: NaiveDateTime,
+}
@@ -12,7 +12,8 @@ pub struct Star {
     pub create_time: NaiveDateTime,
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[repr(u8)]
 pub enum StarType {
     User = 1,
     Topic = 2,
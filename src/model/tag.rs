@@ -1,8 +1,12 @@
+use crate::common::constant::{MAX_TAG_LOGO_LENGTH, MAX_TAG_NAME_LENGTH};
 use crate::common::date_format;
+use crate::common::err::ErrorCode;
+use crate::common::validate::{trim_string, Validate, Validator};
+use crate::AppResult;
 use sqlx::types::chrono::NaiveDateTime;
 use sqlx::FromRow;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Tag {
     pub pk_id: u64,
     pub tag_name: String,
@@ -15,3 +19,39 @@ pub struct Tag {
     pub update_time: NaiveDateTime,
     pub update_user: u64,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct TagFront {
+    pub tag_name: String,
+    pub uk_logo: Option<String>,
+    pub parent_tag: Option<u64>,
+}
+
+impl Validate for TagFront {
+    fn trim(&mut self) {
+        trim_string(&mut self.tag_name);
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        let mut validator = Validator::new();
+        validator.length(
+            "tag_name",
+            &self.tag_name,
+            1,
+            MAX_TAG_NAME_LENGTH,
+            ErrorCode::TagNameLength,
+        );
+        if let Some(logo) = &self.uk_logo {
+            validator.url("uk_logo", logo, MAX_TAG_LOGO_LENGTH);
+        }
+        validator.finish()
+    }
+}
+
+/// 标签树中的顶层标签，`children` 为其子标签
+#[derive(Debug, Serialize)]
+pub struct TagNode {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub children: Vec<Tag>,
+}
@@ -1,9 +1,13 @@
 use chrono::NaiveDateTime;
 use sqlx::FromRow;
 
+use crate::common::constant::{MAX_TOPIC_CONTENT_LENGTH, MAX_TOPIC_TAGS, MAX_TOPIC_TAGS_STR_LENGTH, MAX_TOPIC_TITLE_LENGTH};
 use crate::common::date_format;
+use crate::common::err::ErrorCode;
+use crate::common::validate::{trim_string, Validate, Validator};
+use crate::AppResult;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Topic {
     pub pk_id: u64,
     pub user_id: u64,
@@ -26,11 +30,39 @@ pub struct TopicFront {
     pub user_id: Option<u64>,
     pub title: String,
     pub content: String,
-    pub tags: String,
+    pub tags: Vec<u64>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Pagination {
-    pub current_page: u32,
-    pub page_size: u32,
+impl Validate for TopicFront {
+    fn trim(&mut self) {
+        trim_string(&mut self.title);
+        trim_string(&mut self.content);
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        Validator::new()
+            .length("title", &self.title, 1, MAX_TOPIC_TITLE_LENGTH, ErrorCode::TitleLength)
+            .length(
+                "content",
+                &self.content,
+                1,
+                MAX_TOPIC_CONTENT_LENGTH,
+                ErrorCode::TopicContentLength,
+            )
+            .check("tags", !self.tags.is_empty(), ErrorCode::TopicTagRequired)
+            .check("tags", self.tags.len() <= MAX_TOPIC_TAGS, ErrorCode::TopicTooManyTags)
+            .check(
+                "tags",
+                self.tags_str().len() <= MAX_TOPIC_TAGS_STR_LENGTH,
+                ErrorCode::TopicTooManyTags,
+            )
+            .finish()
+    }
+}
+
+impl TopicFront {
+    /// 冗余存储在主题表中的标签，例如1-2-3-4
+    pub fn tags_str(&self) -> String {
+        self.tags.iter().map(u64::to_string).collect::<Vec<_>>().join("-")
+    }
 }
@@ -0,0 +1,51 @@
+/// 上传图片的用途，决定图片的处理方式
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageKind {
+    /// 头像，裁剪为正方形
+    Avatar,
+    /// 主题中引用的图片，保持宽高比
+    Topic,
+}
+
+impl ImageKind {
+    /// 对应 key 的第一级目录
+    pub fn dir(self) -> &'static str {
+        match self {
+            ImageKind::Avatar => "avatar",
+            ImageKind::Topic => "topic",
+        }
+    }
+
+    /// 返回的图片的最大边长，超过时缩小
+    pub fn max_size(self) -> u32 {
+        match self {
+            ImageKind::Avatar => 256,
+            ImageKind::Topic => 1920,
+        }
+    }
+
+    /// 缩略图的最大边长
+    pub fn thumbnail_sizes(self) -> &'static [u32] {
+        match self {
+            ImageKind::Avatar => &[64],
+            ImageKind::Topic => &[320],
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadedImage {
+    /// 可以直接用作 `avatar` 字段或在主题 Markdown 中引用的地址
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Thumbnail {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
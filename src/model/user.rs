@@ -2,12 +2,16 @@ use axum::extract::{FromRequest, RequestParts};
 use sqlx::types::chrono::NaiveDateTime;
 use sqlx::FromRow;
 
-use crate::common::constant::TOKEN_HEADER_NAME;
+use crate::common::constant::{
+    MAX_AVATAR_LENGTH, MAX_BLOG_URL_LENGTH, MAX_INTRODUCE_LENGTH, MAX_PASSWORD_LENGTH, TOKEN_HEADER_NAME,
+};
 use crate::common::date_format;
-use crate::common::err::AppError;
-use crate::common::util;
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::locale::Locale;
+use crate::common::validate::{Validate, Validator};
+use crate::AppResult;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub pk_id: u64,
     pub uk_username: String,
@@ -23,6 +27,132 @@ pub struct User {
     pub update_time: NaiveDateTime,
     #[serde(with = "date_format")]
     pub last_login_time: NaiveDateTime,
+    pub role: Role,
+    pub token_version: u32,
+    /// 语言偏好，登录时根据请求头 `Accept-Language` 更新
+    pub locale: String,
+}
+
+impl User {
+    pub fn locale(&self) -> Locale {
+        Locale::from_tag(&self.locale).unwrap_or_default()
+    }
+}
+
+/// 用户角色，权限从低到高排列，高级别角色拥有低级别角色的所有权限
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[repr(u8)]
+pub enum Role {
+    User = 1,
+    Moderator = 2,
+    Admin = 3,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Role::User
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleFront {
+    pub role: Role,
+}
+
+/// 用户公开的简要信息
+#[derive(Debug, Serialize, FromRow)]
+pub struct UserBrief {
+    pub pk_id: u64,
+    pub uk_username: String,
+    pub avatar: Option<String>,
+}
+
+/// 用户的公开主页，不包含邮箱和密码
+#[derive(Debug, Serialize)]
+pub struct UserProfile {
+    pub pk_id: u64,
+    pub uk_username: String,
+    pub avatar: Option<String>,
+    pub blog_url: Option<String>,
+    pub introduce: Option<String>,
+    pub github_uid: Option<String>,
+    pub role: Role,
+    #[serde(with = "date_format")]
+    pub create_time: NaiveDateTime,
+    #[serde(with = "date_format")]
+    pub last_login_time: NaiveDateTime,
+    pub topic_count: i64,
+    pub comment_count: i64,
+    pub follower_count: i64,
+}
+
+impl UserProfile {
+    pub fn new(user: User, topic_count: i64, comment_count: i64, follower_count: i64) -> Self {
+        UserProfile {
+            pk_id: user.pk_id,
+            uk_username: user.uk_username,
+            avatar: user.avatar,
+            blog_url: user.blog_url,
+            introduce: user.introduce,
+            github_uid: user.github_uid,
+            role: user.role,
+            create_time: user.create_time,
+            last_login_time: user.last_login_time,
+            topic_count,
+            comment_count,
+            follower_count,
+        }
+    }
+}
+
+/// 修改个人资料，未传或为空的字段会被清空
+#[derive(Debug, Deserialize)]
+pub struct ProfileFront {
+    pub avatar: Option<String>,
+    pub blog_url: Option<String>,
+    pub introduce: Option<String>,
+    pub github_uid: Option<String>,
+}
+
+impl ProfileFront {
+    pub fn normalize(self) -> Self {
+        ProfileFront {
+            avatar: non_empty(&self.avatar).map(String::from),
+            blog_url: non_empty(&self.blog_url).map(String::from),
+            introduce: non_empty(&self.introduce).map(String::from),
+            github_uid: non_empty(&self.github_uid).map(String::from),
+        }
+    }
+}
+
+impl Validate for ProfileFront {
+    fn validate(&self) -> AppResult<()> {
+        let mut validator = Validator::new();
+        if let Some(avatar) = non_empty(&self.avatar) {
+            validator.url("avatar", avatar, MAX_AVATAR_LENGTH);
+        }
+        if let Some(blog_url) = non_empty(&self.blog_url) {
+            validator.url("blog_url", blog_url, MAX_BLOG_URL_LENGTH);
+        }
+        if let Some(introduce) = non_empty(&self.introduce) {
+            validator.length(
+                "introduce",
+                introduce,
+                0,
+                MAX_INTRODUCE_LENGTH,
+                ErrorCode::IntroduceLength,
+            );
+        }
+        if let Some(github_uid) = non_empty(&self.github_uid) {
+            validator.github_uid("github_uid", github_uid);
+        }
+        validator.finish()
+    }
+}
+
+/// 去掉首尾空白，空字符串按未填写处理
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,6 +161,20 @@ pub struct RegisterUser {
     pub uk_email: String,
     pub email_verify_code: String,
     pub user_password: String,
+    /// 注册时请求头 `Accept-Language` 对应的语言
+    #[serde(skip)]
+    pub locale: Locale,
+}
+
+impl Validate for RegisterUser {
+    fn validate(&self) -> AppResult<()> {
+        Validator::new()
+            .username("uk_username", &self.uk_username)
+            .email("uk_email", &self.uk_email)
+            .required("email_verify_code", &self.email_verify_code)
+            .password("user_password", &self.user_password)
+            .finish()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,6 +184,16 @@ pub struct CaptchaUser {
     pub email: String,
 }
 
+impl Validate for CaptchaUser {
+    fn validate(&self) -> AppResult<()> {
+        Validator::new()
+            .required("captcha_key", &self.captcha_key)
+            .required("captcha_value", &self.captcha_value)
+            .email("email", &self.email)
+            .finish()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LoginUser {
     pub captcha_key: String,
@@ -49,6 +203,22 @@ pub struct LoginUser {
     pub password: String,
 }
 
+/// 登录时不校验密码强度，以免规则调整前注册的用户无法登录
+impl Validate for LoginUser {
+    fn validate(&self) -> AppResult<()> {
+        Validator::new()
+            .required("captcha_key", &self.captcha_key)
+            .required("captcha_value", &self.captcha_value)
+            .email("email", &self.email)
+            .check(
+                "password",
+                !self.password.is_empty() && self.password.chars().count() <= MAX_PASSWORD_LENGTH,
+                ErrorCode::Required,
+            )
+            .finish()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct FindUserPwd {
     pub captcha_key: String,
@@ -58,20 +228,49 @@ pub struct FindUserPwd {
     pub email_verify_code: Option<String>,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+/// 第一步只发送验证码，第二步携带邮箱验证码时才校验新密码
+impl Validate for FindUserPwd {
+    fn validate(&self) -> AppResult<()> {
+        let mut validator = Validator::new();
+        validator.email("email", &self.email);
+        match &self.email_verify_code {
+            None => validator
+                .required("captcha_key", &self.captcha_key)
+                .required("captcha_value", &self.captcha_value),
+            Some(code) => validator
+                .required("email_verify_code", code)
+                .password("password", &self.password),
+        };
+        validator.finish()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct UserToken {
     pub user_id: u64,
     pub uk_username: String,
     pub email: String,
+    // 兼容没有角色信息的旧 token
+    #[serde(default)]
+    pub role: Role,
+    /// 签发时用户的 token 版本，修改密码或退出所有设备后版本号增加，旧 token 随之失效
+    #[serde(default)]
+    pub ver: u32,
+    /// 登录会话ID，与刷新令牌对应
+    #[serde(default)]
+    pub sid: String,
     pub exp: usize,
 }
 
 impl UserToken {
-    pub fn new(user_id: u64, uk_username: String, email: String, exp: usize) -> Self {
+    pub fn new(user: &User, sid: String, exp: usize) -> Self {
         UserToken {
-            user_id,
-            uk_username,
-            email,
+            user_id: user.pk_id,
+            uk_username: user.uk_username.clone(),
+            email: user.uk_email.clone(),
+            role: user.role,
+            ver: user.token_version,
+            sid,
             exp,
         }
     }
@@ -85,19 +284,46 @@ where
     type Rejection = AppError;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        let token_value = req
+        // token 由认证中间件校验，校验通过后放入 extensions 中
+        if let Some(user_token) = req.extensions().and_then(|ext| ext.get::<UserToken>()) {
+            return Ok(user_token.clone());
+        }
+        let has_token = req
             .headers()
-            .and_then(|headers| headers.get(TOKEN_HEADER_NAME))
-            .ok_or(AppError::BusinessError(500, "用户未登录，请登录后操作"))?
-            .to_str()
-            .map_err(|e| {
-                error!("TOKEN解析到字符串出错: {}", e.to_string());
-                AppError::BusinessError(500, "TOKEN已失效，请重新登录")
-            })?;
-        let user_token = util::token_decode(token_value)
-            .await
-            .ok_or(AppError::BusinessError(500, "TOKEN已失效，请重新登录"))?;
-        Ok(user_token)
+            .map_or(false, |headers| headers.contains_key(TOKEN_HEADER_NAME));
+        if has_token {
+            Err(AppError::BusinessError(ErrorCode::TokenInvalid))
+        } else {
+            Err(AppError::BusinessError(ErrorCode::NotLoggedIn))
+        }
+    }
+}
+
+/// 保存在 Redis 中的刷新令牌信息，刷新令牌的格式为 `{会话ID}.{secret}`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshSession {
+    pub user_id: u64,
+    pub secret: String,
+    pub version: u32,
+    pub forever: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// 访问令牌的有效期，单位为秒
+    pub expires_in: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenFront {
+    pub refresh_token: String,
+}
+
+impl Validate for RefreshTokenFront {
+    fn validate(&self) -> AppResult<()> {
+        Validator::new().required("refresh_token", &self.refresh_token).finish()
     }
 }
 
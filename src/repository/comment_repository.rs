@@ -0,0 +1,212 @@
+use crate::common::err::{AppError, ErrorCode};
+use crate::model::comment::{Comment, NewComment};
+use crate::repository::MySqlRepository;
+use crate::AppResult;
+
+#[async_trait]
+pub trait CommentRepository: Send + Sync {
+    async fn insert_one_comment(&self, new_comment: NewComment<'_>) -> AppResult<u64>;
+
+    async fn find_comment_by_id(&self, pk_id: u64) -> AppResult<Comment>;
+
+    async fn count_comments_by_topic(&self, topic_id: u64) -> AppResult<i64>;
+
+    async fn find_comments_by_topic(&self, topic_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Comment>>;
+
+    async fn count_comments_by_user(&self, user_id: u64) -> AppResult<i64>;
+
+    async fn find_comments_by_user(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Comment>>;
+
+    async fn update_comment_content(&self, pk_id: u64, content: &str) -> AppResult<bool>;
+
+    async fn delete_comment(&self, pk_id: u64) -> AppResult<bool>;
+
+    async fn count_root_comments_by_topic(&self, topic_id: u64) -> AppResult<i64>;
+
+    /// 按顶层评论分页，一次查出这些楼层下的全部回复（包括已删除的，由调用方处理）
+    async fn find_comment_threads_by_topic(&self, topic_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Comment>>;
+}
+
+#[async_trait]
+impl CommentRepository for MySqlRepository {
+    async fn insert_one_comment(&self, new_comment: NewComment<'_>) -> AppResult<u64> {
+        sqlx::query!(
+            r#"
+            INSERT INTO comment
+                (user_id, topic_id, parent_id, root_id, depth, content, create_user)
+            VALUES
+                (?, ?, ?, ?, ?, ?, ?)
+            "#,
+            new_comment.user_id,
+            new_comment.topic_id,
+            new_comment.parent_id,
+            new_comment.root_id,
+            new_comment.depth,
+            new_comment.content,
+            new_comment.user_id,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|done| done.last_insert_id())
+    }
+
+    async fn find_comment_by_id(&self, pk_id: u64) -> AppResult<Comment> {
+        sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT pk_id, user_id, topic_id, parent_id, root_id, depth, content, like_amount, create_time, create_user,
+                update_time, deleted as `deleted: bool`
+            FROM comment
+            WHERE pk_id = ? AND deleted = 0
+            "#,
+            pk_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::BusinessError(ErrorCode::CommentNotFound),
+            e => AppError::DatabaseError(e),
+        })
+    }
+
+    async fn count_comments_by_topic(&self, topic_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM comment
+            WHERE topic_id = ? AND deleted = 0
+            "#,
+            topic_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn find_comments_by_topic(&self, topic_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Comment>> {
+        sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT pk_id, user_id, topic_id, parent_id, root_id, depth, content, like_amount, create_time, create_user,
+                update_time, deleted as `deleted: bool`
+            FROM comment
+            WHERE topic_id = ? AND deleted = 0
+            ORDER BY create_time, pk_id
+            LIMIT ? OFFSET ?
+            "#,
+            topic_id,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+
+    async fn count_comments_by_user(&self, user_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM comment
+            WHERE user_id = ? AND deleted = 0
+            "#,
+            user_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn find_comments_by_user(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Comment>> {
+        sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT pk_id, user_id, topic_id, parent_id, root_id, depth, content, like_amount, create_time, create_user,
+                update_time, deleted as `deleted: bool`
+            FROM comment
+            WHERE user_id = ? AND deleted = 0
+            ORDER BY create_time DESC, pk_id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            user_id,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+
+    async fn update_comment_content(&self, pk_id: u64, content: &str) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE comment SET content = ? WHERE pk_id = ? AND deleted = 0
+            "#,
+            content,
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    async fn delete_comment(&self, pk_id: u64) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE comment SET deleted = 1 WHERE pk_id = ? AND deleted = 0
+            "#,
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    // 顶层评论已删除但仍有回复时也要返回，保证楼层结构完整
+    async fn count_root_comments_by_topic(&self, topic_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM comment c
+            WHERE c.topic_id = ? AND c.parent_id IS NULL
+                AND (c.deleted = 0 OR EXISTS (SELECT 1 FROM comment r WHERE r.root_id = c.pk_id AND r.deleted = 0))
+            "#,
+            topic_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn find_comment_threads_by_topic(&self, topic_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Comment>> {
+        sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT c.pk_id, c.user_id, c.topic_id, c.parent_id, c.root_id, c.depth, c.content, c.like_amount,
+                c.create_time, c.create_user, c.update_time, c.deleted as `deleted: bool`
+            FROM comment c
+            JOIN (
+                SELECT rc.pk_id
+                FROM comment rc
+                WHERE rc.topic_id = ? AND rc.parent_id IS NULL
+                    AND (rc.deleted = 0 OR EXISTS (SELECT 1 FROM comment r WHERE r.root_id = rc.pk_id AND r.deleted = 0))
+                ORDER BY rc.create_time, rc.pk_id
+                LIMIT ? OFFSET ?
+            ) roots ON c.pk_id = roots.pk_id OR c.root_id = roots.pk_id
+            ORDER BY c.create_time, c.pk_id
+            "#,
+            topic_id,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+}
@@ -0,0 +1,930 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet};
+use std::iter;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{Local, NaiveDateTime};
+
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::locale::Locale;
+use crate::model::comment::{Comment, NewComment};
+use crate::model::notice::{NewNotice, Notice};
+use crate::model::search::SearchRow;
+use crate::model::star::{Star, StarType};
+use crate::model::tag::{Tag, TagFront};
+use crate::model::topic::{Topic, TopicFront};
+use crate::model::user::{ProfileFront, RegisterUser, Role, User, UserBrief};
+use crate::repository::search_repository::merge_hits;
+use crate::repository::{
+    CommentRepository, NoticeRepository, SearchRepository, StarRepository, TagRepository, TopicRepository,
+    UserRepository,
+};
+use crate::AppResult;
+
+/// 与表结构中的默认值保持一致
+const DEFAULT_AVATAR: &str = "https://avatars3.githubusercontent.com/u/18442141";
+const DEFAULT_TAG_LOGO: &str = "https://avatars.githubusercontent.com/u/40875493?s=60&v=4";
+
+/// 自增主键的表
+struct Table<T> {
+    rows: BTreeMap<u64, T>,
+    last_id: u64,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Self {
+            rows: BTreeMap::new(),
+            last_id: 0,
+        }
+    }
+}
+
+impl<T> Table<T> {
+    fn insert(&mut self, row: impl FnOnce(u64) -> T) -> u64 {
+        self.last_id += 1;
+        self.rows.insert(self.last_id, row(self.last_id));
+        self.last_id
+    }
+}
+
+struct TopicRow {
+    topic: Topic,
+    deleted: bool,
+}
+
+#[derive(Default)]
+struct Tables {
+    users: Table<User>,
+    topics: Table<TopicRow>,
+    comments: Table<Comment>,
+    stars: Table<Star>,
+    notices: Table<Notice>,
+    tags: Table<Tag>,
+    /// `(tag_id, topic_id)`
+    tag_topics: BTreeSet<(u64, u64)>,
+}
+
+impl Tables {
+    fn user_by_email(&self, email: &str) -> Option<&User> {
+        self.users
+            .rows
+            .values()
+            .find(|u| u.uk_email.eq_ignore_ascii_case(email))
+    }
+
+    fn user_by_username(&self, username: &str) -> Option<&User> {
+        self.users
+            .rows
+            .values()
+            .find(|u| u.uk_username.eq_ignore_ascii_case(username))
+    }
+
+    fn user_brief(&self, pk_id: u64) -> Option<UserBrief> {
+        self.users.rows.get(&pk_id).map(|u| UserBrief {
+            pk_id: u.pk_id,
+            uk_username: u.uk_username.clone(),
+            avatar: u.avatar.clone(),
+        })
+    }
+
+    fn topic(&self, pk_id: u64) -> Option<&Topic> {
+        self.topics.rows.get(&pk_id).filter(|r| !r.deleted).map(|r| &r.topic)
+    }
+
+    fn topic_mut(&mut self, pk_id: u64) -> Option<&mut Topic> {
+        self.topics
+            .rows
+            .get_mut(&pk_id)
+            .filter(|r| !r.deleted)
+            .map(|r| &mut r.topic)
+    }
+
+    /// 未删除的主题，`top_first` 为 `true` 时置顶的主题排在最前面，其余按创建时间倒序
+    fn sorted_topics(&self, top_first: bool, filter: impl Fn(&Topic) -> bool) -> Vec<&Topic> {
+        let mut topics: Vec<&Topic> = self
+            .topics
+            .rows
+            .values()
+            .filter(|r| !r.deleted && filter(&r.topic))
+            .map(|r| &r.topic)
+            .collect();
+        topics.sort_by_key(|t| (Reverse(top_first && t.top), Reverse(t.create_time), Reverse(t.pk_id)));
+        topics
+    }
+
+    /// 该标签及其子标签关联的主题
+    fn topic_ids_by_tag(&self, tag_id: u64) -> BTreeSet<u64> {
+        let tag_ids: BTreeSet<u64> = self
+            .tags
+            .rows
+            .values()
+            .filter(|t| t.parent_tag == tag_id)
+            .map(|t| t.pk_id)
+            .chain(iter::once(tag_id))
+            .collect();
+        self.tag_topics
+            .iter()
+            .filter(|(tag_id, _)| tag_ids.contains(tag_id))
+            .map(|&(_, topic_id)| topic_id)
+            .collect()
+    }
+
+    fn set_topic_tags(&mut self, topic_id: u64, tag_ids: &[u64]) {
+        self.tag_topics = std::mem::take(&mut self.tag_topics)
+            .into_iter()
+            .filter(|&(_, t)| t != topic_id)
+            .collect();
+        self.tag_topics.extend(tag_ids.iter().map(|&tag_id| (tag_id, topic_id)));
+    }
+
+    fn comment(&self, pk_id: u64) -> Option<&Comment> {
+        self.comments.rows.get(&pk_id).filter(|c| !c.deleted)
+    }
+
+    fn comment_mut(&mut self, pk_id: u64) -> Option<&mut Comment> {
+        self.comments.rows.get_mut(&pk_id).filter(|c| !c.deleted)
+    }
+
+    fn sorted_comments(&self, desc: bool, filter: impl Fn(&Comment) -> bool) -> Vec<&Comment> {
+        let mut comments: Vec<&Comment> = self.comments.rows.values().filter(|c| filter(c)).collect();
+        comments.sort_by_key(|c| (c.create_time, c.pk_id));
+        if desc {
+            comments.reverse();
+        }
+        comments
+    }
+
+    // 顶层评论已删除但仍有回复时也要返回，保证楼层结构完整
+    fn root_comments(&self, topic_id: u64) -> Vec<&Comment> {
+        self.sorted_comments(false, |c| {
+            c.topic_id == topic_id
+                && c.parent_id.is_none()
+                && (!c.deleted
+                    || self
+                        .comments
+                        .rows
+                        .values()
+                        .any(|r| r.root_id == Some(c.pk_id) && !r.deleted))
+        })
+    }
+
+    fn tag_name_exists(&self, tag_name: &str, except: Option<u64>) -> bool {
+        self.tags
+            .rows
+            .values()
+            .any(|t| Some(t.pk_id) != except && t.tag_name.eq_ignore_ascii_case(tag_name))
+    }
+
+    fn sorted_stars(&self, filter: impl Fn(&Star) -> bool) -> Vec<&Star> {
+        let mut stars: Vec<&Star> = self.stars.rows.values().filter(|s| filter(s)).collect();
+        stars.sort_by_key(|s| (Reverse(s.create_time), Reverse(s.pk_id)));
+        stars
+    }
+
+    // 帖子收藏数和评论点赞数与 star 表保持同步，关注没有冗余计数
+    fn star_count_mut(&mut self, star_type: StarType, star_id: u64) -> Option<&mut u64> {
+        match star_type {
+            StarType::Topic => self.topics.rows.get_mut(&star_id).map(|r| &mut r.topic.like_times),
+            StarType::Comment => self.comments.rows.get_mut(&star_id).map(|c| &mut c.like_amount),
+            StarType::User => None,
+        }
+    }
+
+    fn sorted_notices(&self, notified_user_id: u64) -> Vec<&Notice> {
+        let mut notices: Vec<&Notice> = self
+            .notices
+            .rows
+            .values()
+            .filter(|n| n.notified_user_id == notified_user_id)
+            .collect();
+        notices.sort_by_key(|n| (n.viewed, Reverse(n.create_time), Reverse(n.pk_id)));
+        notices
+    }
+
+    // 不区分大小写地匹配空白分隔的每个词，相关度为所有词出现的总次数，
+    // 命中的主题和评论分别按相关度和创建时间倒序排列
+    fn search_hits(
+        &self,
+        keyword: &str,
+        tag_id: Option<u64>,
+        author_id: Option<u64>,
+    ) -> (Vec<SearchRow>, Vec<SearchRow>) {
+        let terms: Vec<String> = keyword.split_whitespace().map(str::to_lowercase).collect();
+        let score = |text: &str| {
+            let text = text.to_lowercase();
+            terms
+                .iter()
+                .map(|term| text.matches(term.as_str()).count())
+                .sum::<usize>() as f64
+        };
+        let tag_topics = tag_id.map(|tag_id| self.topic_ids_by_tag(tag_id));
+        let in_scope = |topic_id: u64, user_id: u64| {
+            author_id.map_or(true, |id| id == user_id)
+                && tag_topics.as_ref().map_or(true, |ids| ids.contains(&topic_id))
+        };
+        let mut topic_hits = self
+            .sorted_topics(false, |t| in_scope(t.pk_id, t.user_id))
+            .into_iter()
+            .map(|t| SearchRow {
+                hit_type: String::from("topic"),
+                topic_id: t.pk_id,
+                comment_id: None,
+                title: t.title.clone(),
+                content: t.content.clone(),
+                user_id: t.user_id,
+                score: score(&format!("{} {}", t.title, t.content)),
+                create_time: t.create_time,
+            });
+        let mut comment_hits = self
+            .sorted_comments(true, |c| !c.deleted && in_scope(c.topic_id, c.user_id))
+            .into_iter()
+            .filter_map(|c| {
+                let topic = self.topic(c.topic_id)?;
+                Some(SearchRow {
+                    hit_type: String::from("comment"),
+                    topic_id: c.topic_id,
+                    comment_id: Some(c.pk_id),
+                    title: topic.title.clone(),
+                    content: c.content.clone(),
+                    user_id: c.user_id,
+                    score: score(&c.content),
+                    create_time: c.create_time,
+                })
+            });
+        let sorted = |hits: &mut dyn Iterator<Item = SearchRow>| {
+            let mut hits: Vec<SearchRow> = hits.filter(|h| h.score > 0.0).collect();
+            hits.sort_by(|a, b| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(Ordering::Equal)
+                    .then(b.create_time.cmp(&a.create_time))
+            });
+            hits
+        };
+        (sorted(&mut topic_hits), sorted(&mut comment_hits))
+    }
+}
+
+fn now() -> NaiveDateTime {
+    Local::now().naive_local()
+}
+
+fn page<T: Clone>(rows: Vec<&T>, offset: u32, limit: u32) -> Vec<T> {
+    rows.into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .cloned()
+        .collect()
+}
+
+/// 进程内的数据仓库，数据保存在内存中，重启后丢失
+///
+/// 行为与 MySQL 实现保持一致，包括软删除、唯一约束和排序规则，用于开发环境和不依赖数据库的测试。
+/// 与 MySQL 默认的排序规则一样，用户名、邮箱和标签名称不区分大小写。
+#[derive(Default)]
+pub struct MemoryRepository {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        // 持有锁期间不会 panic，锁被污染时数据仍然可用
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryRepository {
+    async fn count_by_email(&self, email: &str) -> AppResult<i64> {
+        Ok(self.tables().user_by_email(email).is_some() as i64)
+    }
+
+    async fn update_user_pwd(&self, pwd: String, id: u64) -> AppResult<u64> {
+        match self.tables().users.rows.get_mut(&id) {
+            Some(user) => {
+                user.user_password = pwd;
+                user.update_time = now();
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    async fn count_by_username(&self, username: String) -> AppResult<i64> {
+        Ok(self.tables().user_by_username(&username).is_some() as i64)
+    }
+
+    async fn insert_one_user(&self, user: RegisterUser) -> AppResult<u64> {
+        let mut tables = self.tables();
+        if tables.user_by_email(&user.uk_email).is_some() || tables.user_by_username(&user.uk_username).is_some() {
+            return Err(AppError::BusinessError(ErrorCode::UserExists));
+        }
+        let now = now();
+        Ok(tables.users.insert(|pk_id| User {
+            pk_id,
+            uk_username: user.uk_username,
+            uk_email: user.uk_email,
+            user_password: user.user_password,
+            avatar: Some(String::from(DEFAULT_AVATAR)),
+            blog_url: None,
+            introduce: None,
+            github_uid: None,
+            create_time: now,
+            update_time: now,
+            last_login_time: now,
+            role: Role::default(),
+            token_version: 0,
+            locale: String::from(user.locale.as_str()),
+        }))
+    }
+
+    async fn find_user_by_email(&self, email: &str) -> AppResult<User> {
+        self.tables()
+            .user_by_email(email)
+            .cloned()
+            .ok_or(AppError::BusinessError(ErrorCode::UserNotFound))
+    }
+
+    async fn find_user_by_id(&self, pk_id: u64) -> AppResult<User> {
+        self.tables()
+            .users
+            .rows
+            .get(&pk_id)
+            .cloned()
+            .ok_or(AppError::BusinessError(ErrorCode::UserNotFound))
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> AppResult<User> {
+        self.tables()
+            .user_by_username(username)
+            .cloned()
+            .ok_or(AppError::BusinessError(ErrorCode::UserNotFound))
+    }
+
+    async fn find_user_brief_by_username(&self, username: &str) -> AppResult<Option<UserBrief>> {
+        let tables = self.tables();
+        Ok(tables
+            .user_by_username(username)
+            .and_then(|u| tables.user_brief(u.pk_id)))
+    }
+
+    async fn update_user_profile(&self, pk_id: u64, profile: &ProfileFront) -> AppResult<bool> {
+        let mut tables = self.tables();
+        let same = |a: &Option<String>, b: &Option<String>| match (a, b) {
+            (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+            _ => false,
+        };
+        let conflict = tables.users.rows.values().any(|u| {
+            u.pk_id != pk_id && (same(&u.blog_url, &profile.blog_url) || same(&u.github_uid, &profile.github_uid))
+        });
+        if conflict {
+            return Err(AppError::BusinessError(ErrorCode::ProfileConflict));
+        }
+        match tables.users.rows.get_mut(&pk_id) {
+            Some(user) => {
+                user.avatar = profile.avatar.clone();
+                user.blog_url = profile.blog_url.clone();
+                user.introduce = profile.introduce.clone();
+                user.github_uid = profile.github_uid.clone();
+                user.update_time = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn update_last_login(&self, pk_id: u64, locale: Locale) -> AppResult<bool> {
+        match self.tables().users.rows.get_mut(&pk_id) {
+            Some(user) => {
+                user.last_login_time = now();
+                user.locale = String::from(locale.as_str());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn update_user_role(&self, pk_id: u64, role: Role) -> AppResult<bool> {
+        match self.tables().users.rows.get_mut(&pk_id) {
+            Some(user) => {
+                user.role = role;
+                user.update_time = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn find_token_version(&self, pk_id: u64) -> AppResult<u32> {
+        self.tables()
+            .users
+            .rows
+            .get(&pk_id)
+            .map(|u| u.token_version)
+            .ok_or(AppError::BusinessError(ErrorCode::UserNotFound))
+    }
+
+    async fn increase_token_version(&self, pk_id: u64) -> AppResult<bool> {
+        match self.tables().users.rows.get_mut(&pk_id) {
+            Some(user) => {
+                user.token_version += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl TopicRepository for MemoryRepository {
+    async fn insert_one_topic(&self, new_topic: &TopicFront) -> AppResult<u64> {
+        let user_id = new_topic
+            .user_id
+            .ok_or(AppError::BusinessError(ErrorCode::NotLoggedIn))?;
+        let mut tables = self.tables();
+        let now = now();
+        let pk_id = tables.topics.insert(|pk_id| TopicRow {
+            topic: Topic {
+                pk_id,
+                user_id,
+                title: new_topic.title.clone(),
+                content: new_topic.content.clone(),
+                tags: new_topic.tags_str(),
+                like_times: 0,
+                click_times: 0,
+                top: false,
+                create_time: now,
+                create_user: user_id,
+                update_time: now,
+                update_user: user_id,
+            },
+            deleted: false,
+        });
+        tables.set_topic_tags(pk_id, &new_topic.tags);
+        Ok(pk_id)
+    }
+
+    async fn update_topic_top(&self, pk_id: u64, top: bool) -> AppResult<bool> {
+        match self.tables().topic_mut(pk_id) {
+            Some(topic) => {
+                topic.top = top;
+                topic.update_time = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn find_topic_by_id(&self, pk_id: u64) -> AppResult<Topic> {
+        self.tables()
+            .topic(pk_id)
+            .cloned()
+            .ok_or(AppError::BusinessError(ErrorCode::TopicNotFound))
+    }
+
+    async fn increase_click_times(&self, pk_id: u64) -> AppResult<bool> {
+        match self.tables().topic_mut(pk_id) {
+            Some(topic) => {
+                topic.click_times += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn count_topics(&self) -> AppResult<i64> {
+        Ok(self.tables().sorted_topics(false, |_| true).len() as i64)
+    }
+
+    async fn find_topics(&self, offset: u32, limit: u32) -> AppResult<Vec<Topic>> {
+        Ok(page(self.tables().sorted_topics(true, |_| true), offset, limit))
+    }
+
+    async fn count_topics_by_user(&self, user_id: u64) -> AppResult<i64> {
+        Ok(self.tables().sorted_topics(false, |t| t.user_id == user_id).len() as i64)
+    }
+
+    async fn find_topics_by_user(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Topic>> {
+        let tables = self.tables();
+        Ok(page(
+            tables.sorted_topics(false, |t| t.user_id == user_id),
+            offset,
+            limit,
+        ))
+    }
+
+    async fn update_topic(&self, pk_id: u64, topic: TopicFront, update_user: u64) -> AppResult<bool> {
+        let mut tables = self.tables();
+        let tags_str = topic.tags_str();
+        match tables.topic_mut(pk_id) {
+            Some(current) => {
+                current.title = topic.title;
+                current.content = topic.content;
+                current.tags = tags_str;
+                current.update_user = update_user;
+                current.update_time = now();
+            }
+            None => return Ok(false),
+        }
+        tables.set_topic_tags(pk_id, &topic.tags);
+        Ok(true)
+    }
+
+    async fn delete_topic(&self, pk_id: u64, update_user: u64) -> AppResult<bool> {
+        match self.tables().topics.rows.get_mut(&pk_id).filter(|r| !r.deleted) {
+            Some(row) => {
+                row.deleted = true;
+                row.topic.update_user = update_user;
+                row.topic.update_time = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn count_topics_by_tag(&self, tag_id: u64) -> AppResult<i64> {
+        let tables = self.tables();
+        let topic_ids = tables.topic_ids_by_tag(tag_id);
+        Ok(tables.sorted_topics(false, |t| topic_ids.contains(&t.pk_id)).len() as i64)
+    }
+
+    async fn find_topics_by_tag(&self, tag_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Topic>> {
+        let tables = self.tables();
+        let topic_ids = tables.topic_ids_by_tag(tag_id);
+        Ok(page(
+            tables.sorted_topics(true, |t| topic_ids.contains(&t.pk_id)),
+            offset,
+            limit,
+        ))
+    }
+}
+
+#[async_trait]
+impl CommentRepository for MemoryRepository {
+    async fn insert_one_comment(&self, new_comment: NewComment<'_>) -> AppResult<u64> {
+        let now = now();
+        Ok(self.tables().comments.insert(|pk_id| Comment {
+            pk_id,
+            user_id: new_comment.user_id,
+            topic_id: new_comment.topic_id,
+            parent_id: new_comment.parent_id,
+            root_id: new_comment.root_id,
+            depth: new_comment.depth,
+            content: String::from(new_comment.content),
+            like_amount: 0,
+            create_time: now,
+            create_user: new_comment.user_id,
+            update_time: now,
+            deleted: false,
+        }))
+    }
+
+    async fn find_comment_by_id(&self, pk_id: u64) -> AppResult<Comment> {
+        self.tables()
+            .comment(pk_id)
+            .cloned()
+            .ok_or(AppError::BusinessError(ErrorCode::CommentNotFound))
+    }
+
+    async fn count_comments_by_topic(&self, topic_id: u64) -> AppResult<i64> {
+        let tables = self.tables();
+        Ok(tables
+            .sorted_comments(false, |c| c.topic_id == topic_id && !c.deleted)
+            .len() as i64)
+    }
+
+    async fn find_comments_by_topic(&self, topic_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Comment>> {
+        let tables = self.tables();
+        Ok(page(
+            tables.sorted_comments(false, |c| c.topic_id == topic_id && !c.deleted),
+            offset,
+            limit,
+        ))
+    }
+
+    async fn count_comments_by_user(&self, user_id: u64) -> AppResult<i64> {
+        let tables = self.tables();
+        Ok(tables
+            .sorted_comments(true, |c| c.user_id == user_id && !c.deleted)
+            .len() as i64)
+    }
+
+    async fn find_comments_by_user(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Comment>> {
+        let tables = self.tables();
+        Ok(page(
+            tables.sorted_comments(true, |c| c.user_id == user_id && !c.deleted),
+            offset,
+            limit,
+        ))
+    }
+
+    async fn update_comment_content(&self, pk_id: u64, content: &str) -> AppResult<bool> {
+        match self.tables().comment_mut(pk_id) {
+            Some(comment) => {
+                comment.content = String::from(content);
+                comment.update_time = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_comment(&self, pk_id: u64) -> AppResult<bool> {
+        match self.tables().comment_mut(pk_id) {
+            Some(comment) => {
+                comment.deleted = true;
+                comment.update_time = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn count_root_comments_by_topic(&self, topic_id: u64) -> AppResult<i64> {
+        Ok(self.tables().root_comments(topic_id).len() as i64)
+    }
+
+    async fn find_comment_threads_by_topic(&self, topic_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Comment>> {
+        let tables = self.tables();
+        let roots: BTreeSet<u64> = tables
+            .root_comments(topic_id)
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|c| c.pk_id)
+            .collect();
+        let threads = tables.sorted_comments(false, |c| {
+            roots.contains(&c.pk_id) || c.root_id.map_or(false, |root_id| roots.contains(&root_id))
+        });
+        Ok(threads.into_iter().cloned().collect())
+    }
+}
+
+#[async_trait]
+impl StarRepository for MemoryRepository {
+    async fn insert_star(&self, star_type: StarType, user_id: u64, star_id: u64) -> AppResult<bool> {
+        let mut tables = self.tables();
+        let exists = tables
+            .stars
+            .rows
+            .values()
+            .any(|s| s.star_type == star_type && s.user_id == user_id && s.star_id == star_id);
+        if exists {
+            return Ok(false);
+        }
+        tables.stars.insert(|pk_id| Star {
+            pk_id,
+            star_type,
+            user_id,
+            star_id,
+            create_time: now(),
+        });
+        if let Some(count) = tables.star_count_mut(star_type, star_id) {
+            *count += 1;
+        }
+        Ok(true)
+    }
+
+    async fn delete_star(&self, star_type: StarType, user_id: u64, star_id: u64) -> AppResult<bool> {
+        let mut tables = self.tables();
+        let pk_id = tables
+            .stars
+            .rows
+            .values()
+            .find(|s| s.star_type == star_type && s.user_id == user_id && s.star_id == star_id)
+            .map(|s| s.pk_id);
+        let pk_id = match pk_id {
+            Some(pk_id) => pk_id,
+            None => return Ok(false),
+        };
+        tables.stars.rows.remove(&pk_id);
+        if let Some(count) = tables.star_count_mut(star_type, star_id) {
+            *count = count.saturating_sub(1);
+        }
+        Ok(true)
+    }
+
+    async fn count_stars_by_user(&self, star_type: StarType, user_id: u64) -> AppResult<i64> {
+        let tables = self.tables();
+        Ok(tables
+            .sorted_stars(|s| s.star_type == star_type && s.user_id == user_id)
+            .len() as i64)
+    }
+
+    async fn count_stars_by_target(&self, star_type: StarType, star_id: u64) -> AppResult<i64> {
+        let tables = self.tables();
+        Ok(tables
+            .sorted_stars(|s| s.star_type == star_type && s.star_id == star_id)
+            .len() as i64)
+    }
+
+    async fn count_star_topics_by_user(&self, user_id: u64) -> AppResult<i64> {
+        let tables = self.tables();
+        let stars = tables.sorted_stars(|s| {
+            s.star_type == StarType::Topic && s.user_id == user_id && tables.topic(s.star_id).is_some()
+        });
+        Ok(stars.len() as i64)
+    }
+
+    async fn find_star_topics_by_user(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Topic>> {
+        let tables = self.tables();
+        let topics = tables
+            .sorted_stars(|s| s.star_type == StarType::Topic && s.user_id == user_id)
+            .into_iter()
+            .filter_map(|s| tables.topic(s.star_id))
+            .collect();
+        Ok(page(topics, offset, limit))
+    }
+
+    async fn find_followers(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<UserBrief>> {
+        let tables = self.tables();
+        Ok(tables
+            .sorted_stars(|s| s.star_type == StarType::User && s.star_id == user_id)
+            .into_iter()
+            .filter_map(|s| tables.user_brief(s.user_id))
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn find_followings(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<UserBrief>> {
+        let tables = self.tables();
+        Ok(tables
+            .sorted_stars(|s| s.star_type == StarType::User && s.user_id == user_id)
+            .into_iter()
+            .filter_map(|s| tables.user_brief(s.star_id))
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl NoticeRepository for MemoryRepository {
+    async fn insert_one_notice(&self, notice: &NewNotice) -> AppResult<u64> {
+        let now = now();
+        Ok(self.tables().notices.insert(|pk_id| Notice {
+            pk_id,
+            notice_type: notice.notice_type,
+            target_id: notice.target_id,
+            actor_name: notice.actor_name.clone(),
+            target_title: notice.target_title.clone(),
+            notified_user_id: notice.notified_user_id,
+            viewed: false,
+            create_time: now,
+            create_user: notice.create_user,
+        }))
+    }
+
+    async fn count_notices(&self, notified_user_id: u64) -> AppResult<i64> {
+        Ok(self.tables().sorted_notices(notified_user_id).len() as i64)
+    }
+
+    async fn count_unread_notices(&self, notified_user_id: u64) -> AppResult<i64> {
+        let tables = self.tables();
+        let unread = tables
+            .sorted_notices(notified_user_id)
+            .into_iter()
+            .filter(|n| !n.viewed);
+        Ok(unread.count() as i64)
+    }
+
+    async fn find_notices(&self, notified_user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Notice>> {
+        Ok(page(self.tables().sorted_notices(notified_user_id), offset, limit))
+    }
+
+    async fn mark_notice_viewed(&self, pk_id: u64, notified_user_id: u64) -> AppResult<bool> {
+        let mut tables = self.tables();
+        match tables
+            .notices
+            .rows
+            .get_mut(&pk_id)
+            .filter(|n| n.notified_user_id == notified_user_id)
+        {
+            Some(notice) => {
+                notice.viewed = true;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn mark_all_notices_viewed(&self, notified_user_id: u64) -> AppResult<u64> {
+        let mut rows_affected = 0;
+        for notice in self.tables().notices.rows.values_mut() {
+            if notice.notified_user_id == notified_user_id && !notice.viewed {
+                notice.viewed = true;
+                rows_affected += 1;
+            }
+        }
+        Ok(rows_affected)
+    }
+
+    async fn find_unread_notified_users(&self, since: NaiveDateTime) -> AppResult<Vec<u64>> {
+        let tables = self.tables();
+        let users: BTreeSet<u64> = tables
+            .notices
+            .rows
+            .values()
+            .filter(|n| !n.viewed && n.create_time >= since)
+            .map(|n| n.notified_user_id)
+            .collect();
+        Ok(users.into_iter().collect())
+    }
+}
+
+#[async_trait]
+impl TagRepository for MemoryRepository {
+    async fn insert_one_tag(&self, tag: &TagFront, parent_tag: u64, user_id: u64) -> AppResult<u64> {
+        let mut tables = self.tables();
+        if tables.tag_name_exists(&tag.tag_name, None) {
+            return Err(AppError::BusinessError(ErrorCode::TagNameExists));
+        }
+        let now = now();
+        Ok(tables.tags.insert(|pk_id| Tag {
+            pk_id,
+            tag_name: tag.tag_name.clone(),
+            uk_logo: tag.uk_logo.clone().unwrap_or_else(|| String::from(DEFAULT_TAG_LOGO)),
+            parent_tag,
+            create_time: now,
+            create_user: user_id,
+            update_time: now,
+            update_user: user_id,
+        }))
+    }
+
+    async fn find_tag_by_id(&self, pk_id: u64) -> AppResult<Tag> {
+        self.tables()
+            .tags
+            .rows
+            .get(&pk_id)
+            .cloned()
+            .ok_or(AppError::BusinessError(ErrorCode::TagNotFound))
+    }
+
+    async fn find_all_tags(&self) -> AppResult<Vec<Tag>> {
+        let tables = self.tables();
+        let mut tags: Vec<Tag> = tables.tags.rows.values().cloned().collect();
+        tags.sort_by_key(|t| (t.parent_tag, t.pk_id));
+        Ok(tags)
+    }
+
+    async fn count_child_tags(&self, pk_id: u64) -> AppResult<i64> {
+        let tables = self.tables();
+        Ok(tables.tags.rows.values().filter(|t| t.parent_tag == pk_id).count() as i64)
+    }
+
+    async fn update_tag(&self, pk_id: u64, tag: &TagFront, parent_tag: u64, user_id: u64) -> AppResult<bool> {
+        let mut tables = self.tables();
+        if tables.tag_name_exists(&tag.tag_name, Some(pk_id)) {
+            return Err(AppError::BusinessError(ErrorCode::TagNameExists));
+        }
+        match tables.tags.rows.get_mut(&pk_id) {
+            Some(row) => {
+                row.tag_name = tag.tag_name.clone();
+                if let Some(logo) = &tag.uk_logo {
+                    row.uk_logo = logo.clone();
+                }
+                row.parent_tag = parent_tag;
+                row.update_user = user_id;
+                row.update_time = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_tag(&self, pk_id: u64) -> AppResult<bool> {
+        let mut tables = self.tables();
+        tables.tag_topics = std::mem::take(&mut tables.tag_topics)
+            .into_iter()
+            .filter(|&(tag_id, _)| tag_id != pk_id)
+            .collect();
+        Ok(tables.tags.rows.remove(&pk_id).is_some())
+    }
+}
+
+#[async_trait]
+impl SearchRepository for MemoryRepository {
+    async fn count_hits(&self, keyword: &str, tag_id: Option<u64>, author_id: Option<u64>) -> AppResult<i64> {
+        let (topics, comments) = self.tables().search_hits(keyword, tag_id, author_id);
+        Ok((topics.len() + comments.len()) as i64)
+    }
+
+    async fn find_hits(
+        &self,
+        keyword: &str,
+        tag_id: Option<u64>,
+        author_id: Option<u64>,
+        offset: u32,
+        limit: u32,
+    ) -> AppResult<Vec<SearchRow>> {
+        let (topics, comments) = self.tables().search_hits(keyword, tag_id, author_id);
+        Ok(merge_hits(topics, comments, offset, limit))
+    }
+}
@@ -0,0 +1,59 @@
+use sqlx::MySqlPool;
+use sqlx::migrate::Migrator;
+
+use crate::AppResult;
+
+/// 编译时嵌入 migrations 目录下的迁移，已执行的版本记录在 `_sqlx_migrations` 表中
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// 按版本顺序执行所有未应用的迁移，已应用的迁移被修改时返回错误
+pub async fn run(pool: &MySqlPool) -> AppResult<()> {
+    MIGRATOR.run(pool).await?;
+    if let Some(latest) = MIGRATOR.iter().last() {
+        info!("数据库迁移完成，当前版本 {} {}", latest.version, latest.description);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MySQL 测试库地址，设置后通过 `cargo test -- --ignored` 运行
+    const DATABASE_URL_ENV: &str = "WHATSOO_TEST_DATABASE_URL";
+
+    #[test]
+    fn migrations_are_numbered_in_order() {
+        let versions = MIGRATOR.iter().map(|m| m.version).collect::<Vec<_>>();
+        let expected = (1..=versions.len() as i64).collect::<Vec<_>>();
+        assert_eq!(versions, expected);
+        assert!(MIGRATOR.iter().all(|m| !m.description.is_empty()));
+    }
+
+    #[test]
+    fn migrations_never_drop_database() {
+        for migration in MIGRATOR.iter() {
+            let sql = migration.sql.to_lowercase();
+            assert!(!sql.contains("drop database"), "{}", migration.description);
+            assert!(!sql.contains("drop table"), "{}", migration.description);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn mysql_migrations_are_idempotent() {
+        let url = match std::env::var(DATABASE_URL_ENV) {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let pool = MySqlPool::connect(&url).await.unwrap();
+        run(&pool).await.unwrap();
+        // 再次执行时所有版本都已应用，不做任何修改
+        run(&pool).await.unwrap();
+        let applied: i64 = sqlx::query_scalar("select count(*) from _sqlx_migrations where success = true")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(applied, MIGRATOR.iter().count() as i64);
+    }
+}
@@ -1,2 +1,81 @@
+use std::sync::Arc;
+
+use sqlx::MySqlPool;
+
+pub use comment_repository::CommentRepository;
+pub use memory_repository::MemoryRepository;
+pub use notice_repository::NoticeRepository;
+pub use search_repository::SearchRepository;
+pub use star_repository::StarRepository;
+pub use tag_repository::TagRepository;
+pub use topic_repository::TopicRepository;
+pub use user_repository::UserRepository;
+
+pub mod comment_repository;
+mod memory_repository;
+pub mod migration;
+pub mod notice_repository;
+pub mod search_repository;
+pub mod star_repository;
+pub mod tag_repository;
 pub mod topic_repository;
 pub mod user_repository;
+
+/// 基于 MySQL 的数据访问，各个仓库 trait 的实现分别放在对应的模块中
+#[derive(Clone)]
+pub struct MySqlRepository {
+    pool: MySqlPool,
+}
+
+impl MySqlRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// 所有的数据仓库，通过 `ShareState` 注入到路由和服务中
+///
+/// 生产环境使用 [`Repositories::mysql`]，[`Repositories::memory`] 不需要数据库，用于开发和测试。
+#[derive(Clone)]
+pub struct Repositories {
+    pub users: Arc<dyn UserRepository>,
+    pub topics: Arc<dyn TopicRepository>,
+    pub comments: Arc<dyn CommentRepository>,
+    pub stars: Arc<dyn StarRepository>,
+    pub notices: Arc<dyn NoticeRepository>,
+    pub tags: Arc<dyn TagRepository>,
+    pub search: Arc<dyn SearchRepository>,
+}
+
+impl Repositories {
+    pub fn mysql(pool: MySqlPool) -> Self {
+        Self::from_shared(Arc::new(MySqlRepository::new(pool)))
+    }
+
+    pub fn memory() -> Self {
+        Self::from_shared(Arc::new(MemoryRepository::new()))
+    }
+
+    // 同一个实现的所有仓库共用一份数据，例如收藏主题时需要同时修改主题的收藏数
+    fn from_shared<R>(repository: Arc<R>) -> Self
+    where
+        R: UserRepository
+            + TopicRepository
+            + CommentRepository
+            + StarRepository
+            + NoticeRepository
+            + TagRepository
+            + SearchRepository
+            + 'static,
+    {
+        Self {
+            users: repository.clone(),
+            topics: repository.clone(),
+            comments: repository.clone(),
+            stars: repository.clone(),
+            notices: repository.clone(),
+            tags: repository.clone(),
+            search: repository,
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use chrono::NaiveDateTime;
+
+use crate::common::err::AppError;
+use crate::model::notice::{NewNotice, Notice, NoticeType};
+use crate::repository::MySqlRepository;
+use crate::AppResult;
+
+#[async_trait]
+pub trait NoticeRepository: Send + Sync {
+    async fn insert_one_notice(&self, notice: &NewNotice) -> AppResult<u64>;
+
+    async fn count_notices(&self, notified_user_id: u64) -> AppResult<i64>;
+
+    async fn count_unread_notices(&self, notified_user_id: u64) -> AppResult<i64>;
+
+    async fn find_notices(&self, notified_user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Notice>>;
+
+    async fn mark_notice_viewed(&self, pk_id: u64, notified_user_id: u64) -> AppResult<bool>;
+
+    async fn mark_all_notices_viewed(&self, notified_user_id: u64) -> AppResult<u64>;
+
+    /// `since` 之后收到新通知且仍未读的用户
+    async fn find_unread_notified_users(&self, since: NaiveDateTime) -> AppResult<Vec<u64>>;
+}
+
+#[async_trait]
+impl NoticeRepository for MySqlRepository {
+    async fn insert_one_notice(&self, notice: &NewNotice) -> AppResult<u64> {
+        sqlx::query!(
+            r#"
+            INSERT INTO notice
+                (notice_type, target_id, actor_name, target_title, notified_user_id, create_user)
+            VALUES
+                (?, ?, ?, ?, ?, ?)
+            "#,
+            notice.notice_type as u8,
+            notice.target_id,
+            notice.actor_name,
+            notice.target_title,
+            notice.notified_user_id,
+            notice.create_user,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|done| done.last_insert_id())
+    }
+
+    async fn count_notices(&self, notified_user_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM notice
+            WHERE notified_user_id = ?
+            "#,
+            notified_user_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn count_unread_notices(&self, notified_user_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM notice
+            WHERE notified_user_id = ? AND viewed = 0
+            "#,
+            notified_user_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    // 未读的通知排在前面
+    async fn find_notices(&self, notified_user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Notice>> {
+        sqlx::query_as!(
+            Notice,
+            r#"
+            SELECT pk_id, notice_type as `notice_type: NoticeType`, target_id, actor_name, target_title,
+                notified_user_id, viewed as `viewed: bool`, create_time, create_user
+            FROM notice
+            WHERE notified_user_id = ?
+            ORDER BY viewed, create_time DESC, pk_id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            notified_user_id,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+
+    async fn mark_notice_viewed(&self, pk_id: u64, notified_user_id: u64) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE notice SET viewed = 1 WHERE pk_id = ? AND notified_user_id = ?
+            "#,
+            pk_id,
+            notified_user_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    async fn mark_all_notices_viewed(&self, notified_user_id: u64) -> AppResult<u64> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE notice SET viewed = 1 WHERE notified_user_id = ? AND viewed = 0
+            "#,
+            notified_user_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected)
+    }
+
+    async fn find_unread_notified_users(&self, since: NaiveDateTime) -> AppResult<Vec<u64>> {
+        sqlx::query!(
+            r#"
+            SELECT DISTINCT notified_user_id
+            FROM notice
+            WHERE viewed = 0 AND create_time >= ?
+            "#,
+            since,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|rows| rows.into_iter().map(|row| row.notified_user_id).collect())
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{common::err::AppError, model::search::SearchRow, repository::MySqlRepository, AppResult};
+
+// 主题按标题和内容检索，评论按内容检索，标签过滤包括子标签，评论按所属主题的标签过滤。
+// 两条语句的占位符顺序相同且需要重复绑定，统一由 bind_hits! 按顺序绑定。
+macro_rules! topic_hits_sql {
+    () => {
+        r#"
+        SELECT 'topic' as hit_type, t.pk_id as topic_id, CAST(NULL AS UNSIGNED) as comment_id,
+            t.title, t.content, t.user_id,
+            MATCH (t.title, t.content) AGAINST (? IN NATURAL LANGUAGE MODE) as score, t.create_time
+        FROM topic t
+        WHERE t.deleted = 0
+            AND MATCH (t.title, t.content) AGAINST (? IN NATURAL LANGUAGE MODE)
+            AND (? IS NULL OR t.user_id = ?)
+            AND (? IS NULL OR t.pk_id IN (
+                SELECT r.topic_id
+                FROM tag_topic_relation r
+                WHERE r.tag_id = ? OR r.tag_id IN (SELECT pk_id FROM tag WHERE parent_tag = ?)
+            ))
+        "#
+    };
+}
+
+macro_rules! comment_hits_sql {
+    () => {
+        r#"
+        SELECT 'comment' as hit_type, c.topic_id, c.pk_id as comment_id,
+            t.title, c.content, c.user_id,
+            MATCH (c.content) AGAINST (? IN NATURAL LANGUAGE MODE) as score, c.create_time
+        FROM comment c
+        JOIN topic t ON t.pk_id = c.topic_id
+        WHERE c.deleted = 0 AND t.deleted = 0
+            AND MATCH (c.content) AGAINST (? IN NATURAL LANGUAGE MODE)
+            AND (? IS NULL OR c.user_id = ?)
+            AND (? IS NULL OR c.topic_id IN (
+                SELECT r.topic_id
+                FROM tag_topic_relation r
+                WHERE r.tag_id = ? OR r.tag_id IN (SELECT pk_id FROM tag WHERE parent_tag = ?)
+            ))
+        "#
+    };
+}
+
+macro_rules! count_sql {
+    ($hits_sql:expr) => {
+        concat!("SELECT COUNT(*) FROM (", $hits_sql, ") hits")
+    };
+}
+
+// 相关度相同时新的记录在前
+macro_rules! find_sql {
+    ($hits_sql:expr) => {
+        concat!($hits_sql, " ORDER BY score DESC, create_time DESC LIMIT ?")
+    };
+}
+
+macro_rules! bind_hits {
+    ($query:expr, $keyword:expr, $tag_id:expr, $author_id:expr) => {
+        $query
+            .bind($keyword)
+            .bind($keyword)
+            .bind($author_id)
+            .bind($author_id)
+            .bind($tag_id)
+            .bind($tag_id)
+            .bind($tag_id)
+    };
+}
+
+/// 全文检索主题和评论，`score` 为相关度，只有同一个实现中同一类记录的相关度可以互相比较
+#[async_trait]
+pub trait SearchRepository: Send + Sync {
+    async fn count_hits(&self, keyword: &str, tag_id: Option<u64>, author_id: Option<u64>) -> AppResult<i64>;
+
+    /// 结果按 [`merge_hits`] 的顺序排列
+    async fn find_hits(
+        &self,
+        keyword: &str,
+        tag_id: Option<u64>,
+        author_id: Option<u64>,
+        offset: u32,
+        limit: u32,
+    ) -> AppResult<Vec<SearchRow>>;
+}
+
+/// 合并分别按相关度排好序的主题和评论，并取出 `offset` 开始的 `limit` 条
+///
+/// 主题按标题和内容计算相关度，评论只按内容计算，两者的相关度不能互相比较，
+/// 因此不按相关度统一排序，而是交替排列：第 n 个主题排在第 n 个评论前面，一类取完后接着取另一类。
+/// 两类都只需要各自的前 `offset + limit` 条。
+pub fn merge_hits(topics: Vec<SearchRow>, comments: Vec<SearchRow>, offset: u32, limit: u32) -> Vec<SearchRow> {
+    let mut merged = Vec::with_capacity(topics.len() + comments.len());
+    let (mut topics, mut comments) = (topics.into_iter(), comments.into_iter());
+    loop {
+        match (topics.next(), comments.next()) {
+            (None, None) => break,
+            (topic, comment) => merged.extend(topic.into_iter().chain(comment)),
+        }
+    }
+    merged.into_iter().skip(offset as usize).take(limit as usize).collect()
+}
+
+impl MySqlRepository {
+    async fn count_source_hits(
+        &self,
+        sql: &'static str,
+        keyword: &str,
+        tag_id: Option<u64>,
+        author_id: Option<u64>,
+    ) -> AppResult<i64> {
+        bind_hits!(sqlx::query_scalar::<_, i64>(sql), keyword, tag_id, author_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(AppError::DatabaseError)
+    }
+
+    async fn find_source_hits(
+        &self,
+        sql: &'static str,
+        keyword: &str,
+        tag_id: Option<u64>,
+        author_id: Option<u64>,
+        limit: u32,
+    ) -> AppResult<Vec<SearchRow>> {
+        bind_hits!(sqlx::query_as::<_, SearchRow>(sql), keyword, tag_id, author_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(AppError::DatabaseError)
+    }
+}
+
+#[async_trait]
+impl SearchRepository for MySqlRepository {
+    async fn count_hits(&self, keyword: &str, tag_id: Option<u64>, author_id: Option<u64>) -> AppResult<i64> {
+        let topics = self
+            .count_source_hits(count_sql!(topic_hits_sql!()), keyword, tag_id, author_id)
+            .await?;
+        let comments = self
+            .count_source_hits(count_sql!(comment_hits_sql!()), keyword, tag_id, author_id)
+            .await?;
+        Ok(topics + comments)
+    }
+
+    async fn find_hits(
+        &self,
+        keyword: &str,
+        tag_id: Option<u64>,
+        author_id: Option<u64>,
+        offset: u32,
+        limit: u32,
+    ) -> AppResult<Vec<SearchRow>> {
+        let fetch = offset.saturating_add(limit);
+        let topics = self
+            .find_source_hits(find_sql!(topic_hits_sql!()), keyword, tag_id, author_id, fetch)
+            .await?;
+        let comments = self
+            .find_source_hits(find_sql!(comment_hits_sql!()), keyword, tag_id, author_id, fetch)
+            .await?;
+        Ok(merge_hits(topics, comments, offset, limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+    use sqlx::MySqlPool;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::common::locale::Locale;
+    use crate::model::comment::NewComment;
+    use crate::model::topic::TopicFront;
+    use crate::model::user::RegisterUser;
+    use crate::repository::{migration, Repositories};
+
+    /// MySQL 测试库地址，设置后通过 `cargo test -- --ignored` 运行，测试会执行迁移并写入数据
+    const DATABASE_URL_ENV: &str = "WHATSOO_TEST_DATABASE_URL";
+
+    fn row(hit_type: &str, score: f64) -> SearchRow {
+        SearchRow {
+            hit_type: String::from(hit_type),
+            topic_id: 1,
+            comment_id: None,
+            title: String::new(),
+            content: String::new(),
+            user_id: 1,
+            score,
+            create_time: Local::now().naive_local(),
+        }
+    }
+
+    fn scores(rows: &[SearchRow]) -> Vec<(&str, f64)> {
+        rows.iter().map(|r| (r.hit_type.as_str(), r.score)).collect()
+    }
+
+    #[test]
+    fn merge_hits_alternates_sources() {
+        let topics = vec![row("topic", 0.5), row("topic", 0.2), row("topic", 0.1)];
+        // 评论的相关度更高，但不能和主题的相关度比较
+        let comments = vec![row("comment", 9.0)];
+        let merged = merge_hits(topics, comments, 0, 10);
+        assert_eq!(
+            scores(&merged),
+            vec![("topic", 0.5), ("comment", 9.0), ("topic", 0.2), ("topic", 0.1)]
+        );
+    }
+
+    #[test]
+    fn merge_hits_pages() {
+        let topics = vec![row("topic", 2.0), row("topic", 1.0)];
+        let comments = vec![row("comment", 2.0), row("comment", 1.0)];
+        let merged = merge_hits(topics, comments, 1, 2);
+        assert_eq!(scores(&merged), vec![("comment", 2.0), ("topic", 1.0)]);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn mysql_fulltext_search() {
+        let url = match std::env::var(DATABASE_URL_ENV) {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let pool = MySqlPool::connect(&url).await.unwrap();
+        migration::run(&pool).await.unwrap();
+        let repos = Repositories::mysql(pool);
+        // 每次运行使用新的用户，按作者过滤后不受库中已有数据影响
+        let suffix = Uuid::new_v4().to_simple().to_string();
+        let user_id = repos
+            .users
+            .insert_one_user(RegisterUser {
+                uk_username: format!("search_{}", &suffix[..16]),
+                uk_email: format!("{}@whatsoo.org", suffix),
+                email_verify_code: String::new(),
+                user_password: String::new(),
+                locale: Locale::default(),
+            })
+            .await
+            .unwrap();
+        let topic = TopicFront {
+            user_id: Some(user_id),
+            title: String::from("全文检索入门"),
+            content: String::from("使用 ngram 解析器支持中文分词"),
+            tags: Vec::new(),
+        };
+        let topic_id = repos.topics.insert_one_topic(&topic).await.unwrap();
+        let comment = NewComment {
+            user_id,
+            topic_id,
+            parent_id: None,
+            root_id: None,
+            depth: 0,
+            content: "评论里也提到了全文检索",
+        };
+        let comment_id = repos.comments.insert_one_comment(comment).await.unwrap();
+
+        let author = Some(user_id);
+        assert_eq!(repos.search.count_hits("全文检索", None, author).await.unwrap(), 2);
+        let hits = repos.search.find_hits("全文检索", None, author, 0, 10).await.unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!((hits[0].hit_type.as_str(), hits[0].topic_id), ("topic", topic_id));
+        assert_eq!(
+            (hits[1].hit_type.as_str(), hits[1].comment_id),
+            ("comment", Some(comment_id))
+        );
+        assert!(hits.iter().all(|h| h.score > 0.0));
+        assert_eq!(hits[1].title, "全文检索入门");
+
+        let page = repos.search.find_hits("全文检索", None, author, 1, 1).await.unwrap();
+        assert_eq!(page[0].comment_id, Some(comment_id));
+        assert_eq!(repos.search.count_hits("分词", None, author).await.unwrap(), 1);
+        assert_eq!(repos.search.count_hits("不存在的词", None, author).await.unwrap(), 0);
+    }
+}
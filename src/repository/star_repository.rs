@@ -0,0 +1,237 @@
+use sqlx::{MySql, Transaction};
+
+use crate::common::err::AppError;
+use crate::model::star::StarType;
+use crate::model::topic::Topic;
+use crate::model::user::UserBrief;
+use crate::repository::MySqlRepository;
+use crate::AppResult;
+
+#[async_trait]
+pub trait StarRepository: Send + Sync {
+    /// 已存在时不做任何修改，返回是否新增了记录
+    async fn insert_star(&self, star_type: StarType, user_id: u64, star_id: u64) -> AppResult<bool>;
+
+    /// 不存在时不做任何修改，返回是否删除了记录
+    async fn delete_star(&self, star_type: StarType, user_id: u64, star_id: u64) -> AppResult<bool>;
+
+    async fn count_stars_by_user(&self, star_type: StarType, user_id: u64) -> AppResult<i64>;
+
+    async fn count_stars_by_target(&self, star_type: StarType, star_id: u64) -> AppResult<i64>;
+
+    async fn count_star_topics_by_user(&self, user_id: u64) -> AppResult<i64>;
+
+    async fn find_star_topics_by_user(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Topic>>;
+
+    /// 关注了该用户的人
+    async fn find_followers(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<UserBrief>>;
+
+    /// 该用户关注的人
+    async fn find_followings(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<UserBrief>>;
+}
+
+#[async_trait]
+impl StarRepository for MySqlRepository {
+    async fn insert_star(&self, star_type: StarType, user_id: u64, star_id: u64) -> AppResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let rows_affected = sqlx::query!(
+            r#"
+            INSERT IGNORE INTO star
+                (star_type, user_id, star_id)
+            VALUES
+                (?, ?, ?)
+            "#,
+            star_type as u8,
+            user_id,
+            star_id,
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        let inserted = rows_affected > 0;
+        if inserted {
+            increase_star_count(star_type, star_id, &mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn delete_star(&self, star_type: StarType, user_id: u64, star_id: u64) -> AppResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let rows_affected = sqlx::query!(
+            r#"
+            DELETE FROM star
+            WHERE star_type = ? AND user_id = ? AND star_id = ?
+            "#,
+            star_type as u8,
+            user_id,
+            star_id,
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        let deleted = rows_affected > 0;
+        if deleted {
+            decrease_star_count(star_type, star_id, &mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn count_stars_by_user(&self, star_type: StarType, user_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM star
+            WHERE star_type = ? AND user_id = ?
+            "#,
+            star_type as u8,
+            user_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn count_stars_by_target(&self, star_type: StarType, star_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM star
+            WHERE star_type = ? AND star_id = ?
+            "#,
+            star_type as u8,
+            star_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn count_star_topics_by_user(&self, user_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM star s
+            JOIN topic t ON t.pk_id = s.star_id
+            WHERE s.star_type = ? AND s.user_id = ? AND t.deleted = 0
+            "#,
+            StarType::Topic as u8,
+            user_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn find_star_topics_by_user(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Topic>> {
+        sqlx::query_as!(
+            Topic,
+            r#"
+            SELECT t.pk_id, t.user_id, t.title, t.content, t.tags, t.like_times, t.click_times, t.top as `top: bool`,
+                t.create_time, t.create_user, t.update_time, t.update_user
+            FROM star s
+            JOIN topic t ON t.pk_id = s.star_id
+            WHERE s.star_type = ? AND s.user_id = ? AND t.deleted = 0
+            ORDER BY s.create_time DESC, s.pk_id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            StarType::Topic as u8,
+            user_id,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+
+    async fn find_followers(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<UserBrief>> {
+        sqlx::query_as!(
+            UserBrief,
+            r#"
+            SELECT u.pk_id, u.uk_username, u.avatar
+            FROM star s
+            JOIN user u ON u.pk_id = s.user_id
+            WHERE s.star_type = ? AND s.star_id = ?
+            ORDER BY s.create_time DESC, s.pk_id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            StarType::User as u8,
+            user_id,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+
+    async fn find_followings(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<UserBrief>> {
+        sqlx::query_as!(
+            UserBrief,
+            r#"
+            SELECT u.pk_id, u.uk_username, u.avatar
+            FROM star s
+            JOIN user u ON u.pk_id = s.star_id
+            WHERE s.star_type = ? AND s.user_id = ?
+            ORDER BY s.create_time DESC, s.pk_id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            StarType::User as u8,
+            user_id,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+}
+
+// 帖子收藏数和评论点赞数与 star 表保持同步，关注没有冗余计数
+async fn increase_star_count(star_type: StarType, star_id: u64, tx: &mut Transaction<'_, MySql>) -> AppResult<()> {
+    match star_type {
+        StarType::Topic => {
+            sqlx::query!("UPDATE topic SET like_times = like_times + 1 WHERE pk_id = ?", star_id)
+                .execute(tx)
+                .await?;
+        }
+        StarType::Comment => {
+            sqlx::query!(
+                "UPDATE comment SET like_amount = like_amount + 1 WHERE pk_id = ?",
+                star_id
+            )
+            .execute(tx)
+            .await?;
+        }
+        StarType::User => {}
+    }
+    Ok(())
+}
+
+async fn decrease_star_count(star_type: StarType, star_id: u64, tx: &mut Transaction<'_, MySql>) -> AppResult<()> {
+    match star_type {
+        StarType::Topic => {
+            sqlx::query!(
+                "UPDATE topic SET like_times = like_times - 1 WHERE pk_id = ? AND like_times > 0",
+                star_id
+            )
+            .execute(tx)
+            .await?;
+        }
+        StarType::Comment => {
+            sqlx::query!(
+                "UPDATE comment SET like_amount = like_amount - 1 WHERE pk_id = ? AND like_amount > 0",
+                star_id
+            )
+            .execute(tx)
+            .await?;
+        }
+        StarType::User => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use crate::common::err::{AppError, ErrorCode};
+use crate::model::tag::{Tag, TagFront};
+use crate::repository::MySqlRepository;
+use crate::AppResult;
+
+#[async_trait]
+pub trait TagRepository: Send + Sync {
+    async fn insert_one_tag(&self, tag: &TagFront, parent_tag: u64, user_id: u64) -> AppResult<u64>;
+
+    async fn find_tag_by_id(&self, pk_id: u64) -> AppResult<Tag>;
+
+    async fn find_all_tags(&self) -> AppResult<Vec<Tag>>;
+
+    async fn count_child_tags(&self, pk_id: u64) -> AppResult<i64>;
+
+    async fn update_tag(&self, pk_id: u64, tag: &TagFront, parent_tag: u64, user_id: u64) -> AppResult<bool>;
+
+    /// 同时删除该标签与主题的关联
+    async fn delete_tag(&self, pk_id: u64) -> AppResult<bool>;
+}
+
+#[async_trait]
+impl TagRepository for MySqlRepository {
+    async fn insert_one_tag(&self, tag: &TagFront, parent_tag: u64, user_id: u64) -> AppResult<u64> {
+        sqlx::query!(
+            r#"
+            INSERT INTO tag
+                (tag_name, uk_logo, parent_tag, create_user, update_user)
+            VALUES
+                (?, COALESCE(?, DEFAULT(uk_logo)), ?, ?, ?)
+            "#,
+            tag.tag_name,
+            tag.uk_logo,
+            parent_tag,
+            user_id,
+            user_id,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23000") => {
+                AppError::BusinessError(ErrorCode::TagNameExists)
+            }
+            e => AppError::DatabaseError(e),
+        })
+        .map(|done| done.last_insert_id())
+    }
+
+    async fn find_tag_by_id(&self, pk_id: u64) -> AppResult<Tag> {
+        sqlx::query_as!(
+            Tag,
+            r#"
+            SELECT pk_id, tag_name, uk_logo, parent_tag, create_time, create_user, update_time, update_user
+            FROM tag
+            WHERE pk_id = ?
+            "#,
+            pk_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::BusinessError(ErrorCode::TagNotFound),
+            e => AppError::DatabaseError(e),
+        })
+    }
+
+    async fn find_all_tags(&self) -> AppResult<Vec<Tag>> {
+        sqlx::query_as!(
+            Tag,
+            r#"
+            SELECT pk_id, tag_name, uk_logo, parent_tag, create_time, create_user, update_time, update_user
+            FROM tag
+            ORDER BY parent_tag, pk_id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+
+    async fn count_child_tags(&self, pk_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM tag
+            WHERE parent_tag = ?
+            "#,
+            pk_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn update_tag(&self, pk_id: u64, tag: &TagFront, parent_tag: u64, user_id: u64) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE tag
+            SET tag_name = ?, uk_logo = COALESCE(?, uk_logo), parent_tag = ?, update_user = ?
+            WHERE pk_id = ?
+            "#,
+            tag.tag_name,
+            tag.uk_logo,
+            parent_tag,
+            user_id,
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23000") => {
+                AppError::BusinessError(ErrorCode::TagNameExists)
+            }
+            e => AppError::DatabaseError(e),
+        })?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    async fn delete_tag(&self, pk_id: u64) -> AppResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM tag_topic_relation WHERE tag_id = ?", pk_id)
+            .execute(&mut tx)
+            .await?;
+        let rows_affected = sqlx::query!("DELETE FROM tag WHERE pk_id = ?", pk_id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(rows_affected > 0)
+    }
+}
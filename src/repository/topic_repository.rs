@@ -1,42 +1,294 @@
-use sqlx::MySqlPool;
-
-use crate::{common::err::AppError, model::topic::TopicFront, AppResult};
-
-#[inline(always)]
-pub async fn insert_one_topic(new_topic: TopicFront, pool: &MySqlPool) -> AppResult<bool> {
-    let user_id = new_topic.user_id.ok_or(AppError::BusinessError(500, "用户不能为空"))?;
-    sqlx::query!(
-        r#"
-        INSERT INTO topic
-            (user_id, title, content, tags, create_user, update_user)
-        VALUES
-            (?,?,?,?,?,?)
-        "#,
-        user_id,
-        new_topic.title,
-        new_topic.content,
-        new_topic.tags,
-        user_id,
-        user_id,
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| AppError::DatabaseError(e))
-    .map(|done| done.last_insert_id() > 0)
+use sqlx::{MySql, Transaction};
+
+use crate::{
+    common::err::{AppError, ErrorCode},
+    model::topic::{Topic, TopicFront},
+    repository::MySqlRepository,
+    AppResult,
+};
+
+#[async_trait]
+pub trait TopicRepository: Send + Sync {
+    async fn insert_one_topic(&self, new_topic: &TopicFront) -> AppResult<u64>;
+
+    async fn update_topic_top(&self, pk_id: u64, top: bool) -> AppResult<bool>;
+
+    async fn find_topic_by_id(&self, pk_id: u64) -> AppResult<Topic>;
+
+    async fn increase_click_times(&self, pk_id: u64) -> AppResult<bool>;
+
+    async fn count_topics(&self) -> AppResult<i64>;
+
+    async fn find_topics(&self, offset: u32, limit: u32) -> AppResult<Vec<Topic>>;
+
+    async fn count_topics_by_user(&self, user_id: u64) -> AppResult<i64>;
+
+    async fn find_topics_by_user(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Topic>>;
+
+    async fn update_topic(&self, pk_id: u64, topic: TopicFront, update_user: u64) -> AppResult<bool>;
+
+    async fn delete_topic(&self, pk_id: u64, update_user: u64) -> AppResult<bool>;
+
+    /// 包括该标签下子标签关联的主题
+    async fn count_topics_by_tag(&self, tag_id: u64) -> AppResult<i64>;
+
+    async fn find_topics_by_tag(&self, tag_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Topic>>;
+}
+
+#[async_trait]
+impl TopicRepository for MySqlRepository {
+    async fn insert_one_topic(&self, new_topic: &TopicFront) -> AppResult<u64> {
+        let user_id = new_topic
+            .user_id
+            .ok_or(AppError::BusinessError(ErrorCode::NotLoggedIn))?;
+        let mut tx = self.pool.begin().await?;
+        let pk_id = sqlx::query!(
+            r#"
+            INSERT INTO topic
+                (user_id, title, content, tags, create_user, update_user)
+            VALUES
+                (?,?,?,?,?,?)
+            "#,
+            user_id,
+            new_topic.title,
+            new_topic.content,
+            new_topic.tags_str(),
+            user_id,
+            user_id,
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|done| done.last_insert_id())?;
+        insert_tag_relations(pk_id, &new_topic.tags, user_id, &mut tx).await?;
+        tx.commit().await?;
+        Ok(pk_id)
+    }
+
+    async fn update_topic_top(&self, pk_id: u64, top: bool) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE topic SET top = ? WHERE pk_id = ? AND deleted = 0
+            "#,
+            top,
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    async fn find_topic_by_id(&self, pk_id: u64) -> AppResult<Topic> {
+        sqlx::query_as!(
+            Topic,
+            r#"
+            SELECT pk_id, user_id, title, content, tags, like_times, click_times, top as `top: bool`,
+                create_time, create_user, update_time, update_user
+            FROM topic
+            WHERE pk_id = ? AND deleted = 0
+            "#,
+            pk_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::BusinessError(ErrorCode::TopicNotFound),
+            e => AppError::DatabaseError(e),
+        })
+    }
+
+    async fn increase_click_times(&self, pk_id: u64) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE topic SET click_times = click_times + 1 WHERE pk_id = ? AND deleted = 0
+            "#,
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    async fn count_topics(&self) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM topic
+            WHERE deleted = 0
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    // 置顶的主题排在最前面
+    async fn find_topics(&self, offset: u32, limit: u32) -> AppResult<Vec<Topic>> {
+        sqlx::query_as!(
+            Topic,
+            r#"
+            SELECT pk_id, user_id, title, content, tags, like_times, click_times, top as `top: bool`,
+                create_time, create_user, update_time, update_user
+            FROM topic
+            WHERE deleted = 0
+            ORDER BY top DESC, create_time DESC, pk_id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+
+    async fn count_topics_by_user(&self, user_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM topic
+            WHERE user_id = ? AND deleted = 0
+            "#,
+            user_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn find_topics_by_user(&self, user_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Topic>> {
+        sqlx::query_as!(
+            Topic,
+            r#"
+            SELECT pk_id, user_id, title, content, tags, like_times, click_times, top as `top: bool`,
+                create_time, create_user, update_time, update_user
+            FROM topic
+            WHERE user_id = ? AND deleted = 0
+            ORDER BY create_time DESC, pk_id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            user_id,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+
+    async fn update_topic(&self, pk_id: u64, topic: TopicFront, update_user: u64) -> AppResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE topic
+            SET title = ?, content = ?, tags = ?, update_user = ?
+            WHERE pk_id = ? AND deleted = 0
+            "#,
+            topic.title,
+            topic.content,
+            topic.tags_str(),
+            update_user,
+            pk_id,
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        if rows_affected == 0 {
+            return Ok(false);
+        }
+        sqlx::query!("DELETE FROM tag_topic_relation WHERE topic_id = ?", pk_id)
+            .execute(&mut tx)
+            .await?;
+        insert_tag_relations(pk_id, &topic.tags, update_user, &mut tx).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // 软删除，主题下的评论仍然保留
+    async fn delete_topic(&self, pk_id: u64, update_user: u64) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE topic
+            SET deleted = 1, update_user = ?
+            WHERE pk_id = ? AND deleted = 0
+            "#,
+            update_user,
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    async fn count_topics_by_tag(&self, tag_id: u64) -> AppResult<i64> {
+        sqlx::query!(
+            r#"
+            SELECT COUNT(DISTINCT t.pk_id) as count
+            FROM topic t
+            JOIN tag_topic_relation r ON r.topic_id = t.pk_id
+            WHERE t.deleted = 0
+                AND (r.tag_id = ? OR r.tag_id IN (SELECT pk_id FROM tag WHERE parent_tag = ?))
+            "#,
+            tag_id,
+            tag_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+        .map(|res| res.count)
+    }
+
+    async fn find_topics_by_tag(&self, tag_id: u64, offset: u32, limit: u32) -> AppResult<Vec<Topic>> {
+        sqlx::query_as!(
+            Topic,
+            r#"
+            SELECT t.pk_id, t.user_id, t.title, t.content, t.tags, t.like_times, t.click_times, t.top as `top: bool`,
+                t.create_time, t.create_user, t.update_time, t.update_user
+            FROM topic t
+            WHERE t.deleted = 0 AND t.pk_id IN (
+                SELECT r.topic_id
+                FROM tag_topic_relation r
+                WHERE r.tag_id = ? OR r.tag_id IN (SELECT pk_id FROM tag WHERE parent_tag = ?)
+            )
+            ORDER BY t.top DESC, t.create_time DESC, t.pk_id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            tag_id,
+            tag_id,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
 }
 
-// todo 管理人员才能置顶
-#[inline(always)]
-pub async fn update_topic_top(pk_id: u64, top: bool, pool: &MySqlPool) -> AppResult<bool> {
-    let rows_affected = sqlx::query!(
-        r#"
-        UPDATE topic SET top = ? WHERE pk_id = ?
-        "#,
-        top,
-        pk_id,
-    )
-    .execute(pool)
-    .await?
-    .rows_affected();
-    Ok(rows_affected > 0)
+async fn insert_tag_relations(
+    topic_id: u64,
+    tag_ids: &[u64],
+    user_id: u64,
+    tx: &mut Transaction<'_, MySql>,
+) -> AppResult<()> {
+    for tag_id in tag_ids {
+        sqlx::query!(
+            r#"
+            INSERT IGNORE INTO tag_topic_relation
+                (tag_id, topic_id, create_user)
+            VALUES
+                (?, ?, ?)
+            "#,
+            tag_id,
+            topic_id,
+            user_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(())
 }
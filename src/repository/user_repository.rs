@@ -1,12 +1,44 @@
-use sqlx::MySqlPool;
-
-use crate::common::err::AppError;
-use crate::model::user::{RegisterUser, User};
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::locale::Locale;
+use crate::model::user::{ProfileFront, RegisterUser, Role, User, UserBrief};
+use crate::repository::MySqlRepository;
 use crate::AppResult;
 
-impl User {
-    #[inline(always)]
-    pub async fn count_by_email(email: &str, pool: &MySqlPool) -> AppResult<i64> {
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn count_by_email(&self, email: &str) -> AppResult<i64>;
+
+    async fn update_user_pwd(&self, pwd: String, id: u64) -> AppResult<u64>;
+
+    async fn count_by_username(&self, username: String) -> AppResult<i64>;
+
+    /// 用户名或邮箱已存在时返回 `UserExists`
+    async fn insert_one_user(&self, user: RegisterUser) -> AppResult<u64>;
+
+    async fn find_user_by_email(&self, email: &str) -> AppResult<User>;
+
+    async fn find_user_by_id(&self, pk_id: u64) -> AppResult<User>;
+
+    async fn find_user_by_username(&self, username: &str) -> AppResult<User>;
+
+    async fn find_user_brief_by_username(&self, username: &str) -> AppResult<Option<UserBrief>>;
+
+    /// 博客网址或 GitHub 用户名已被其他用户使用时返回 `ProfileConflict`
+    async fn update_user_profile(&self, pk_id: u64, profile: &ProfileFront) -> AppResult<bool>;
+
+    /// 只修改登录时间和语言偏好，不改变 `update_time`
+    async fn update_last_login(&self, pk_id: u64, locale: Locale) -> AppResult<bool>;
+
+    async fn update_user_role(&self, pk_id: u64, role: Role) -> AppResult<bool>;
+
+    async fn find_token_version(&self, pk_id: u64) -> AppResult<u32>;
+
+    async fn increase_token_version(&self, pk_id: u64) -> AppResult<bool>;
+}
+
+#[async_trait]
+impl UserRepository for MySqlRepository {
+    async fn count_by_email(&self, email: &str) -> AppResult<i64> {
         sqlx::query!(
             r#"
             SELECT COUNT(*) as count
@@ -15,14 +47,13 @@ impl User {
             "#,
             email
         )
-        .fetch_one(pool)
+        .fetch_one(&self.pool)
         .await
         .map_err(|e| AppError::DatabaseError(e))
         .map(|res| res.count)
     }
 
-    #[inline(always)]
-    pub async fn update_user_pwd(pwd: String, id: u64, pool: &MySqlPool) -> AppResult<u64> {
+    async fn update_user_pwd(&self, pwd: String, id: u64) -> AppResult<u64> {
         let rows_affected = sqlx::query!(
             r#"
             UPDATE user
@@ -32,14 +63,13 @@ impl User {
             pwd,
             id,
         )
-        .execute(pool)
+        .execute(&self.pool)
         .await?
         .rows_affected();
         Ok(rows_affected)
     }
 
-    #[inline(always)]
-    pub async fn count_by_username(username: String, pool: &MySqlPool) -> AppResult<i64> {
+    async fn count_by_username(&self, username: String) -> AppResult<i64> {
         sqlx::query!(
             r#"
             SELECT COUNT(*) as count
@@ -48,44 +78,197 @@ impl User {
             "#,
             username
         )
-        .fetch_one(pool)
+        .fetch_one(&self.pool)
         .await
         .map_err(|e| AppError::DatabaseError(e))
         .map(|res| res.count)
     }
 
-    #[inline(always)]
-    pub async fn insert_one_user(user: RegisterUser, pool: &MySqlPool) -> AppResult<u64> {
+    async fn insert_one_user(&self, user: RegisterUser) -> AppResult<u64> {
         sqlx::query!(
             r#"
             INSERT INTO user
-                (uk_username, uk_email, user_password)
+                (uk_username, uk_email, user_password, locale)
             VALUES
-                (?, ?, ?);
+                (?, ?, ?, ?);
             "#,
             user.uk_username,
             user.uk_email,
             user.user_password,
+            user.locale.as_str(),
         )
-        .execute(pool)
+        .execute(&self.pool)
         .await
-        .map_err(|e| AppError::DatabaseError(e))
+        .map_err(|e| match e {
+            // 并发注册时唯一索引冲突
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23000") => {
+                AppError::BusinessError(ErrorCode::UserExists)
+            }
+            e => AppError::DatabaseError(e),
+        })
         .map(|done| done.last_insert_id())
     }
 
-    #[inline(always)]
-    pub async fn find_user_by_email(email: &str, pool: &MySqlPool) -> AppResult<User> {
+    async fn find_user_by_email(&self, email: &str) -> AppResult<User> {
         sqlx::query_as!(
             User,
             r#"
-            SELECT *
+            SELECT pk_id, uk_username, uk_email, user_password, avatar, blog_url, introduce, github_uid,
+                create_time, update_time, last_login_time, role as `role: Role`, token_version, locale
             FROM user
             WHERE uk_email = ?
             "#,
             email
         )
-        .fetch_one(pool)
+        .fetch_one(&self.pool)
         .await
-        .map_err(|_| AppError::BusinessError(500, "用户不存在"))
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::BusinessError(ErrorCode::UserNotFound),
+            _ => AppError::DatabaseError(e),
+        })
+    }
+
+    async fn find_user_by_id(&self, pk_id: u64) -> AppResult<User> {
+        sqlx::query_as!(
+            User,
+            r#"
+            SELECT pk_id, uk_username, uk_email, user_password, avatar, blog_url, introduce, github_uid,
+                create_time, update_time, last_login_time, role as `role: Role`, token_version, locale
+            FROM user
+            WHERE pk_id = ?
+            "#,
+            pk_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::BusinessError(ErrorCode::UserNotFound),
+            e => AppError::DatabaseError(e),
+        })
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> AppResult<User> {
+        sqlx::query_as!(
+            User,
+            r#"
+            SELECT pk_id, uk_username, uk_email, user_password, avatar, blog_url, introduce, github_uid,
+                create_time, update_time, last_login_time, role as `role: Role`, token_version, locale
+            FROM user
+            WHERE uk_username = ?
+            "#,
+            username
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::BusinessError(ErrorCode::UserNotFound),
+            e => AppError::DatabaseError(e),
+        })
+    }
+
+    async fn find_user_brief_by_username(&self, username: &str) -> AppResult<Option<UserBrief>> {
+        sqlx::query_as!(
+            UserBrief,
+            r#"
+            SELECT pk_id, uk_username, avatar
+            FROM user
+            WHERE uk_username = ?
+            "#,
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e))
+    }
+
+    async fn update_user_profile(&self, pk_id: u64, profile: &ProfileFront) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE user
+            SET avatar = ?, blog_url = ?, introduce = ?, github_uid = ?
+            WHERE pk_id = ?
+            "#,
+            profile.avatar,
+            profile.blog_url,
+            profile.introduce,
+            profile.github_uid,
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            // blog_url 和 github_uid 都有唯一索引
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23000") => {
+                AppError::BusinessError(ErrorCode::ProfileConflict)
+            }
+            e => AppError::DatabaseError(e),
+        })?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    async fn update_last_login(&self, pk_id: u64, locale: Locale) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE user
+            SET last_login_time = NOW(), locale = ?, update_time = update_time
+            WHERE pk_id = ?
+            "#,
+            locale.as_str(),
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    async fn update_user_role(&self, pk_id: u64, role: Role) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE user
+            SET role = ?
+            WHERE pk_id = ?
+            "#,
+            role as u8,
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected > 0)
+    }
+
+    async fn find_token_version(&self, pk_id: u64) -> AppResult<u32> {
+        sqlx::query!(
+            r#"
+            SELECT token_version
+            FROM user
+            WHERE pk_id = ?
+            "#,
+            pk_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::BusinessError(ErrorCode::UserNotFound),
+            e => AppError::DatabaseError(e),
+        })
+        .map(|res| res.token_version)
+    }
+
+    async fn increase_token_version(&self, pk_id: u64) -> AppResult<bool> {
+        let rows_affected = sqlx::query!(
+            r#"
+            UPDATE user
+            SET token_version = token_version + 1
+            WHERE pk_id = ?
+            "#,
+            pk_id,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(rows_affected > 0)
     }
 }
@@ -0,0 +1,84 @@
+use axum::extract::{Extension, Path};
+use axum::Json;
+
+use crate::common::api::ApiResult;
+use crate::common::validate::ValidatedJson;
+use crate::middleware::role::{Admin, Moderator, RequireRole};
+use crate::model::tag::TagFront;
+use crate::model::user::{RoleFront, VerifyStatus};
+use crate::service::{admin_service, tag_service};
+use crate::{AppResult, ShareState};
+
+pub(crate) async fn pin_topic(
+    Path(pk_id): Path<u64>,
+    moderator: RequireRole<Moderator>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    admin_service::set_topic_top(pk_id, true, &moderator, &state.repos).await?;
+    Ok(ApiResult::ok().msg("置顶主题成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn unpin_topic(
+    Path(pk_id): Path<u64>,
+    moderator: RequireRole<Moderator>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    admin_service::set_topic_top(pk_id, false, &moderator, &state.repos).await?;
+    Ok(ApiResult::ok().msg("取消置顶成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn remove_topic(
+    Path(pk_id): Path<u64>,
+    moderator: RequireRole<Moderator>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    admin_service::delete_topic(pk_id, &moderator, &state.repos).await?;
+    Ok(ApiResult::ok().msg("删除主题成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn remove_comment(
+    Path(pk_id): Path<u64>,
+    moderator: RequireRole<Moderator>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    admin_service::delete_comment(pk_id, &moderator, &state.repos).await?;
+    Ok(ApiResult::ok().msg("删除评论成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn create_tag(
+    ValidatedJson(tag): ValidatedJson<TagFront>,
+    admin: RequireRole<Admin>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<u64>> {
+    let pk_id = tag_service::create_tag(tag, &admin, &state.repos).await?;
+    Ok(ApiResult::ok().msg("创建标签成功").data(pk_id))
+}
+
+pub(crate) async fn update_tag(
+    Path(pk_id): Path<u64>,
+    ValidatedJson(tag): ValidatedJson<TagFront>,
+    admin: RequireRole<Admin>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    tag_service::update_tag(pk_id, tag, &admin, &state.repos).await?;
+    Ok(ApiResult::ok().msg("修改标签成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn delete_tag(
+    Path(pk_id): Path<u64>,
+    admin: RequireRole<Admin>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    tag_service::delete_tag(pk_id, &admin, &state.repos).await?;
+    Ok(ApiResult::ok().msg("删除标签成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn update_user_role(
+    Path(user_id): Path<u64>,
+    Json(role): Json<RoleFront>,
+    admin: RequireRole<Admin>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    admin_service::update_user_role(user_id, role.role, &admin, &state.repos, &*state.cache).await?;
+    Ok(ApiResult::ok().msg("修改角色成功").data(VerifyStatus::success()))
+}
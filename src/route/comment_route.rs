@@ -1 +1,65 @@
+use axum::extract::{Extension, Path, Query};
 
+use crate::common::api::{ApiResult, Page, Pagination};
+use crate::common::validate::ValidatedJson;
+use crate::model::comment::{Comment, CommentFront, CommentNode};
+use crate::model::user::{UserToken, VerifyStatus};
+use crate::service::comment_service;
+use crate::{AppResult, ShareState};
+
+pub(crate) async fn create_comment(
+    Path(topic_id): Path<u64>,
+    ValidatedJson(comment): ValidatedJson<CommentFront>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<u64>> {
+    let pk_id =
+        comment_service::create_comment(topic_id, comment, &user_token, &state.repos, &state.notice_hub).await?;
+    Ok(ApiResult::ok().msg("评论成功").data(pk_id))
+}
+
+pub(crate) async fn list_topic_comments(
+    Path(topic_id): Path<u64>,
+    Query(pagination): Query<Pagination>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<Comment>>> {
+    let page = comment_service::list_topic_comments(topic_id, pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
+
+pub(crate) async fn list_topic_comment_tree(
+    Path(topic_id): Path<u64>,
+    Query(pagination): Query<Pagination>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<CommentNode>>> {
+    let page = comment_service::list_topic_comment_tree(topic_id, pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
+
+pub(crate) async fn list_user_comments(
+    Path(user_id): Path<u64>,
+    Query(pagination): Query<Pagination>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<Comment>>> {
+    let page = comment_service::list_user_comments(user_id, pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
+
+pub(crate) async fn update_comment(
+    Path(pk_id): Path<u64>,
+    ValidatedJson(comment): ValidatedJson<CommentFront>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    comment_service::update_comment(pk_id, comment, &user_token, &state.repos).await?;
+    Ok(ApiResult::ok().msg("修改评论成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn delete_comment(
+    Path(pk_id): Path<u64>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    comment_service::delete_comment(pk_id, &user_token, &state.repos).await?;
+    Ok(ApiResult::ok().msg("删除评论成功").data(VerifyStatus::success()))
+}
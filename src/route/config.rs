@@ -1,23 +1,224 @@
-use axum::handler::{get, post};
+use std::convert::Infallible;
+
+use axum::handler::{delete, get, post, put};
+use axum::http::{Method, StatusCode};
 use axum::routing::BoxRoute;
-use axum::Router;
+use axum::{service, Router};
+use tower_http::services::ServeDir;
 
-use crate::route::topic_route::create_topic;
+use crate::common::constant::UPLOAD_ROUTE;
+use crate::common::settings::{StorageBackend, UploadSettings};
+use crate::middleware::auth::JwtAuth;
+use crate::middleware::rate_limit::{LimitKey, Policy, RateLimitLayer};
+use crate::route::admin_route::{
+    create_tag, delete_tag, pin_topic, remove_comment, remove_topic, unpin_topic, update_tag, update_user_role,
+};
+use crate::route::comment_route::{
+    create_comment, delete_comment, list_topic_comment_tree, list_topic_comments, list_user_comments, update_comment,
+};
+use crate::route::notice_route::{list_notices, read_all_notices, read_notice, stream_notices, stream_ticket};
+use crate::route::search_route::search;
+use crate::route::star_route::{
+    follow_user, like_comment, list_my_bookmarks, list_my_followers, list_my_followings, star_topic, unfollow_user,
+    unlike_comment, unstar_topic,
+};
+use crate::route::tag_route::{list_tag_topics, tag_tree};
+use crate::route::topic_route::{create_topic, delete_topic, get_topic, list_topics, list_user_topics, update_topic};
+use crate::route::upload_route::upload_image;
 use crate::route::user_route::{
-    change_user_pwd, find_user_pwd, get_captcha, login, validate_email, validate_username, verify_captcha, verify_email,
+    change_user_pwd, find_user_pwd, get_captcha, get_user_profile, login, logout, logout_all, refresh_token,
+    update_my_profile, validate_email, validate_username, verify_captcha, verify_email,
 };
 
+// 路由按模块分组后各自 boxed 再合并，避免路由嵌套类型过深导致编译过慢
+#[inline]
+pub fn init(upload: &UploadSettings) -> Router<BoxRoute> {
+    auth_routes()
+        .or(session_routes())
+        .or(user_routes())
+        .or(topic_routes())
+        .or(comment_routes())
+        .or(star_routes())
+        .or(star_list_routes())
+        .or(notice_routes())
+        .or(tag_routes())
+        .or(search_routes())
+        .or(upload_routes(upload))
+        .or(admin_routes())
+        .or(admin_tag_routes())
+        .boxed()
+}
+
+/// 无需登录即可访问的路由
 #[inline]
-pub fn init() -> Router<BoxRoute> {
+pub fn auth() -> JwtAuth {
+    JwtAuth::new()
+        .allow("/login")
+        .allow("/token/refresh")
+        .allow("/captcha")
+        .allow("/find/user")
+        .allow("/verify/*")
+        .allow("/user/validate/*")
+        .allow_method(Method::GET, "/topic/:id")
+        .allow_method(Method::GET, "/topics")
+        .allow_method(Method::GET, "/user/:username")
+        .allow_method(Method::GET, "/user/:id/topics")
+        .allow_method(Method::GET, "/topic/:id/comments")
+        .allow_method(Method::GET, "/topic/:id/comments/tree")
+        .allow_method(Method::GET, "/user/:id/comments")
+        .allow_method(Method::GET, "/tags/tree")
+        .allow_method(Method::GET, "/tag/:id/topics")
+        .allow_method(Method::GET, "/search")
+        .allow_method(Method::GET, &format!("{}/*", UPLOAD_ROUTE))
+        // 推送连接在处理函数中认证，以支持通过查询参数传递一次性票据
+        .allow_method(Method::GET, "/notices/stream")
+}
+
+/// 限流规则，时间窗口单位为秒
+#[inline]
+pub fn rate_limit() -> RateLimitLayer {
+    RateLimitLayer::new()
+        .policy(
+            Policy::new("login", Method::POST, "/login")
+                .limit(LimitKey::Ip, 20, 60)
+                .limit(LimitKey::Email, 10, 60 * 10),
+        )
+        .policy(Policy::new("captcha", Method::GET, "/captcha").limit(LimitKey::Ip, 30, 60))
+        // 以下接口会发送邮件
+        .policy(
+            Policy::new("register_mail", Method::POST, "/verify/captcha")
+                .limit(LimitKey::Ip, 5, 60)
+                .limit(LimitKey::Ip, 30, 60 * 60 * 24)
+                .limit(LimitKey::Email, 3, 60 * 10),
+        )
+        .policy(
+            Policy::new("reset_mail", Method::POST, "/find/user")
+                .limit(LimitKey::Ip, 5, 60)
+                .limit(LimitKey::Ip, 30, 60 * 60 * 24)
+                .limit(LimitKey::Email, 3, 60 * 10),
+        )
+        .policy(Policy::new("register", Method::POST, "/verify/email").limit(LimitKey::Ip, 10, 60))
+        .policy(Policy::new("refresh_token", Method::POST, "/token/refresh").limit(LimitKey::Ip, 30, 60))
+        .policy(Policy::new("create_topic", Method::POST, "/topic").limit(LimitKey::User, 10, 60))
+        .policy(Policy::new("create_comment", Method::POST, "/topic/:id/comments").limit(LimitKey::User, 30, 60))
+        .policy(
+            Policy::new("upload_image", Method::POST, "/upload/:kind")
+                .limit(LimitKey::User, 10, 60)
+                .limit(LimitKey::User, 200, 60 * 60 * 24),
+        )
+}
+
+fn auth_routes() -> Router<BoxRoute> {
     Router::new()
-        .route("/user/validate/email/:email", get(validate_email))
-        .route("/user/validate/username/:username", get(validate_username))
         .route("/verify/captcha", post(verify_captcha))
         .route("/verify/email", post(verify_email))
         .route("/login", post(login))
+        .route("/captcha", get(get_captcha))
+        .boxed()
+}
+
+fn session_routes() -> Router<BoxRoute> {
+    Router::new()
+        .route("/token/refresh", post(refresh_token))
+        .route("/logout", post(logout))
+        .route("/logout/all", post(logout_all))
+        .boxed()
+}
+
+fn user_routes() -> Router<BoxRoute> {
+    Router::new()
+        .route("/user/validate/email/:email", get(validate_email))
+        .route("/user/validate/username/:username", get(validate_username))
         .route("/change/user/:pwd", post(change_user_pwd))
         .route("/find/user", post(find_user_pwd))
-        .route("/captcha", get(get_captcha))
+        // `/user/me` 只处理 PUT，GET 请求会继续匹配 `/user/:username`
+        .route("/user/:username", get(get_user_profile))
+        .route("/user/me", put(update_my_profile))
+        .boxed()
+}
+
+fn topic_routes() -> Router<BoxRoute> {
+    Router::new()
         .route("/topic", post(create_topic))
+        .route("/topic/:id", get(get_topic).put(update_topic).delete(delete_topic))
+        .route("/topics", get(list_topics))
+        .route("/user/:id/topics", get(list_user_topics))
+        .boxed()
+}
+
+fn comment_routes() -> Router<BoxRoute> {
+    Router::new()
+        .route("/topic/:id/comments", get(list_topic_comments).post(create_comment))
+        .route("/topic/:id/comments/tree", get(list_topic_comment_tree))
+        .route("/comment/:id", put(update_comment).delete(delete_comment))
+        .route("/user/:id/comments", get(list_user_comments))
+        .boxed()
+}
+
+fn star_routes() -> Router<BoxRoute> {
+    Router::new()
+        .route("/topic/:id/star", put(star_topic).delete(unstar_topic))
+        .route("/comment/:id/like", put(like_comment).delete(unlike_comment))
+        .route("/user/:id/follow", put(follow_user).delete(unfollow_user))
+        .boxed()
+}
+
+fn star_list_routes() -> Router<BoxRoute> {
+    Router::new()
+        .route("/user/me/bookmarks", get(list_my_bookmarks))
+        .route("/user/me/followers", get(list_my_followers))
+        .route("/user/me/followings", get(list_my_followings))
+        .boxed()
+}
+
+fn notice_routes() -> Router<BoxRoute> {
+    Router::new()
+        .route("/notices", get(list_notices))
+        .route("/notice/:id/read", put(read_notice))
+        .route("/notices/read", put(read_all_notices))
+        .route("/notices/stream", get(stream_notices))
+        .route("/notices/stream/ticket", post(stream_ticket))
+        .boxed()
+}
+
+fn tag_routes() -> Router<BoxRoute> {
+    Router::new()
+        .route("/tags/tree", get(tag_tree))
+        .route("/tag/:id/topics", get(list_tag_topics))
+        .boxed()
+}
+
+fn search_routes() -> Router<BoxRoute> {
+    Router::new().route("/search", get(search)).boxed()
+}
+
+/// `local` 存储的文件由 `/uploads` 路由直接提供访问
+fn upload_routes(settings: &UploadSettings) -> Router<BoxRoute> {
+    let router = Router::new().route("/upload/:kind", post(upload_image));
+    match settings.backend {
+        StorageBackend::Local => {
+            let serve_dir = service::get(ServeDir::new(&settings.dir)).handle_error(|e: std::io::Error| {
+                error!("读取上传文件失败: {}", e);
+                Ok::<_, Infallible>(StatusCode::INTERNAL_SERVER_ERROR)
+            });
+            router.nest(UPLOAD_ROUTE, serve_dir).boxed()
+        }
+    }
+}
+
+/// 管理路由，处理函数通过 `RequireRole` 校验角色
+fn admin_routes() -> Router<BoxRoute> {
+    Router::new()
+        .route("/admin/topic/:id/top", put(pin_topic).delete(unpin_topic))
+        .route("/admin/topic/:id", delete(remove_topic))
+        .route("/admin/comment/:id", delete(remove_comment))
+        .boxed()
+}
+
+fn admin_tag_routes() -> Router<BoxRoute> {
+    Router::new()
+        .route("/admin/tag", post(create_tag))
+        .route("/admin/tag/:id", put(update_tag).delete(delete_tag))
+        .route("/admin/user/:id/role", put(update_user_role))
         .boxed()
 }
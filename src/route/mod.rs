@@ -1,4 +1,10 @@
+pub mod admin_route;
 pub mod comment_route;
 pub mod config;
+pub mod notice_route;
+pub mod search_route;
+pub mod star_route;
+pub mod tag_route;
 pub mod topic_route;
+pub mod upload_route;
 pub mod user_route;
@@ -0,0 +1,99 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::extract::{Extension, Path, Query};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::stream::{self, Stream};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::common::api::{ApiResult, Pagination};
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::locale::Locale;
+use crate::model::notice::{NoticePage, StreamTicket};
+use crate::model::user::{UserToken, VerifyStatus};
+use crate::service::{notice_service, session_service};
+use crate::{AppResult, ShareState};
+
+#[derive(Debug, Deserialize)]
+pub struct StreamQuery {
+    ticket: Option<String>,
+}
+
+pub(crate) async fn list_notices(
+    Query(pagination): Query<Pagination>,
+    user_token: UserToken,
+    locale: Locale,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<NoticePage>> {
+    let page = notice_service::list_notices(user_token.user_id, pagination, locale, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
+
+pub(crate) async fn read_notice(
+    Path(pk_id): Path<u64>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    notice_service::mark_read(pk_id, user_token.user_id, &state.repos).await?;
+    Ok(ApiResult::ok().data(VerifyStatus::success()))
+}
+
+pub(crate) async fn read_all_notices(user_token: UserToken, state: Extension<ShareState>) -> AppResult<ApiResult<u64>> {
+    let count = notice_service::mark_all_read(user_token.user_id, &state.repos).await?;
+    Ok(ApiResult::ok().msg("已全部标记为已读").data(count))
+}
+
+/// 签发建立通知推送连接用的一次性票据
+pub(crate) async fn stream_ticket(
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<StreamTicket>> {
+    let ticket = session_service::issue_stream_ticket(&user_token, &*state.cache).await?;
+    Ok(ApiResult::ok().data(ticket))
+}
+
+/// 通过 Server-Sent Events 实时推送新通知
+///
+/// 浏览器的 EventSource 无法设置请求头，因此除了请求头外也可以通过 `ticket` 查询参数传递一次性票据，
+/// 票据通过 `POST /notices/stream/ticket` 获取。
+pub(crate) async fn stream_notices(
+    user_token: Option<UserToken>,
+    Query(query): Query<StreamQuery>,
+    locale: Locale,
+    state: Extension<ShareState>,
+) -> AppResult<(HeaderMap, Sse<impl Stream<Item = Result<Event, Infallible>>>)> {
+    let user_token = match (user_token, query.ticket) {
+        (Some(user_token), _) => user_token,
+        (None, Some(ticket)) => session_service::consume_stream_ticket(&ticket, &state.repos, &*state.cache).await?,
+        (None, None) => return Err(AppError::BusinessError(ErrorCode::NotLoggedIn)),
+    };
+    let user_id = user_token.user_id;
+    let receiver = state.notice_hub.subscribe();
+    let events = stream::unfold(receiver, move |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(notice) if notice.notified_user_id == user_id => {
+                    let event = Event::default()
+                        .event("notice")
+                        .id(notice.pk_id.to_string())
+                        .json_data((*notice).clone().localize(locale))
+                        .unwrap_or_else(|_| Event::default().event("notice"));
+                    return Some((Ok(event), receiver));
+                }
+                Ok(_) => continue,
+                // 推送过慢丢失的通知可以通过通知列表接口获取
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("用户 {} 的通知推送过慢，丢弃 {} 条通知", user_id, skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    // 推送流不能压缩，否则压缩缓冲会导致通知无法及时送达
+    let mut headers = HeaderMap::with_capacity(1usize);
+    headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("identity"));
+    let sse = Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)));
+    Ok((headers, sse))
+}
@@ -0,0 +1,15 @@
+use axum::extract::{Extension, Query};
+
+use crate::common::api::{ApiResult, Page, Pagination};
+use crate::model::search::{SearchHit, SearchQuery};
+use crate::service::search_service;
+use crate::{AppResult, ShareState};
+
+pub(crate) async fn search(
+    Query(query): Query<SearchQuery>,
+    Query(pagination): Query<Pagination>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<SearchHit>>> {
+    let page = search_service::search(query, pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
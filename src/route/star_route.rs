@@ -0,0 +1,96 @@
+use axum::extract::{Extension, Path, Query};
+
+use crate::common::api::{ApiResult, Page, Pagination};
+use crate::model::star::StarType;
+use crate::model::topic::Topic;
+use crate::model::user::{UserBrief, UserToken, VerifyStatus};
+use crate::service::star_service;
+use crate::{AppResult, ShareState};
+
+pub(crate) async fn star_topic(
+    Path(topic_id): Path<u64>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    star_service::star(StarType::Topic, topic_id, &user_token, &state.repos, &state.notice_hub).await?;
+    Ok(ApiResult::ok().msg("收藏成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn unstar_topic(
+    Path(topic_id): Path<u64>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    star_service::unstar(StarType::Topic, topic_id, &user_token, &state.repos).await?;
+    Ok(ApiResult::ok().msg("取消收藏成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn like_comment(
+    Path(comment_id): Path<u64>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    star_service::star(
+        StarType::Comment,
+        comment_id,
+        &user_token,
+        &state.repos,
+        &state.notice_hub,
+    )
+    .await?;
+    Ok(ApiResult::ok().msg("点赞成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn unlike_comment(
+    Path(comment_id): Path<u64>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    star_service::unstar(StarType::Comment, comment_id, &user_token, &state.repos).await?;
+    Ok(ApiResult::ok().msg("取消点赞成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn follow_user(
+    Path(user_id): Path<u64>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    star_service::star(StarType::User, user_id, &user_token, &state.repos, &state.notice_hub).await?;
+    Ok(ApiResult::ok().msg("关注成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn unfollow_user(
+    Path(user_id): Path<u64>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    star_service::unstar(StarType::User, user_id, &user_token, &state.repos).await?;
+    Ok(ApiResult::ok().msg("取消关注成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn list_my_bookmarks(
+    Query(pagination): Query<Pagination>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<Topic>>> {
+    let page = star_service::list_star_topics(user_token.user_id, pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
+
+pub(crate) async fn list_my_followers(
+    Query(pagination): Query<Pagination>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<UserBrief>>> {
+    let page = star_service::list_followers(user_token.user_id, pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
+
+pub(crate) async fn list_my_followings(
+    Query(pagination): Query<Pagination>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<UserBrief>>> {
+    let page = star_service::list_followings(user_token.user_id, pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
@@ -0,0 +1,21 @@
+use axum::extract::{Extension, Path, Query};
+
+use crate::common::api::{ApiResult, Page, Pagination};
+use crate::model::tag::TagNode;
+use crate::model::topic::Topic;
+use crate::service::tag_service;
+use crate::{AppResult, ShareState};
+
+pub(crate) async fn tag_tree(state: Extension<ShareState>) -> AppResult<ApiResult<Vec<TagNode>>> {
+    let tree = tag_service::tag_tree(&state.repos).await?;
+    Ok(ApiResult::ok().data(tree))
+}
+
+pub(crate) async fn list_tag_topics(
+    Path(tag_id): Path<u64>,
+    Query(pagination): Query<Pagination>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<Topic>>> {
+    let page = tag_service::list_tag_topics(tag_id, pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
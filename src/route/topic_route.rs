@@ -1,22 +1,63 @@
-use axum::extract::Extension;
-use axum::Json;
+use axum::extract::{Extension, Path, Query};
 
-use crate::common::api::ApiResult;
-use crate::common::err::AppError;
-use crate::model::topic::TopicFront;
+use crate::common::api::{ApiResult, Page, Pagination};
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::validate::ValidatedJson;
+use crate::model::topic::{Topic, TopicFront};
 use crate::model::user::{UserToken, VerifyStatus};
-use crate::repository::topic_repository;
+use crate::service::topic_service;
 use crate::{AppResult, ShareState};
 
 pub(crate) async fn create_topic(
-    Json(mut new_topic): Json<TopicFront>,
+    ValidatedJson(mut new_topic): ValidatedJson<TopicFront>,
     user_token: UserToken,
     state: Extension<ShareState>,
 ) -> AppResult<ApiResult<VerifyStatus>> {
     new_topic.user_id = Some(user_token.user_id);
-    if topic_repository::insert_one_topic(new_topic, &state.db_pool).await? {
+    if topic_service::create_topic(new_topic, &user_token, &state.repos, &state.notice_hub).await? > 0 {
         Ok(ApiResult::ok().msg("创建主题成功").data(VerifyStatus::success()))
     } else {
-        Err(AppError::BusinessError(500, "创建主题失败"))
+        Err(AppError::BusinessError(ErrorCode::InternalError))
     }
 }
+
+pub(crate) async fn get_topic(Path(pk_id): Path<u64>, state: Extension<ShareState>) -> AppResult<ApiResult<Topic>> {
+    let topic = topic_service::get_topic(pk_id, &state.repos).await?;
+    Ok(ApiResult::ok().data(topic))
+}
+
+pub(crate) async fn list_topics(
+    Query(pagination): Query<Pagination>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<Topic>>> {
+    let page = topic_service::list_topics(pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
+
+pub(crate) async fn list_user_topics(
+    Path(user_id): Path<u64>,
+    Query(pagination): Query<Pagination>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<Page<Topic>>> {
+    let page = topic_service::list_user_topics(user_id, pagination, &state.repos).await?;
+    Ok(ApiResult::ok().data(page))
+}
+
+pub(crate) async fn update_topic(
+    Path(pk_id): Path<u64>,
+    ValidatedJson(topic): ValidatedJson<TopicFront>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    topic_service::update_topic(pk_id, topic, &user_token, &state.repos).await?;
+    Ok(ApiResult::ok().msg("修改主题成功").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn delete_topic(
+    Path(pk_id): Path<u64>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    topic_service::delete_topic(pk_id, &user_token, &state.repos).await?;
+    Ok(ApiResult::ok().msg("删除主题成功").data(VerifyStatus::success()))
+}
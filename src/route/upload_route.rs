@@ -0,0 +1,54 @@
+use axum::extract::multipart::{Multipart, MultipartRejection};
+use axum::extract::{Extension, Path};
+use futures::StreamExt;
+
+use crate::common::api::ApiResult;
+use crate::common::err::{AppError, ErrorCode, FieldError};
+use crate::model::upload::{ImageKind, UploadedImage};
+use crate::model::user::UserToken;
+use crate::service::upload_service;
+use crate::{AppResult, ShareState};
+
+/// 上传文件的表单字段名
+const FILE_FIELD: &str = "file";
+
+/// `multipart/form-data` 上传一张图片，文件放在 `file` 字段中
+pub(crate) async fn upload_image(
+    Path(kind): Path<ImageKind>,
+    multipart: Result<Multipart, MultipartRejection>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<UploadedImage>> {
+    let mut multipart = multipart.map_err(|e| {
+        info!("上传请求解析失败: {:?}", e);
+        AppError::BusinessError(ErrorCode::BadRequest)
+    })?;
+    let settings = &state.settings.upload;
+    let bad_request = |e| {
+        info!("上传请求解析失败: {:?}", e);
+        AppError::BusinessError(ErrorCode::BadRequest)
+    };
+    while let Some(mut field) = multipart.next_field().await.map_err(bad_request)? {
+        if field.name() != Some(FILE_FIELD) {
+            continue;
+        }
+        let content_type = field.content_type().map(|mime| mime.essence_str().to_string());
+        // 边读边检查大小，不把超过上限的文件读入内存
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(bad_request)?;
+            if bytes.len() + chunk.len() > settings.max_file_size {
+                return Err(AppError::BusinessError(ErrorCode::PayloadTooLarge));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        info!("用户 {} 上传图片，大小 {} 字节", user_token.user_id, bytes.len());
+        let uploaded =
+            upload_service::upload_image(kind, bytes, content_type.as_deref(), settings, &*state.storage).await?;
+        return Ok(ApiResult::ok().msg("上传成功").data(uploaded));
+    }
+    Err(AppError::ValidationError(vec![FieldError::new(
+        FILE_FIELD,
+        ErrorCode::Required,
+    )]))
+}
@@ -1,18 +1,23 @@
-use std::sync::Arc;
-
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
-use axum::extract::{Extension, Form, Path};
+use axum::extract::{Extension, Path};
 use axum::http::header::HeaderName;
 use axum::http::{HeaderMap, HeaderValue, StatusCode};
-use chrono::Local;
+use tera::Context;
 
 use crate::common::api::ApiResult;
-use crate::common::constant::TOKEN_HEADER_NAME;
-use crate::common::err::AppError;
+use crate::common::constant::{EMAIL_CODE_EXPIRE_SECS, TOKEN_HEADER_NAME};
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::locale::Locale;
 use crate::common::util;
-use crate::model::user::{CaptchaUser, FindUserPwd, LoginUser, RegisterUser, User, UserToken, VerifyStatus};
-use crate::service::user_service;
+use crate::common::validate::{ValidatedForm, ValidatedJson, Validator};
+use crate::common::verify_code::{self, VerifyCodeKind};
+use crate::mail::MailTemplate;
+use crate::model::user::{
+    CaptchaUser, FindUserPwd, LoginUser, ProfileFront, RefreshTokenFront, RegisterUser, TokenPair, UserProfile,
+    UserToken, VerifyStatus,
+};
+use crate::service::{session_service, user_service};
 use crate::{AppResult, ShareState};
 
 pub(crate) async fn validate_email(
@@ -20,20 +25,21 @@ pub(crate) async fn validate_email(
     state: Extension<ShareState>,
 ) -> AppResult<ApiResult<VerifyStatus>> {
     util::validate_email(&email).await?;
-    user_service::check_email_exists(email, &state.db_pool).await.into()
+    user_service::check_email_exists(email, &state.repos).await
 }
 
 pub(crate) async fn validate_username(
     Path(username): Path<String>,
     state: Extension<ShareState>,
-) -> ApiResult<VerifyStatus> {
-    user_service::check_username_exists(username, &state.db_pool).await
+) -> AppResult<ApiResult<VerifyStatus>> {
+    Validator::new().username("username", &username).finish()?;
+    user_service::check_username_exists(username, &state.repos).await
 }
 
 pub(crate) async fn get_captcha(state: Extension<ShareState>) -> AppResult<(StatusCode, HeaderMap, Vec<u8>)> {
-    let conn = &mut state.get_redis_conn().await?;
+    let cache = &*state.cache;
     let (key, captcha_value, vec) = util::gen_pic_captcha().await?;
-    util::redis_set(&key, &captcha_value, 60 * 5, conn).await;
+    verify_code::save(VerifyCodeKind::Captcha, &key, &captcha_value, cache).await?;
     let mut headers = HeaderMap::with_capacity(1usize);
     headers.insert(
         HeaderName::from_static("captcha-key"),
@@ -47,20 +53,29 @@ pub(crate) async fn get_captcha(state: Extension<ShareState>) -> AppResult<(Stat
 }
 
 pub(crate) async fn verify_captcha(
-    captcha_user: Form<CaptchaUser>,
+    captcha_user: ValidatedForm<CaptchaUser>,
+    locale: Locale,
     state: Extension<ShareState>,
 ) -> AppResult<ApiResult<VerifyStatus>> {
-    util::validate_email(&captcha_user.email).await?;
-    let connection = &mut state.get_redis_conn().await?;
-    util::verify_captcha(&captcha_user.captcha_key, &captcha_user.captcha_value, connection).await?;
-    // todo!("删除验证码缓存")
-    let arc = Arc::clone(&state.smtp_transport);
+    let cache = &*state.cache;
+    verify_code::verify(
+        VerifyCodeKind::Captcha,
+        &captcha_user.captcha_key,
+        &captcha_user.captcha_value,
+        cache,
+    )
+    .await?;
     let verify_code = String::from(SaltString::generate(&mut OsRng).as_str());
-    util::redis_set(&captcha_user.email, &verify_code, 60 * 50, connection).await;
-    tokio::spawn(async move {
-        let smtp_transport = arc.lock().await;
-        util::send_email(&captcha_user.email, &verify_code, smtp_transport).await;
-    });
+    verify_code::save(VerifyCodeKind::Register, &captcha_user.email, &verify_code, cache).await?;
+    state
+        .mailer
+        .send_template(
+            MailTemplate::Register,
+            locale,
+            &captcha_user.email,
+            &verify_code_context(&verify_code),
+        )
+        .await?;
     ApiResult::ok()
         .msg("验证码校验成功，已发送验证码到您邮箱，请查收")
         .data(VerifyStatus::success())
@@ -68,88 +83,104 @@ pub(crate) async fn verify_captcha(
 }
 
 pub(crate) async fn verify_email(
-    register_user: Form<RegisterUser>,
+    ValidatedForm(mut register_user): ValidatedForm<RegisterUser>,
+    locale: Locale,
     state: Extension<ShareState>,
 ) -> AppResult<ApiResult<VerifyStatus>> {
-    let connection = &mut state.get_redis_conn().await?;
-    let pool = &state.db_pool;
-    user_service::register_user(register_user.0, connection, pool).await
+    let cache = &*state.cache;
+    let repos = &state.repos;
+    register_user.locale = locale;
+    user_service::register_user(register_user, cache, repos).await
 }
 
 pub(crate) async fn login(
-    login_user: Form<LoginUser>,
+    login_user: ValidatedForm<LoginUser>,
+    locale: Locale,
     state: Extension<ShareState>,
 ) -> AppResult<(StatusCode, HeaderMap, Vec<u8>)> {
-    let connection = &mut state.get_redis_conn().await?;
-    let pool = &state.db_pool;
-    util::verify_captcha(&login_user.captcha_key, &login_user.captcha_value, connection).await?;
-    let u = User::find_user_by_email(&login_user.email, pool).await?;
-    let login_success = util::verify_pwd(&login_user.password, &u.user_password).await?;
-    if login_success {
-        let exp: usize = if login_user.forever {
-            (Local::now().timestamp() + 60 * 60 * 24 * 365) as usize
-        } else {
-            (Local::now().timestamp() + 60 * 60 * 24 * 7) as usize
-        };
-        let user_token = util::token_encode(&UserToken::new(u.pk_id, u.uk_username, u.uk_email, exp)).await?;
-        let mut headers = HeaderMap::with_capacity(1usize);
-        headers.insert(
-            HeaderName::from_static(TOKEN_HEADER_NAME),
-            HeaderValue::from_static(Box::leak(user_token.into_boxed_str())),
-        );
-        headers.insert(
-            HeaderName::from_static("content-type"),
-            HeaderValue::from_static("application/json"),
-        );
-        let body = serde_json::to_vec(&ApiResult::ok().msg("登录成功").data(VerifyStatus::success()))?;
-        Ok((StatusCode::OK, headers, body))
-    } else {
-        let body = serde_json::to_vec(
-            &ApiResult::error()
-                .msg("登录失败，用户名或密码错误")
-                .data(VerifyStatus::fail()),
-        )?;
-        let mut headers = HeaderMap::with_capacity(1usize);
-        headers.insert(
-            HeaderName::from_static("content-type"),
-            HeaderValue::from_static("application/json"),
-        );
-        Ok((StatusCode::OK, headers, body))
-    }
+    let cache = &*state.cache;
+    let rate_limit = &state.settings.rate_limit;
+    let token_pair = user_service::login(&login_user, locale, rate_limit, cache, &state.repos).await?;
+    let mut headers = HeaderMap::with_capacity(1usize);
+    headers.insert(
+        HeaderName::from_static(TOKEN_HEADER_NAME),
+        HeaderValue::from_static(Box::leak(token_pair.access_token.clone().into_boxed_str())),
+    );
+    headers.insert(
+        HeaderName::from_static("content-type"),
+        HeaderValue::from_static("application/json"),
+    );
+    let body = serde_json::to_vec(&ApiResult::ok().msg("登录成功").data(token_pair))?;
+    Ok((StatusCode::OK, headers, body))
+}
+
+pub(crate) async fn refresh_token(
+    ValidatedForm(refresh): ValidatedForm<RefreshTokenFront>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<TokenPair>> {
+    let cache = &*state.cache;
+    let token_pair = session_service::refresh_session(&refresh.refresh_token, &state.repos, cache).await?;
+    Ok(ApiResult::ok().data(token_pair))
+}
+
+pub(crate) async fn logout(user_token: UserToken, state: Extension<ShareState>) -> AppResult<ApiResult<VerifyStatus>> {
+    let cache = &*state.cache;
+    session_service::logout(&user_token, cache).await?;
+    Ok(ApiResult::ok().msg("已退出登录").data(VerifyStatus::success()))
+}
+
+pub(crate) async fn logout_all(
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<VerifyStatus>> {
+    let cache = &*state.cache;
+    session_service::revoke_all_sessions(user_token.user_id, &state.repos, cache).await?;
+    Ok(ApiResult::ok().msg("已退出所有设备").data(VerifyStatus::success()))
 }
 
 pub(crate) async fn find_user_pwd(
-    Form(find_user_pwd): Form<FindUserPwd>,
+    ValidatedForm(find_user_pwd): ValidatedForm<FindUserPwd>,
+    locale: Locale,
     state: Extension<ShareState>,
 ) -> AppResult<ApiResult<()>> {
-    let conn = &mut state.get_redis_conn().await?;
-    let pool = &state.db_pool;
-    let user = User::find_user_by_email(&find_user_pwd.email, pool).await?;
+    let cache = &*state.cache;
+    let repos = &state.repos;
     match find_user_pwd.email_verify_code {
         None => {
-            util::validate_email(&find_user_pwd.email).await?;
-            util::verify_captcha(&find_user_pwd.captcha_key, &find_user_pwd.captcha_value, conn).await?;
-            let arc = Arc::clone(&state.smtp_transport);
-            let verify_code = String::from(SaltString::generate(&mut OsRng).as_str());
-            util::redis_set(&find_user_pwd.email, &verify_code, 60 * 50, conn).await;
-            tokio::spawn(async move {
-                let smtp_transport = arc.lock().await;
-                util::send_email(&find_user_pwd.email, &verify_code, smtp_transport).await;
-            });
-            Ok(ApiResult::ok().msg("验证码已发送至邮箱").data(()))
+            verify_code::verify(
+                VerifyCodeKind::Captcha,
+                &find_user_pwd.captcha_key,
+                &find_user_pwd.captcha_value,
+                cache,
+            )
+            .await?;
+            // 无论邮箱是否注册都返回相同的结果，避免被用来探测账号
+            if repos.users.count_by_email(&find_user_pwd.email).await? > 0 {
+                let verify_code = String::from(SaltString::generate(&mut OsRng).as_str());
+                verify_code::save(VerifyCodeKind::ResetPassword, &find_user_pwd.email, &verify_code, cache).await?;
+                state
+                    .mailer
+                    .send_template(
+                        MailTemplate::ResetPassword,
+                        locale,
+                        &find_user_pwd.email,
+                        &verify_code_context(&verify_code),
+                    )
+                    .await?;
+            }
+            Ok(ApiResult::ok().msg("如果该邮箱已注册，验证码将发送至邮箱").data(()))
         }
         Some(code) => {
-            let cache_verify_code = util::redis_get::<String>(&find_user_pwd.email, conn).await?;
-            if code.eq(&cache_verify_code) {
-                let encode_pwd = util::encode_pwd(&find_user_pwd.password).await?;
-                let rows_affected = User::update_user_pwd(encode_pwd, user.pk_id, pool).await?;
-                if rows_affected == 1 {
-                    Ok(ApiResult::ok().msg("修改密码成功").data(()))
-                } else {
-                    Err(AppError::BusinessError(500, "修改密码失败"))
-                }
+            // 验证码只会发给已注册的邮箱，先校验验证码再查询用户
+            verify_code::verify(VerifyCodeKind::ResetPassword, &find_user_pwd.email, &code, cache).await?;
+            let user = repos.users.find_user_by_email(&find_user_pwd.email).await?;
+            let encode_pwd = util::encode_pwd(&find_user_pwd.password).await?;
+            let rows_affected = repos.users.update_user_pwd(encode_pwd, user.pk_id).await?;
+            if rows_affected == 1 {
+                session_service::revoke_all_sessions(user.pk_id, repos, cache).await?;
+                Ok(ApiResult::ok().msg("修改密码成功").data(()))
             } else {
-                Err(AppError::BusinessError(500, "邮箱验证码校验错误"))
+                Err(AppError::BusinessError(ErrorCode::UpdateConflict))
             }
         }
     }
@@ -160,12 +191,38 @@ pub(crate) async fn change_user_pwd(
     Path(pwd): Path<String>,
     state: Extension<ShareState>,
 ) -> AppResult<ApiResult<()>> {
-    let pool = &state.db_pool;
+    Validator::new().password("pwd", &pwd).finish()?;
+    let repos = &state.repos;
     let encode_pwd = util::encode_pwd(&pwd).await?;
-    let rows_affected = User::update_user_pwd(encode_pwd, user_token.user_id, pool).await?;
+    let rows_affected = repos.users.update_user_pwd(encode_pwd, user_token.user_id).await?;
     if rows_affected == 1 {
-        Ok(ApiResult::ok().msg("修改密码成功").data(()))
+        session_service::revoke_all_sessions(user_token.user_id, repos, &*state.cache).await?;
+        Ok(ApiResult::ok().msg("修改密码成功，请重新登录").data(()))
     } else {
-        Err(AppError::BusinessError(500, "修改密码失败"))
+        Err(AppError::BusinessError(ErrorCode::UpdateConflict))
     }
 }
+
+pub(crate) async fn get_user_profile(
+    Path(username): Path<String>,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<UserProfile>> {
+    let profile = user_service::get_profile(&username, &state.repos).await?;
+    Ok(ApiResult::ok().data(profile))
+}
+
+pub(crate) async fn update_my_profile(
+    ValidatedJson(profile): ValidatedJson<ProfileFront>,
+    user_token: UserToken,
+    state: Extension<ShareState>,
+) -> AppResult<ApiResult<UserProfile>> {
+    let profile = user_service::update_profile(user_token.user_id, profile, &state.repos).await?;
+    Ok(ApiResult::ok().msg("修改资料成功").data(profile))
+}
+
+fn verify_code_context(verify_code: &str) -> Context {
+    let mut context = Context::new();
+    context.insert("code", verify_code);
+    context.insert("expire_minutes", &(EMAIL_CODE_EXPIRE_SECS / 60));
+    context
+}
@@ -0,0 +1,115 @@
+use crate::cache::Cache;
+use crate::common::err::{AppError, ErrorCode};
+use crate::model::user::{Role, UserToken};
+use crate::repository::Repositories;
+use crate::service::session_service;
+use crate::AppResult;
+
+pub async fn set_topic_top(pk_id: u64, top: bool, operator: &UserToken, repos: &Repositories) -> AppResult<()> {
+    if repos.topics.update_topic_top(pk_id, top).await? {
+        info!("用户 {} 修改主题 {} 置顶状态为: {}", operator.user_id, pk_id, top);
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::TopicNotFound))
+    }
+}
+
+pub async fn delete_topic(pk_id: u64, operator: &UserToken, repos: &Repositories) -> AppResult<()> {
+    if repos.topics.delete_topic(pk_id, operator.user_id).await? {
+        info!("用户 {} 删除主题 {}", operator.user_id, pk_id);
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::TopicNotFound))
+    }
+}
+
+pub async fn delete_comment(pk_id: u64, operator: &UserToken, repos: &Repositories) -> AppResult<()> {
+    if repos.comments.delete_comment(pk_id).await? {
+        info!("用户 {} 删除评论 {}", operator.user_id, pk_id);
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::CommentNotFound))
+    }
+}
+
+// 修改角色后注销该用户的所有会话，旧令牌中的角色立即失效，重新登录后按新角色签发
+pub async fn update_user_role(
+    user_id: u64,
+    role: Role,
+    operator: &UserToken,
+    repos: &Repositories,
+    cache: &dyn Cache,
+) -> AppResult<()> {
+    if user_id == operator.user_id {
+        return Err(AppError::BusinessError(ErrorCode::CannotChangeOwnRole));
+    }
+    let user = repos.users.find_user_by_id(user_id).await?;
+    if user.role == role {
+        return Ok(());
+    }
+    if repos.users.update_user_role(user_id, role).await? {
+        info!("用户 {} 修改用户 {} 的角色为: {:?}", operator.user_id, user_id, role);
+        session_service::revoke_all_sessions(user_id, repos, cache).await
+    } else {
+        Err(AppError::BusinessError(ErrorCode::UpdateConflict))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CacheExt, MemoryCache};
+    use crate::common::test_util::{create_topic, create_user, error_code, user_token};
+
+    #[tokio::test]
+    async fn role_change_revokes_sessions() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user("tester", "tester@whatsoo.org", &repos).await;
+        let admin = user_token(user.pk_id + 1, Role::Admin);
+        let version_key = format!("token_version:{}", user.pk_id);
+        cache.set(&version_key, &user.token_version, None).await.unwrap();
+
+        update_user_role(user.pk_id, Role::Moderator, &admin, &repos, &cache)
+            .await
+            .unwrap();
+        let updated = repos.users.find_user_by_id(user.pk_id).await.unwrap();
+        assert_eq!(updated.role, Role::Moderator);
+        // 旧令牌中的角色随 token 版本一起失效
+        assert_eq!(updated.token_version, user.token_version + 1);
+        assert!(!cache.exists(&version_key).await.unwrap());
+
+        // 角色没有变化时不注销会话
+        update_user_role(user.pk_id, Role::Moderator, &admin, &repos, &cache)
+            .await
+            .unwrap();
+        assert_eq!(
+            repos.users.find_token_version(user.pk_id).await.unwrap(),
+            updated.token_version
+        );
+    }
+
+    #[tokio::test]
+    async fn admin_cannot_change_own_role() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user("tester", "tester@whatsoo.org", &repos).await;
+        let admin = user_token(user.pk_id, Role::Admin);
+        let result = update_user_role(user.pk_id, Role::User, &admin, &repos, &cache).await;
+        assert_eq!(error_code(result), Some(ErrorCode::CannotChangeOwnRole));
+        let result = update_user_role(user.pk_id + 1, Role::User, &user_token(0, Role::Admin), &repos, &cache).await;
+        assert_eq!(error_code(result), Some(ErrorCode::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn moderator_pins_and_deletes_content() {
+        let repos = Repositories::memory();
+        let moderator = user_token(2, Role::Moderator);
+        let pk_id = create_topic(1, "主题", &repos).await;
+        set_topic_top(pk_id, true, &moderator, &repos).await.unwrap();
+        assert!(repos.topics.find_topic_by_id(pk_id).await.unwrap().top);
+        delete_topic(pk_id, &moderator, &repos).await.unwrap();
+        let result = set_topic_top(pk_id, false, &moderator, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TopicNotFound));
+        let result = delete_comment(1, &moderator, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::CommentNotFound));
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use crate::common::api::{Page, Pagination};
+use crate::common::constant::MAX_COMMENT_DEPTH;
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::notice_hub::NoticeHub;
+use crate::model::comment::{Comment, CommentFront, CommentNode, NewComment};
+use crate::model::user::UserToken;
+use crate::repository::Repositories;
+use crate::service::notice_service;
+use crate::AppResult;
+
+pub async fn create_comment(
+    topic_id: u64,
+    comment: CommentFront,
+    user_token: &UserToken,
+    repos: &Repositories,
+    hub: &NoticeHub,
+) -> AppResult<u64> {
+    // 主题不存在或已删除时不能评论
+    let topic = repos.topics.find_topic_by_id(topic_id).await?;
+    let mut new_comment = NewComment {
+        user_id: user_token.user_id,
+        topic_id,
+        parent_id: None,
+        root_id: None,
+        depth: 0,
+        content: &comment.content,
+    };
+    let mut parent_author = None;
+    if let Some(parent_id) = comment.parent_id {
+        let parent = repos.comments.find_comment_by_id(parent_id).await?;
+        reply_to(&mut new_comment, &parent)?;
+        parent_author = Some(parent.user_id);
+    }
+    let pk_id = repos.comments.insert_one_comment(new_comment).await?;
+
+    let notified = match parent_author {
+        Some(author_id) => {
+            notice_service::notify_comment_reply(topic_id, &topic.title, author_id, user_token, repos, hub).await;
+            author_id
+        }
+        None => {
+            notice_service::notify_topic_comment(topic_id, &topic.title, topic.user_id, user_token, repos, hub).await;
+            topic.user_id
+        }
+    };
+    notice_service::notify_mentions(
+        &comment.content,
+        topic_id,
+        &topic.title,
+        &[notified],
+        user_token,
+        repos,
+        hub,
+    )
+    .await;
+    Ok(pk_id)
+}
+
+// 被回复的评论必须属于同一个主题，且嵌套层级没有达到上限
+fn reply_to(new_comment: &mut NewComment, parent: &Comment) -> AppResult<()> {
+    if parent.topic_id != new_comment.topic_id {
+        return Err(AppError::BusinessError(ErrorCode::ReplyNotInTopic));
+    }
+    if parent.depth >= MAX_COMMENT_DEPTH {
+        return Err(AppError::BusinessError(ErrorCode::ReplyTooDeep));
+    }
+    new_comment.parent_id = Some(parent.pk_id);
+    new_comment.root_id = Some(parent.root_id.unwrap_or(parent.pk_id));
+    new_comment.depth = parent.depth + 1;
+    Ok(())
+}
+
+pub async fn list_topic_comment_tree(
+    topic_id: u64,
+    pagination: Pagination,
+    repos: &Repositories,
+) -> AppResult<Page<CommentNode>> {
+    let total = repos.comments.count_root_comments_by_topic(topic_id).await?;
+    let comments = repos
+        .comments
+        .find_comment_threads_by_topic(topic_id, pagination.offset(), pagination.limit())
+        .await?;
+    Ok(Page::new(total, &pagination, build_comment_tree(comments)))
+}
+
+// comments 需按创建时间升序排列，回复总是晚于被回复的评论
+fn build_comment_tree(comments: Vec<Comment>) -> Vec<CommentNode> {
+    let mut children: HashMap<u64, Vec<Comment>> = HashMap::new();
+    let mut roots = Vec::new();
+    for comment in comments {
+        match comment.parent_id {
+            Some(parent_id) => children.entry(parent_id).or_default().push(comment),
+            None => roots.push(comment),
+        }
+    }
+    roots
+        .into_iter()
+        .filter_map(|root| build_comment_node(root, &mut children))
+        .collect()
+}
+
+// 已删除的评论只在还有可见回复时保留，并隐藏其内容
+fn build_comment_node(mut comment: Comment, children: &mut HashMap<u64, Vec<Comment>>) -> Option<CommentNode> {
+    let replies: Vec<CommentNode> = children
+        .remove(&comment.pk_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|reply| build_comment_node(reply, children))
+        .collect();
+    if comment.deleted {
+        if replies.is_empty() {
+            return None;
+        }
+        comment.content = String::from("该评论已删除");
+    }
+    Some(CommentNode { comment, replies })
+}
+
+pub async fn list_topic_comments(
+    topic_id: u64,
+    pagination: Pagination,
+    repos: &Repositories,
+) -> AppResult<Page<Comment>> {
+    let total = repos.comments.count_comments_by_topic(topic_id).await?;
+    let records = repos
+        .comments
+        .find_comments_by_topic(topic_id, pagination.offset(), pagination.limit())
+        .await?;
+    Ok(Page::new(total, &pagination, records))
+}
+
+pub async fn list_user_comments(
+    user_id: u64,
+    pagination: Pagination,
+    repos: &Repositories,
+) -> AppResult<Page<Comment>> {
+    let total = repos.comments.count_comments_by_user(user_id).await?;
+    let records = repos
+        .comments
+        .find_comments_by_user(user_id, pagination.offset(), pagination.limit())
+        .await?;
+    Ok(Page::new(total, &pagination, records))
+}
+
+pub async fn update_comment(
+    pk_id: u64,
+    comment: CommentFront,
+    user_token: &UserToken,
+    repos: &Repositories,
+) -> AppResult<()> {
+    check_comment_owner(pk_id, user_token, repos).await?;
+    if repos.comments.update_comment_content(pk_id, &comment.content).await? {
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::UpdateConflict))
+    }
+}
+
+pub async fn delete_comment(pk_id: u64, user_token: &UserToken, repos: &Repositories) -> AppResult<()> {
+    check_comment_owner(pk_id, user_token, repos).await?;
+    if repos.comments.delete_comment(pk_id).await? {
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::DeleteConflict))
+    }
+}
+
+async fn check_comment_owner(pk_id: u64, user_token: &UserToken, repos: &Repositories) -> AppResult<Comment> {
+    let comment = repos.comments.find_comment_by_id(pk_id).await?;
+    if comment.user_id == user_token.user_id {
+        Ok(comment)
+    } else {
+        Err(AppError::BusinessError(ErrorCode::NotCommentOwner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::{create_topic, error_code, pagination, user_token};
+    use crate::model::user::Role;
+
+    async fn insert_comment(user_id: u64, topic_id: u64, content: &str, repos: &Repositories) -> u64 {
+        let comment = NewComment {
+            user_id,
+            topic_id,
+            parent_id: None,
+            root_id: None,
+            depth: 0,
+            content,
+        };
+        repos.comments.insert_one_comment(comment).await.unwrap()
+    }
+
+    fn front(content: &str) -> CommentFront {
+        CommentFront {
+            parent_id: None,
+            content: String::from(content),
+        }
+    }
+
+    fn contents(page: &Page<Comment>) -> Vec<&str> {
+        page.records.iter().map(|c| c.content.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn list_comments_by_topic_and_user() {
+        let repos = Repositories::memory();
+        let topic_id = create_topic(1, "主题", &repos).await;
+        let other_topic = create_topic(1, "另一个主题", &repos).await;
+        insert_comment(2, topic_id, "第一条", &repos).await;
+        insert_comment(3, topic_id, "第二条", &repos).await;
+        insert_comment(2, other_topic, "第三条", &repos).await;
+
+        let page = list_topic_comments(topic_id, pagination(1, 1), &repos).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(contents(&page), vec!["第一条"]);
+        let page = list_user_comments(2, pagination(1, 10), &repos).await.unwrap();
+        assert_eq!(page.total, 2);
+        // 用户的评论记录新的在前
+        assert_eq!(contents(&page), vec!["第三条", "第一条"]);
+    }
+
+    #[tokio::test]
+    async fn only_owner_can_edit_or_delete() {
+        let repos = Repositories::memory();
+        let topic_id = create_topic(1, "主题", &repos).await;
+        let pk_id = insert_comment(2, topic_id, "评论", &repos).await;
+        // 主题作者和版主也不能修改别人的评论
+        for token in &[user_token(1, Role::User), user_token(3, Role::Moderator)] {
+            let result = update_comment(pk_id, front("修改"), token, &repos).await;
+            assert_eq!(error_code(result), Some(ErrorCode::NotCommentOwner));
+            let result = delete_comment(pk_id, token, &repos).await;
+            assert_eq!(error_code(result), Some(ErrorCode::NotCommentOwner));
+        }
+
+        let owner = user_token(2, Role::User);
+        update_comment(pk_id, front("修改后"), &owner, &repos).await.unwrap();
+        assert_eq!(
+            repos.comments.find_comment_by_id(pk_id).await.unwrap().content,
+            "修改后"
+        );
+        delete_comment(pk_id, &owner, &repos).await.unwrap();
+        let page = list_topic_comments(topic_id, pagination(1, 10), &repos).await.unwrap();
+        assert_eq!(page.total, 0);
+        let result = delete_comment(pk_id, &owner, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::CommentNotFound));
+    }
+
+    /// 按 `create_comment` 的规则回复 `parent_id`，返回新评论的id
+    async fn reply(user_id: u64, topic_id: u64, parent_id: u64, content: &str, repos: &Repositories) -> AppResult<u64> {
+        let parent = repos.comments.find_comment_by_id(parent_id).await?;
+        let mut new_comment = NewComment {
+            user_id,
+            topic_id,
+            parent_id: None,
+            root_id: None,
+            depth: 0,
+            content,
+        };
+        reply_to(&mut new_comment, &parent)?;
+        repos.comments.insert_one_comment(new_comment).await
+    }
+
+    #[tokio::test]
+    async fn reply_depth_is_limited() {
+        let repos = Repositories::memory();
+        let topic_id = create_topic(1, "主题", &repos).await;
+        let root_id = insert_comment(2, topic_id, "楼主", &repos).await;
+        let mut parent_id = root_id;
+        for depth in 1..=MAX_COMMENT_DEPTH {
+            parent_id = reply(2, topic_id, parent_id, "回复", &repos).await.unwrap();
+            let comment = repos.comments.find_comment_by_id(parent_id).await.unwrap();
+            assert_eq!((comment.depth, comment.root_id), (depth, Some(root_id)));
+        }
+        let result = reply(2, topic_id, parent_id, "太深了", &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::ReplyTooDeep));
+    }
+
+    #[tokio::test]
+    async fn reply_must_be_in_same_topic() {
+        let repos = Repositories::memory();
+        let topic_id = create_topic(1, "主题", &repos).await;
+        let other_topic = create_topic(1, "另一个主题", &repos).await;
+        let parent_id = insert_comment(2, topic_id, "评论", &repos).await;
+        let result = reply(2, other_topic, parent_id, "回复", &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::ReplyNotInTopic));
+    }
+
+    #[tokio::test]
+    async fn comment_tree_keeps_deleted_parents_with_replies() {
+        let repos = Repositories::memory();
+        let topic_id = create_topic(1, "主题", &repos).await;
+        let first = insert_comment(2, topic_id, "第一楼", &repos).await;
+        let reply_id = reply(3, topic_id, first, "回复第一楼", &repos).await.unwrap();
+        reply(2, topic_id, reply_id, "再回复", &repos).await.unwrap();
+        let second = insert_comment(3, topic_id, "第二楼", &repos).await;
+        repos.comments.delete_comment(first).await.unwrap();
+        repos.comments.delete_comment(second).await.unwrap();
+
+        let page = list_topic_comment_tree(topic_id, pagination(1, 10), &repos)
+            .await
+            .unwrap();
+        // 第二楼已删除且没有回复，不再显示
+        assert_eq!(page.records.len(), 1);
+        let root = &page.records[0];
+        assert_eq!(root.comment.content, "该评论已删除");
+        assert_eq!(root.replies[0].comment.content, "回复第一楼");
+        assert_eq!(root.replies[0].replies[0].comment.content, "再回复");
+    }
+}
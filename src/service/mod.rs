@@ -1 +1,10 @@
+pub mod admin_service;
+pub mod comment_service;
+pub mod notice_service;
+pub mod search_service;
+pub mod session_service;
+pub mod star_service;
+pub mod tag_service;
+pub mod topic_service;
+pub mod upload_service;
 pub mod user_service;
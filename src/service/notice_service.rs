@@ -0,0 +1,330 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use chrono::Local;
+use tera::Context;
+
+use crate::cache::Cache;
+use crate::common::api::{Page, Pagination};
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::notice_hub::NoticeHub;
+use crate::common::locale::Locale;
+use crate::mail::{MailTemplate, Mailer};
+use crate::model::notice::{LocalizedNotice, NewNotice, NoticePage, NoticeType};
+use crate::model::user::UserToken;
+use crate::repository::Repositories;
+use crate::{AppResult, ShareState};
+
+/// 一条内容中最多通知的@提及人数
+const MAX_MENTIONS: usize = 10;
+
+/// 汇总邮件中最多列出的通知数
+const DIGEST_MAX_NOTICES: u32 = 10;
+
+/// 汇总邮件的发送锁，key 为发送周期的序号
+const DIGEST_LOCK_PREFIX: &str = "notice_digest:";
+
+/// 发送通知并实时推送给在线用户，失败只记录日志，不影响触发通知的操作
+pub async fn send(notice: NewNotice, repos: &Repositories, hub: &NoticeHub) {
+    if notice.notified_user_id == notice.create_user {
+        return;
+    }
+    match repos.notices.insert_one_notice(&notice).await {
+        Ok(pk_id) => {
+            let notice = notice.into_notice(pk_id, Local::now().naive_local());
+            if let Err(e) = hub.publish(&notice).await {
+                error!("推送通知失败，通知id: {}，失败原因: {}", pk_id, e.to_string());
+            }
+        }
+        Err(e) => error!("发送通知失败，通知: {:?}，失败原因: {}", notice, e.to_string()),
+    }
+}
+
+pub async fn notify_topic_comment(
+    topic_id: u64,
+    topic_title: &str,
+    author_id: u64,
+    from: &UserToken,
+    repos: &Repositories,
+    hub: &NoticeHub,
+) {
+    let notice = NewNotice {
+        notice_type: NoticeType::Comment,
+        target_id: topic_id,
+        actor_name: from.uk_username.clone(),
+        target_title: String::from(topic_title),
+        notified_user_id: author_id,
+        create_user: from.user_id,
+    };
+    send(notice, repos, hub).await;
+}
+
+pub async fn notify_comment_reply(
+    topic_id: u64,
+    topic_title: &str,
+    author_id: u64,
+    from: &UserToken,
+    repos: &Repositories,
+    hub: &NoticeHub,
+) {
+    let notice = NewNotice {
+        notice_type: NoticeType::Reply,
+        target_id: topic_id,
+        actor_name: from.uk_username.clone(),
+        target_title: String::from(topic_title),
+        notified_user_id: author_id,
+        create_user: from.user_id,
+    };
+    send(notice, repos, hub).await;
+}
+
+pub async fn notify_follow(user_id: u64, from: &UserToken, repos: &Repositories, hub: &NoticeHub) {
+    let notice = NewNotice {
+        notice_type: NoticeType::Follow,
+        target_id: from.user_id,
+        actor_name: from.uk_username.clone(),
+        target_title: String::new(),
+        notified_user_id: user_id,
+        create_user: from.user_id,
+    };
+    send(notice, repos, hub).await;
+}
+
+/// 内容中 @ 到的用户名，按出现顺序去重后最多取 `MAX_MENTIONS` 个，用户名不区分大小写
+fn mentioned_usernames<'a>(content: &'a str, self_username: &str) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    crate::MENTION_RE
+        .captures_iter(content)
+        .filter_map(|c| c.get(1))
+        .map(|m| m.as_str())
+        .filter(|name| !name.eq_ignore_ascii_case(self_username))
+        .filter(|name| seen.insert(name.to_ascii_lowercase()))
+        .take(MAX_MENTIONS)
+        .collect()
+}
+
+/// 通知内容中 @ 到的用户，`notified` 中的用户已收到其他通知，不再重复通知
+pub async fn notify_mentions(
+    content: &str,
+    topic_id: u64,
+    topic_title: &str,
+    notified: &[u64],
+    from: &UserToken,
+    repos: &Repositories,
+    hub: &NoticeHub,
+) {
+    for username in mentioned_usernames(content, &from.uk_username) {
+        let user = match repos.users.find_user_brief_by_username(username).await {
+            Ok(Some(user)) => user,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("查询被@用户失败，用户名: {}，失败原因: {}", username, e.to_string());
+                continue;
+            }
+        };
+        if notified.contains(&user.pk_id) {
+            continue;
+        }
+        let notice = NewNotice {
+            notice_type: NoticeType::Mention,
+            target_id: topic_id,
+            actor_name: from.uk_username.clone(),
+            target_title: String::from(topic_title),
+            notified_user_id: user.pk_id,
+            create_user: from.user_id,
+        };
+        send(notice, repos, hub).await;
+    }
+}
+
+pub async fn list_notices(
+    user_id: u64,
+    pagination: Pagination,
+    locale: Locale,
+    repos: &Repositories,
+) -> AppResult<NoticePage> {
+    let total = repos.notices.count_notices(user_id).await?;
+    let unread = repos.notices.count_unread_notices(user_id).await?;
+    let records = repos
+        .notices
+        .find_notices(user_id, pagination.offset(), pagination.limit())
+        .await?
+        .into_iter()
+        .map(|n| n.localize(locale))
+        .collect();
+    Ok(NoticePage {
+        unread,
+        page: Page::new(total, &pagination, records),
+    })
+}
+
+pub async fn mark_read(pk_id: u64, user_id: u64, repos: &Repositories) -> AppResult<()> {
+    if repos.notices.mark_notice_viewed(pk_id, user_id).await? {
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::NoticeNotFound))
+    }
+}
+
+pub async fn mark_all_read(user_id: u64, repos: &Repositories) -> AppResult<u64> {
+    repos.notices.mark_all_notices_viewed(user_id).await
+}
+
+/// 启动定时任务，按 `mail.digest_interval_secs` 发送未读通知汇总邮件
+pub fn start_digest_job(state: ShareState) {
+    let interval_secs = state.settings.mail.digest_interval_secs;
+    if interval_secs == 0 {
+        info!("未读通知汇总邮件已关闭");
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = send_digests(interval_secs, &state.repos, &*state.cache, &state.mailer).await {
+                error!("发送未读通知汇总邮件出错，报错信息: {}", e.to_string());
+            }
+        }
+    });
+}
+
+/// 给上一个周期内收到新通知且仍有未读通知的用户发送汇总邮件
+///
+/// 多个实例部署时每个周期只有先拿到锁的实例发送，实例重启后同一个周期内也不会重复发送。
+async fn send_digests(interval_secs: u64, repos: &Repositories, cache: &dyn Cache, mailer: &Mailer) -> AppResult<()> {
+    let now = Local::now();
+    let period = now.timestamp() as u64 / interval_secs;
+    let lock_key = format!("{}{}", DIGEST_LOCK_PREFIX, period);
+    if cache.incr(&lock_key, Duration::from_secs(interval_secs)).await? > 1 {
+        return Ok(());
+    }
+    let since = now.naive_local() - chrono::Duration::seconds(interval_secs as i64);
+    let user_ids = repos.notices.find_unread_notified_users(since).await?;
+    let mut sent = 0;
+    for user_id in user_ids {
+        match send_digest(user_id, repos, mailer).await {
+            Ok(()) => sent += 1,
+            Err(e) => error!("给用户 {} 发送未读通知汇总邮件失败: {}", user_id, e.to_string()),
+        }
+    }
+    info!("未读通知汇总邮件已放入发件箱，共 {} 封", sent);
+    Ok(())
+}
+
+async fn send_digest(user_id: u64, repos: &Repositories, mailer: &Mailer) -> AppResult<()> {
+    let user = repos.users.find_user_by_id(user_id).await?;
+    let unread = repos.notices.count_unread_notices(user_id).await?;
+    let locale = user.locale();
+    // 未读的通知排在前面
+    let notices: Vec<LocalizedNotice> = repos
+        .notices
+        .find_notices(user_id, 0, DIGEST_MAX_NOTICES)
+        .await?
+        .into_iter()
+        .filter(|n| !n.viewed)
+        .map(|n| n.localize(locale))
+        .collect();
+    if notices.is_empty() {
+        return Ok(());
+    }
+    let mut context = Context::new();
+    context.insert("username", &user.uk_username);
+    context.insert("unread", &unread);
+    context.insert("notices", &notices);
+    mailer
+        .send_template(MailTemplate::NoticeDigest, locale, &user.uk_email, &context)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::{error_code, pagination};
+
+    fn new_notice(notice_type: NoticeType, target_title: &str) -> NewNotice {
+        NewNotice {
+            notice_type,
+            target_id: 1,
+            actor_name: String::from("alice"),
+            target_title: String::from(target_title),
+            notified_user_id: 1,
+            create_user: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn list_notices_in_request_locale() {
+        let repos = Repositories::memory();
+        repos
+            .notices
+            .insert_one_notice(&new_notice(NoticeType::Comment, "Rust"))
+            .await
+            .unwrap();
+        let en = list_notices(1, pagination(1, 10), Locale::En, &repos).await.unwrap();
+        assert_eq!(en.page.records[0].content, "alice commented on your topic \"Rust\"");
+        let zh = list_notices(1, pagination(1, 10), Locale::ZhCn, &repos).await.unwrap();
+        assert_eq!(zh.page.records[0].content, "alice 评论了你的主题《Rust》");
+    }
+
+    #[tokio::test]
+    async fn mark_read_only_own_notice() {
+        let repos = Repositories::memory();
+        let notice = new_notice(NoticeType::Reply, "Rust");
+        let pk_id = repos.notices.insert_one_notice(&notice).await.unwrap();
+        assert_eq!(
+            error_code(mark_read(pk_id, 2, &repos).await),
+            Some(ErrorCode::NoticeNotFound)
+        );
+        assert_eq!(
+            error_code(mark_read(pk_id + 1, 1, &repos).await),
+            Some(ErrorCode::NoticeNotFound)
+        );
+        mark_read(pk_id, 1, &repos).await.unwrap();
+        let page = list_notices(1, pagination(1, 10), Locale::En, &repos).await.unwrap();
+        assert_eq!((page.unread, page.page.total), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn mark_all_read_clears_unread() {
+        let repos = Repositories::memory();
+        for title in &["Rust", "Axum", "Tokio"] {
+            let notice = new_notice(NoticeType::Comment, title);
+            repos.notices.insert_one_notice(&notice).await.unwrap();
+        }
+        let mut other = new_notice(NoticeType::Comment, "Other");
+        other.notified_user_id = 3;
+        repos.notices.insert_one_notice(&other).await.unwrap();
+
+        let page = list_notices(1, pagination(1, 2), Locale::En, &repos).await.unwrap();
+        assert_eq!((page.unread, page.page.total, page.page.records.len()), (3, 3, 2));
+        assert_eq!(mark_all_read(1, &repos).await.unwrap(), 3);
+        let page = list_notices(1, pagination(1, 2), Locale::En, &repos).await.unwrap();
+        assert_eq!(page.unread, 0);
+        // 其他用户的通知不受影响
+        let page = list_notices(3, pagination(1, 10), Locale::En, &repos).await.unwrap();
+        assert_eq!(page.unread, 1);
+    }
+
+    #[test]
+    fn follow_notice_content_has_no_title() {
+        let notice = new_notice(NoticeType::Follow, "").into_notice(1, Local::now().naive_local());
+        assert_eq!(notice.content(Locale::En), "alice followed you");
+        assert_eq!(notice.content(Locale::ZhCn), "alice 关注了你");
+    }
+
+    #[test]
+    fn mentioned_usernames_dedup_before_limit() {
+        let repeated = "@alice ".repeat(MAX_MENTIONS + 1);
+        let content = format!("{}@Alice @bob", repeated);
+        assert_eq!(mentioned_usernames(&content, "carol"), vec!["alice", "bob"]);
+    }
+
+    #[test]
+    fn mentioned_usernames_skip_self_and_limit() {
+        let content: String = (0..MAX_MENTIONS + 2).map(|i| format!("@user{} ", i)).collect();
+        let content = format!("@Carol {}", content);
+        let usernames = mentioned_usernames(&content, "carol");
+        assert_eq!(usernames.len(), MAX_MENTIONS);
+        assert_eq!(usernames[0], "user0");
+    }
+}
@@ -0,0 +1,356 @@
+use crate::common::api::{Page, Pagination};
+use crate::common::err::{AppError, ErrorCode};
+use crate::model::search::{SearchHit, SearchQuery};
+use crate::repository::Repositories;
+use crate::AppResult;
+
+/// 搜索关键词最多包含的字符数
+const MAX_KEYWORD_LENGTH: usize = 50;
+
+/// 与 MySQL ngram 解析器默认的 ngram_token_size 保持一致
+const NGRAM_SIZE: usize = 2;
+
+/// 摘要最多包含的字符数
+const SNIPPET_LENGTH: usize = 120;
+
+/// 摘要中第一个命中的关键词之前保留的字符数
+const SNIPPET_CONTEXT: usize = 30;
+
+pub async fn search(query: SearchQuery, pagination: Pagination, repos: &Repositories) -> AppResult<Page<SearchHit>> {
+    let keyword = query.q.trim();
+    if keyword.is_empty() {
+        return Err(AppError::BusinessError(ErrorCode::SearchKeywordEmpty));
+    }
+    if keyword.chars().count() > MAX_KEYWORD_LENGTH {
+        return Err(AppError::BusinessError(ErrorCode::SearchKeywordTooLong));
+    }
+    let total = repos.search.count_hits(keyword, query.tag_id, query.author_id).await?;
+    if total == 0 {
+        return Ok(Page::new(total, &pagination, Vec::new()));
+    }
+    let rows = repos
+        .search
+        .find_hits(
+            keyword,
+            query.tag_id,
+            query.author_id,
+            pagination.offset(),
+            pagination.limit(),
+        )
+        .await?;
+    let terms = search_terms(keyword);
+    let records = rows
+        .into_iter()
+        .map(|row| SearchHit {
+            title: highlight_all(&row.title, &terms),
+            snippet: snippet(&row.content, &terms),
+            hit_type: row.hit_type,
+            topic_id: row.topic_id,
+            comment_id: row.comment_id,
+            user_id: row.user_id,
+            score: row.score,
+            create_time: row.create_time,
+        })
+        .collect();
+    Ok(Page::new(total, &pagination, records))
+}
+
+// 按空白拆分关键词，ngram 检索时整个词不一定出现在内容中，所以同时用词的各个 ngram 高亮，
+// 较长的词排在前面优先匹配
+fn search_terms(keyword: &str) -> Vec<Vec<char>> {
+    let mut terms = Vec::new();
+    for word in keyword.split_whitespace() {
+        let chars: Vec<char> = word.chars().map(fold_case).collect();
+        if chars.len() > NGRAM_SIZE {
+            terms.extend(chars.windows(NGRAM_SIZE).map(<[char]>::to_vec));
+        }
+        terms.push(chars);
+    }
+    terms.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    terms.dedup();
+    terms
+}
+
+// 逐个字符转小写，保证转换后的字符下标与原文一致
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// 找出文本中命中关键词的区间（字符下标），相邻的区间合并为一个
+fn find_matches(text: &[char], terms: &[Vec<char>]) -> Vec<(usize, usize)> {
+    let folded: Vec<char> = text.iter().copied().map(fold_case).collect();
+    let mut matches: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < folded.len() {
+        match terms.iter().find(|term| folded[i..].starts_with(term)) {
+            Some(term) => {
+                let end = i + term.len();
+                match matches.last_mut() {
+                    Some(last) if last.1 == i => last.1 = end,
+                    _ => matches.push((i, end)),
+                }
+                i = end;
+            }
+            None => i += 1,
+        }
+    }
+    matches
+}
+
+fn highlight_all(text: &str, terms: &[Vec<char>]) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let matches = find_matches(&chars, terms);
+    highlight(&chars, &matches, 0, chars.len())
+}
+
+// 截取第一个命中的关键词附近的内容作为摘要
+fn snippet(content: &str, terms: &[Vec<char>]) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let matches = find_matches(&chars, terms);
+    let start = matches.first().map_or(0, |m| m.0.saturating_sub(SNIPPET_CONTEXT));
+    let end = (start + SNIPPET_LENGTH).min(chars.len());
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str("...");
+    }
+    snippet.push_str(&highlight(&chars, &matches, start, end));
+    if end < chars.len() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+// 转义 [start, end) 范围内的文本，并用 <em> 包裹命中的关键词
+fn highlight(chars: &[char], matches: &[(usize, usize)], start: usize, end: usize) -> String {
+    let mut text = String::new();
+    let mut pos = start;
+    for &(m_start, m_end) in matches {
+        if m_end <= start {
+            continue;
+        }
+        if m_start >= end {
+            break;
+        }
+        let (m_start, m_end) = (m_start.max(start), m_end.min(end));
+        escape_into(&mut text, &chars[pos..m_start]);
+        text.push_str("<em>");
+        escape_into(&mut text, &chars[m_start..m_end]);
+        text.push_str("</em>");
+        pos = m_end;
+    }
+    escape_into(&mut text, &chars[pos..end]);
+    text
+}
+
+// 摘要中的换行等空白统一替换为空格
+fn escape_into(text: &mut String, chars: &[char]) {
+    for &c in chars {
+        match c {
+            '&' => text.push_str("&amp;"),
+            '<' => text.push_str("&lt;"),
+            '>' => text.push_str("&gt;"),
+            '"' => text.push_str("&quot;"),
+            '\'' => text.push_str("&#39;"),
+            c if c.is_whitespace() => text.push(' '),
+            c => text.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::error_code;
+    use crate::model::comment::NewComment;
+    use crate::model::tag::TagFront;
+    use crate::model::topic::TopicFront;
+
+    fn terms(keyword: &str) -> Vec<String> {
+        search_terms(keyword)
+            .into_iter()
+            .map(|t| t.into_iter().collect())
+            .collect()
+    }
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    /// 去掉高亮标签和省略号后的摘要正文
+    fn plain(snippet: &str) -> String {
+        snippet.replace("<em>", "").replace("</em>", "").replace("...", "")
+    }
+
+    #[test]
+    fn search_terms_split_words_and_ngrams() {
+        assert_eq!(terms("异步编程"), vec!["异步编程", "异步", "步编", "编程"]);
+        // 长度不超过 ngram 的词不再拆分，重复的词只保留一个
+        assert_eq!(terms("Rust 编程 rust"), vec!["rust", "ru", "st", "us", "编程"]);
+        assert!(terms("  ").is_empty());
+    }
+
+    #[test]
+    fn find_matches_chinese_and_case_insensitive() {
+        let text = chars("学习RUST异步编程");
+        assert_eq!(find_matches(&text, &search_terms("rust")), vec![(2, 6)]);
+        assert_eq!(find_matches(&text, &search_terms("异步编程")), vec![(6, 10)]);
+        // 只命中部分 ngram 时按 ngram 高亮
+        assert_eq!(find_matches(&text, &search_terms("编程语言")), vec![(8, 10)]);
+        assert!(find_matches(&text, &search_terms("python")).is_empty());
+    }
+
+    #[test]
+    fn find_matches_merge_adjacent_and_skip_overlap() {
+        assert_eq!(find_matches(&chars("abcd"), &search_terms("ab cd")), vec![(0, 4)]);
+        // 重叠的关键词只匹配先出现的一个，不会产生嵌套的高亮
+        assert_eq!(find_matches(&chars("编程序"), &search_terms("编程 程序")), vec![(0, 2)]);
+        assert_eq!(highlight_all("编程序", &search_terms("编程 程序")), "<em>编程</em>序");
+    }
+
+    #[test]
+    fn highlight_keeps_original_case_and_escapes() {
+        let terms = search_terms("rust");
+        assert_eq!(highlight_all("Learn RUST", &terms), "Learn <em>RUST</em>");
+        assert_eq!(
+            highlight_all("<b>Rust</b> & \"tera\"", &terms),
+            "&lt;b&gt;<em>Rust</em>&lt;/b&gt; &amp; &quot;tera&quot;"
+        );
+    }
+
+    #[test]
+    fn highlight_clamps_matches_to_range() {
+        let text = chars("abcdef");
+        assert_eq!(highlight(&text, &[(2, 6)], 0, 4), "ab<em>cd</em>");
+        assert_eq!(highlight(&text, &[(0, 3)], 2, 6), "<em>c</em>def");
+        assert_eq!(highlight(&text, &[(0, 1), (5, 6)], 2, 4), "cd");
+    }
+
+    #[test]
+    fn snippet_around_first_match_on_char_boundaries() {
+        let content = format!("{}关键词{}", "前".repeat(100), "后".repeat(200));
+        let snippet = snippet(&content, &search_terms("关键词"));
+        assert!(snippet.starts_with("..."));
+        assert!(snippet.ends_with("..."));
+        assert!(snippet.contains("<em>关键词</em>"));
+        let plain = plain(&snippet);
+        assert_eq!(plain.chars().count(), SNIPPET_LENGTH);
+        assert!(plain.starts_with(&"前".repeat(SNIPPET_CONTEXT)));
+    }
+
+    #[test]
+    fn snippet_without_match_or_short_content() {
+        let content = "内容".repeat(SNIPPET_LENGTH);
+        let snippet = snippet(&content, &search_terms("关键词"));
+        assert!(!snippet.starts_with("..."));
+        assert_eq!(plain(&snippet).chars().count(), SNIPPET_LENGTH);
+        assert_eq!(
+            super::snippet("第一行\n第二行", &search_terms("第二")),
+            "第一行 <em>第二</em>行"
+        );
+    }
+
+    fn pagination() -> Pagination {
+        Pagination {
+            current_page: 1,
+            page_size: 10,
+        }
+    }
+
+    fn query(q: &str, tag_id: Option<u64>, author_id: Option<u64>) -> SearchQuery {
+        SearchQuery {
+            q: String::from(q),
+            tag_id,
+            author_id,
+        }
+    }
+
+    async fn insert_topic(repos: &Repositories, user_id: u64, title: &str, content: &str, tags: Vec<u64>) -> u64 {
+        let topic = TopicFront {
+            user_id: Some(user_id),
+            title: String::from(title),
+            content: String::from(content),
+            tags,
+        };
+        repos.topics.insert_one_topic(&topic).await.unwrap()
+    }
+
+    async fn insert_tag(repos: &Repositories, name: &str, parent_tag: u64) -> u64 {
+        let tag = TagFront {
+            tag_name: String::from(name),
+            uk_logo: None,
+            parent_tag: None,
+        };
+        repos.tags.insert_one_tag(&tag, parent_tag, 1).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn search_keyword_length() {
+        let repos = Repositories::memory();
+        let result = search(query("  ", None, None), pagination(), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::SearchKeywordEmpty));
+        // 按字符数而不是字节数限制
+        let keyword = "搜".repeat(MAX_KEYWORD_LENGTH);
+        assert!(search(query(&keyword, None, None), pagination(), &repos).await.is_ok());
+        let keyword = "搜".repeat(MAX_KEYWORD_LENGTH + 1);
+        let result = search(query(&keyword, None, None), pagination(), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::SearchKeywordTooLong));
+    }
+
+    #[tokio::test]
+    async fn search_topics_and_comments() {
+        let repos = Repositories::memory();
+        let topic_id = insert_topic(&repos, 1, "Rust 异步编程", "tokio 入门", Vec::new()).await;
+        insert_topic(&repos, 2, "Go 并发", "goroutine", Vec::new()).await;
+        let comment = NewComment {
+            user_id: 2,
+            topic_id,
+            parent_id: None,
+            root_id: None,
+            depth: 0,
+            content: "异步编程推荐 tokio",
+        };
+        let comment_id = repos.comments.insert_one_comment(comment).await.unwrap();
+
+        let page = search(query("tokio", None, None), pagination(), &repos).await.unwrap();
+        assert_eq!(page.total, 2);
+        let hit_types: Vec<&str> = page.records.iter().map(|h| h.hit_type.as_str()).collect();
+        assert!(hit_types.contains(&"topic") && hit_types.contains(&"comment"));
+        let comment_hit = page.records.iter().find(|h| h.hit_type == "comment").unwrap();
+        assert_eq!(comment_hit.comment_id, Some(comment_id));
+        assert_eq!(comment_hit.topic_id, topic_id);
+        assert_eq!(comment_hit.snippet, "异步编程推荐 <em>tokio</em>");
+
+        let page = search(query("tokio", None, Some(2)), pagination(), &repos)
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.records[0].hit_type, "comment");
+
+        repos.comments.delete_comment(comment_id).await.unwrap();
+        let page = search(query("tokio", None, None), pagination(), &repos).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.records[0].title, "Rust 异步编程");
+    }
+
+    #[tokio::test]
+    async fn search_by_tag_includes_child_tags() {
+        let repos = Repositories::memory();
+        let parent = insert_tag(&repos, "编程语言", 0).await;
+        let child = insert_tag(&repos, "Rust", parent).await;
+        let other = insert_tag(&repos, "生活", 0).await;
+        insert_topic(&repos, 1, "Rust 入门", "所有权", vec![child]).await;
+        insert_topic(&repos, 1, "入门跑步", "每天五公里", vec![other]).await;
+
+        let page = search(query("入门", Some(parent), None), pagination(), &repos)
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.records[0].title, "Rust <em>入门</em>");
+        let page = search(query("入门", Some(other), None), pagination(), &repos)
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+        let page = search(query("入门", None, None), pagination(), &repos).await.unwrap();
+        assert_eq!(page.total, 2);
+    }
+}
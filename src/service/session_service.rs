@@ -0,0 +1,451 @@
+use std::time::Duration;
+
+use chrono::Local;
+use uuid::Uuid;
+
+use crate::cache::{Cache, CacheExt};
+use crate::common::constant::{
+    ACCESS_TOKEN_EXPIRE_SECS, REFRESH_TOKEN_EXPIRE_SECS, REFRESH_TOKEN_FOREVER_EXPIRE_SECS, STREAM_TICKET_EXPIRE_SECS,
+};
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::settings::RateLimitSettings;
+use crate::common::util;
+use crate::model::notice::StreamTicket;
+use crate::model::user::{RefreshSession, TokenPair, User, UserToken};
+use crate::repository::Repositories;
+use crate::AppResult;
+
+/// 刷新令牌，key 为会话ID
+const REFRESH_SESSION_PREFIX: &str = "refresh_session:";
+
+/// 已退出登录的会话，保留到该会话签发的访问令牌全部过期为止
+const REVOKED_SESSION_PREFIX: &str = "revoked_session:";
+
+/// 用户 token 版本的缓存，以数据库中的版本为准
+const TOKEN_VERSION_PREFIX: &str = "token_version:";
+
+const TOKEN_VERSION_CACHE_SECS: u64 = 60 * 60 * 24;
+
+/// 连续登录失败次数，key 为邮箱
+const LOGIN_FAILURE_PREFIX: &str = "login_failure:";
+
+/// 因连续登录失败被锁定的账号，key 为邮箱
+const LOGIN_LOCK_PREFIX: &str = "login_lock:";
+
+/// 通知推送票据，key 为票据
+const STREAM_TICKET_PREFIX: &str = "stream_ticket:";
+
+/// 登录成功后创建会话，签发访问令牌和刷新令牌
+pub async fn create_session(user: &User, forever: bool, cache: &dyn Cache) -> AppResult<TokenPair> {
+    let session_id = Uuid::new_v4().to_simple().to_string();
+    let session = RefreshSession {
+        user_id: user.pk_id,
+        secret: new_secret(),
+        version: user.token_version,
+        forever,
+    };
+    issue_tokens(user, &session_id, &session, cache).await
+}
+
+/// 使用刷新令牌换取新的令牌，旧的刷新令牌随即失效
+///
+/// 先用 `take` 原子地取出并删除会话再写回新的密钥，同一个刷新令牌并发请求时只有一个能成功，
+/// 其余请求读不到会话，返回会话已过期。
+pub async fn refresh_session(refresh_token: &str, repos: &Repositories, cache: &dyn Cache) -> AppResult<TokenPair> {
+    let expired = || AppError::BusinessError(ErrorCode::SessionExpired);
+    let mut parts = refresh_token.splitn(2, '.');
+    let (session_id, secret) = match (parts.next(), parts.next()) {
+        (Some(session_id), Some(secret)) => (session_id, secret),
+        _ => return Err(expired()),
+    };
+    let key = format!("{}{}", REFRESH_SESSION_PREFIX, session_id);
+    let mut session = cache.take::<RefreshSession>(&key).await?.ok_or_else(expired)?;
+    if session.secret != secret {
+        // 已轮换掉的刷新令牌被再次使用，说明令牌可能已泄露，直接注销整个会话
+        warn!(
+            "用户 {} 的刷新令牌被重复使用，注销会话: {}",
+            session.user_id, session_id
+        );
+        revoke_session(session_id, cache).await?;
+        return Err(expired());
+    }
+    let user = repos.users.find_user_by_id(session.user_id).await?;
+    if user.token_version != session.version {
+        return Err(expired());
+    }
+    session.secret = new_secret();
+    issue_tokens(&user, session_id, &session, cache).await
+}
+
+/// 账号被锁定时返回剩余的锁定时间
+pub async fn check_login_lock(email: &str, cache: &dyn Cache) -> AppResult<()> {
+    let key = format!("{}{}", LOGIN_LOCK_PREFIX, email.to_lowercase());
+    match cache.ttl(&key).await? {
+        Some(ttl) => Err(AppError::TooManyRequests(ttl.as_secs().max(1))),
+        None => Ok(()),
+    }
+}
+
+/// 记录一次登录失败，连续失败次数达到上限时锁定账号
+pub async fn record_login_failure(email: &str, settings: &RateLimitSettings, cache: &dyn Cache) -> AppResult<()> {
+    let email = email.to_lowercase();
+    let failure_key = format!("{}{}", LOGIN_FAILURE_PREFIX, email);
+    let lockout = Duration::from_secs(settings.login_lockout_secs);
+    let failures = cache.incr(&failure_key, lockout).await?;
+    if failures < settings.login_max_failures as i64 {
+        return Ok(());
+    }
+    let lock_key = format!("{}{}", LOGIN_LOCK_PREFIX, email);
+    cache.set(&lock_key, &1, Some(lockout)).await?;
+    cache.del(&failure_key).await?;
+    warn!(
+        "账号 {} 连续登录失败 {} 次，锁定 {} 秒",
+        email, failures, settings.login_lockout_secs
+    );
+    Err(AppError::TooManyRequests(settings.login_lockout_secs))
+}
+
+/// 登录成功后清除失败次数
+pub async fn clear_login_failures(email: &str, cache: &dyn Cache) -> AppResult<()> {
+    cache
+        .del(&format!("{}{}", LOGIN_FAILURE_PREFIX, email.to_lowercase()))
+        .await?;
+    Ok(())
+}
+
+/// 退出当前会话
+pub async fn logout(user_token: &UserToken, cache: &dyn Cache) -> AppResult<()> {
+    revoke_session(&user_token.sid, cache).await
+}
+
+/// 增加用户的 token 版本，用户已签发的所有访问令牌和刷新令牌全部失效
+pub async fn revoke_all_sessions(user_id: u64, repos: &Repositories, cache: &dyn Cache) -> AppResult<()> {
+    if !repos.users.increase_token_version(user_id).await? {
+        return Err(AppError::BusinessError(ErrorCode::UserNotFound));
+    }
+    cache.del(&format!("{}{}", TOKEN_VERSION_PREFIX, user_id)).await?;
+    info!("用户 {} 的所有会话已注销", user_id);
+    Ok(())
+}
+
+/// 校验访问令牌，令牌所属会话已退出或 token 版本已过期时返回 `None`
+pub async fn verify_access_token(token: &str, repos: &Repositories, cache: &dyn Cache) -> Option<UserToken> {
+    let user_token = util::token_decode(token).await?;
+    match check_session(&user_token, repos, cache).await {
+        Ok(true) => Some(user_token),
+        Ok(false) => None,
+        Err(e) => {
+            error!("校验登录会话出错，报错信息: {}", e.to_string());
+            None
+        }
+    }
+}
+
+/// 为已登录用户签发通知推送票据
+///
+/// 浏览器的 EventSource 无法设置请求头，先通过带 TOKEN 的请求换取短期票据，再用票据建立推送连接，
+/// 避免访问令牌出现在 URL 中被记录到日志或浏览器历史里。
+pub async fn issue_stream_ticket(user_token: &UserToken, cache: &dyn Cache) -> AppResult<StreamTicket> {
+    let ticket = new_secret();
+    let ttl = Duration::from_secs(STREAM_TICKET_EXPIRE_SECS as u64);
+    cache
+        .set(&format!("{}{}", STREAM_TICKET_PREFIX, ticket), user_token, Some(ttl))
+        .await?;
+    Ok(StreamTicket {
+        ticket,
+        expires_in: STREAM_TICKET_EXPIRE_SECS,
+    })
+}
+
+/// 使用通知推送票据，票据取出后立即删除，签发后会话已退出的同样视为无效
+pub async fn consume_stream_ticket(ticket: &str, repos: &Repositories, cache: &dyn Cache) -> AppResult<UserToken> {
+    let invalid = || AppError::BusinessError(ErrorCode::TokenInvalid);
+    let key = format!("{}{}", STREAM_TICKET_PREFIX, ticket);
+    let user_token = cache.take::<UserToken>(&key).await?.ok_or_else(invalid)?;
+    if !check_session(&user_token, repos, cache).await? {
+        return Err(invalid());
+    }
+    Ok(user_token)
+}
+
+async fn check_session(user_token: &UserToken, repos: &Repositories, cache: &dyn Cache) -> AppResult<bool> {
+    if cache
+        .exists(&format!("{}{}", REVOKED_SESSION_PREFIX, user_token.sid))
+        .await?
+    {
+        return Ok(false);
+    }
+    let version_key = format!("{}{}", TOKEN_VERSION_PREFIX, user_token.user_id);
+    let version = match cache.get::<u32>(&version_key).await? {
+        Some(version) => version,
+        None => {
+            let version = repos.users.find_token_version(user_token.user_id).await?;
+            let ttl = Duration::from_secs(TOKEN_VERSION_CACHE_SECS);
+            cache.set(&version_key, &version, Some(ttl)).await?;
+            version
+        }
+    };
+    Ok(version == user_token.ver)
+}
+
+async fn issue_tokens(
+    user: &User,
+    session_id: &str,
+    session: &RefreshSession,
+    cache: &dyn Cache,
+) -> AppResult<TokenPair> {
+    let exp = (Local::now().timestamp() + ACCESS_TOKEN_EXPIRE_SECS as i64) as usize;
+    let access_token = util::token_encode(&UserToken::new(user, session_id.to_string(), exp)).await?;
+    let expired_time = if session.forever {
+        REFRESH_TOKEN_FOREVER_EXPIRE_SECS
+    } else {
+        REFRESH_TOKEN_EXPIRE_SECS
+    };
+    let key = format!("{}{}", REFRESH_SESSION_PREFIX, session_id);
+    cache
+        .set(&key, session, Some(Duration::from_secs(expired_time as u64)))
+        .await?;
+    Ok(TokenPair {
+        access_token,
+        refresh_token: format!("{}.{}", session_id, session.secret),
+        expires_in: ACCESS_TOKEN_EXPIRE_SECS,
+    })
+}
+
+async fn revoke_session(session_id: &str, cache: &dyn Cache) -> AppResult<()> {
+    if session_id.is_empty() {
+        return Ok(());
+    }
+    cache.del(&format!("{}{}", REFRESH_SESSION_PREFIX, session_id)).await?;
+    let ttl = Duration::from_secs(ACCESS_TOKEN_EXPIRE_SECS as u64);
+    cache
+        .set(&format!("{}{}", REVOKED_SESSION_PREFIX, session_id), &1, Some(ttl))
+        .await?;
+    Ok(())
+}
+
+fn new_secret() -> String {
+    Uuid::new_v4().to_simple().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::MemoryCache;
+    use crate::common::jwt;
+    use crate::common::test_util::{error_code, rate_limit_settings, EMAIL};
+
+    async fn create_user(repos: &Repositories) -> User {
+        crate::common::test_util::create_user("tester", EMAIL, repos).await
+    }
+
+    fn is_expired<T>(result: AppResult<T>) -> bool {
+        matches!(result, Err(AppError::BusinessError(ErrorCode::SessionExpired)))
+    }
+
+    #[tokio::test]
+    async fn login_locked_after_max_failures() {
+        let cache = MemoryCache::new();
+        let settings = rate_limit_settings();
+        for _ in 0..settings.login_max_failures - 1 {
+            record_login_failure(EMAIL, &settings, &cache).await.unwrap();
+            assert!(check_login_lock(EMAIL, &cache).await.is_ok());
+        }
+        // 达到上限的那一次失败直接返回锁定
+        assert!(matches!(
+            record_login_failure(EMAIL, &settings, &cache).await,
+            Err(AppError::TooManyRequests(secs)) if secs == settings.login_lockout_secs
+        ));
+        // 邮箱不区分大小写
+        assert!(matches!(
+            check_login_lock(&EMAIL.to_uppercase(), &cache).await,
+            Err(AppError::TooManyRequests(secs)) if secs > 0 && secs <= settings.login_lockout_secs
+        ));
+    }
+
+    #[tokio::test]
+    async fn clear_login_failures_resets_count() {
+        let cache = MemoryCache::new();
+        let settings = rate_limit_settings();
+        for _ in 0..settings.login_max_failures - 1 {
+            record_login_failure(EMAIL, &settings, &cache).await.unwrap();
+        }
+        clear_login_failures(EMAIL, &cache).await.unwrap();
+        record_login_failure(EMAIL, &settings, &cache).await.unwrap();
+        assert!(check_login_lock(EMAIL, &cache).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn refresh_rotates_token() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let first = create_session(&user, false, &cache).await.unwrap();
+        let second = refresh_session(&first.refresh_token, &repos, &cache).await.unwrap();
+        assert_ne!(first.refresh_token, second.refresh_token);
+        // 会话ID不变，只轮换密钥
+        assert_eq!(
+            first.refresh_token.split('.').next(),
+            second.refresh_token.split('.').next()
+        );
+        let third = refresh_session(&second.refresh_token, &repos, &cache).await.unwrap();
+        assert_ne!(second.refresh_token, third.refresh_token);
+    }
+
+    #[tokio::test]
+    async fn reused_refresh_token_revokes_session() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let first = create_session(&user, false, &cache).await.unwrap();
+        let second = refresh_session(&first.refresh_token, &repos, &cache).await.unwrap();
+        assert!(is_expired(refresh_session(&first.refresh_token, &repos, &cache).await));
+        // 旧令牌被重复使用后，最新的令牌同样失效
+        assert!(is_expired(refresh_session(&second.refresh_token, &repos, &cache).await));
+    }
+
+    #[tokio::test]
+    async fn concurrent_refresh_only_one_succeeds() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let pair = create_session(&user, false, &cache).await.unwrap();
+        let (a, b) = tokio::join!(
+            refresh_session(&pair.refresh_token, &repos, &cache),
+            refresh_session(&pair.refresh_token, &repos, &cache)
+        );
+        assert_eq!(a.is_ok() as u8 + b.is_ok() as u8, 1);
+    }
+
+    #[tokio::test]
+    async fn revoke_all_sessions_invalidates_refresh_token() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let pair = create_session(&user, true, &cache).await.unwrap();
+        revoke_all_sessions(user.pk_id, &repos, &cache).await.unwrap();
+        assert!(is_expired(refresh_session(&pair.refresh_token, &repos, &cache).await));
+    }
+
+    fn session_id(pair: &TokenPair) -> &str {
+        pair.refresh_token.split('.').next().unwrap()
+    }
+
+    #[tokio::test]
+    async fn access_token_carries_session_and_version() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let pair = create_session(&user, false, &cache).await.unwrap();
+        let user_token = util::token_decode(&pair.access_token).await.unwrap();
+        assert_eq!(user_token.user_id, user.pk_id);
+        assert_eq!(user_token.sid, session_id(&pair));
+        assert_eq!(user_token.ver, user.token_version);
+        assert_eq!(pair.expires_in, ACCESS_TOKEN_EXPIRE_SECS);
+    }
+
+    #[tokio::test]
+    async fn forever_session_lives_longer() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let session_key = |pair: TokenPair| format!("{}{}", REFRESH_SESSION_PREFIX, session_id(&pair));
+        let key = session_key(create_session(&user, false, &cache).await.unwrap());
+        let ttl_secs = cache.ttl(&key).await.unwrap().unwrap().as_secs();
+        assert!(ttl_secs <= REFRESH_TOKEN_EXPIRE_SECS as u64);
+        let key = session_key(create_session(&user, true, &cache).await.unwrap());
+        let ttl_secs = cache.ttl(&key).await.unwrap().unwrap().as_secs();
+        assert!(ttl_secs > REFRESH_TOKEN_EXPIRE_SECS as u64);
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_current_session() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let pair = create_session(&user, false, &cache).await.unwrap();
+        let other = create_session(&user, false, &cache).await.unwrap();
+        let user_token = util::token_decode(&pair.access_token).await.unwrap();
+        logout(&user_token, &cache).await.unwrap();
+        assert!(is_expired(refresh_session(&pair.refresh_token, &repos, &cache).await));
+        let revoked_key = format!("{}{}", REVOKED_SESSION_PREFIX, user_token.sid);
+        assert!(cache.exists(&revoked_key).await.unwrap());
+        // 其他设备上的会话不受影响
+        assert!(refresh_session(&other.refresh_token, &repos, &cache).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn malformed_refresh_token_is_expired() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        for token in &["", "no-dot", "unknown.secret"] {
+            assert!(is_expired(refresh_session(token, &repos, &cache).await), "{}", token);
+        }
+    }
+
+    #[tokio::test]
+    async fn access_token_rejected_after_revocation() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let first = create_session(&user, false, &cache).await.unwrap();
+        let second = create_session(&user, false, &cache).await.unwrap();
+        assert!(verify_access_token(&first.access_token, &repos, &cache).await.is_some());
+        assert!(verify_access_token("not-a-jwt", &repos, &cache).await.is_none());
+
+        let user_token = util::token_decode(&first.access_token).await.unwrap();
+        logout(&user_token, &cache).await.unwrap();
+        assert!(verify_access_token(&first.access_token, &repos, &cache).await.is_none());
+        assert!(
+            verify_access_token(&second.access_token, &repos, &cache)
+                .await
+                .is_some()
+        );
+
+        // token 版本增加后，其他会话签发的访问令牌在过期前同样失效
+        revoke_all_sessions(user.pk_id, &repos, &cache).await.unwrap();
+        assert!(
+            verify_access_token(&second.access_token, &repos, &cache)
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_ticket_is_single_use() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let pair = create_session(&user, false, &cache).await.unwrap();
+        let user_token = util::token_decode(&pair.access_token).await.unwrap();
+        let ticket = issue_stream_ticket(&user_token, &cache).await.unwrap();
+        assert_eq!(ticket.expires_in, STREAM_TICKET_EXPIRE_SECS);
+        let consumed = consume_stream_ticket(&ticket.ticket, &repos, &cache).await.unwrap();
+        assert_eq!(consumed.user_id, user.pk_id);
+        assert_eq!(consumed.sid, user_token.sid);
+        let result = consume_stream_ticket(&ticket.ticket, &repos, &cache).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TokenInvalid));
+        let result = consume_stream_ticket("unknown", &repos, &cache).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TokenInvalid));
+    }
+
+    #[tokio::test]
+    async fn stream_ticket_invalid_after_logout() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let user = create_user(&repos).await;
+        let pair = create_session(&user, false, &cache).await.unwrap();
+        let user_token = util::token_decode(&pair.access_token).await.unwrap();
+        let ticket = issue_stream_ticket(&user_token, &cache).await.unwrap();
+        logout(&user_token, &cache).await.unwrap();
+        let result = consume_stream_ticket(&ticket.ticket, &repos, &cache).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TokenInvalid));
+
+        // 修改密码等操作注销所有会话后，已签发的票据同样失效
+        let pair = create_session(&user, false, &cache).await.unwrap();
+        let user_token = util::token_decode(&pair.access_token).await.unwrap();
+        let ticket = issue_stream_ticket(&user_token, &cache).await.unwrap();
+        revoke_all_sessions(user.pk_id, &repos, &cache).await.unwrap();
+        let result = consume_stream_ticket(&ticket.ticket, &repos, &cache).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TokenInvalid));
+    }
+}
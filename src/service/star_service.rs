@@ -0,0 +1,182 @@
+use crate::common::api::{Page, Pagination};
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::notice_hub::NoticeHub;
+use crate::model::star::StarType;
+use crate::model::topic::Topic;
+use crate::model::user::{UserBrief, UserToken};
+use crate::repository::Repositories;
+use crate::service::notice_service;
+use crate::AppResult;
+
+/// 收藏、点赞或关注，重复操作不会产生变化，返回本次是否新增
+pub async fn star(
+    star_type: StarType,
+    star_id: u64,
+    user_token: &UserToken,
+    repos: &Repositories,
+    hub: &NoticeHub,
+) -> AppResult<bool> {
+    check_star_target(star_type, star_id, user_token, repos).await?;
+    let inserted = repos.stars.insert_star(star_type, user_token.user_id, star_id).await?;
+    if inserted && star_type == StarType::User {
+        notice_service::notify_follow(star_id, user_token, repos, hub).await;
+    }
+    Ok(inserted)
+}
+
+/// 取消收藏、点赞或关注，重复操作不会产生变化，返回本次是否删除
+pub async fn unstar(
+    star_type: StarType,
+    star_id: u64,
+    user_token: &UserToken,
+    repos: &Repositories,
+) -> AppResult<bool> {
+    repos.stars.delete_star(star_type, user_token.user_id, star_id).await
+}
+
+async fn check_star_target(
+    star_type: StarType,
+    star_id: u64,
+    user_token: &UserToken,
+    repos: &Repositories,
+) -> AppResult<()> {
+    match star_type {
+        StarType::User => {
+            if star_id == user_token.user_id {
+                return Err(AppError::BusinessError(ErrorCode::CannotFollowSelf));
+            }
+            repos.users.find_user_by_id(star_id).await?;
+        }
+        StarType::Topic => {
+            repos.topics.find_topic_by_id(star_id).await?;
+        }
+        StarType::Comment => {
+            repos.comments.find_comment_by_id(star_id).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn list_star_topics(user_id: u64, pagination: Pagination, repos: &Repositories) -> AppResult<Page<Topic>> {
+    let total = repos.stars.count_star_topics_by_user(user_id).await?;
+    let records = repos
+        .stars
+        .find_star_topics_by_user(user_id, pagination.offset(), pagination.limit())
+        .await?;
+    Ok(Page::new(total, &pagination, records))
+}
+
+pub async fn list_followers(user_id: u64, pagination: Pagination, repos: &Repositories) -> AppResult<Page<UserBrief>> {
+    let total = repos.stars.count_stars_by_target(StarType::User, user_id).await?;
+    let records = repos
+        .stars
+        .find_followers(user_id, pagination.offset(), pagination.limit())
+        .await?;
+    Ok(Page::new(total, &pagination, records))
+}
+
+pub async fn list_followings(user_id: u64, pagination: Pagination, repos: &Repositories) -> AppResult<Page<UserBrief>> {
+    let total = repos.stars.count_stars_by_user(StarType::User, user_id).await?;
+    let records = repos
+        .stars
+        .find_followings(user_id, pagination.offset(), pagination.limit())
+        .await?;
+    Ok(Page::new(total, &pagination, records))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::{create_topic, create_user, error_code, pagination, user_token};
+    use crate::model::comment::NewComment;
+    use crate::model::user::Role;
+
+    /// 与 `star` 相同，只是不发送关注通知
+    async fn star_without_notice(
+        star_type: StarType,
+        star_id: u64,
+        user_id: u64,
+        repos: &Repositories,
+    ) -> AppResult<bool> {
+        let user_token = user_token(user_id, Role::User);
+        check_star_target(star_type, star_id, &user_token, repos).await?;
+        repos.stars.insert_star(star_type, user_id, star_id).await
+    }
+
+    #[tokio::test]
+    async fn topic_star_keeps_like_times_in_sync() {
+        let repos = Repositories::memory();
+        let topic_id = create_topic(1, "主题", &repos).await;
+        assert!(star_without_notice(StarType::Topic, topic_id, 2, &repos).await.unwrap());
+        // 重复收藏不会重复计数
+        assert!(!star_without_notice(StarType::Topic, topic_id, 2, &repos).await.unwrap());
+        assert!(star_without_notice(StarType::Topic, topic_id, 3, &repos).await.unwrap());
+        assert_eq!(repos.topics.find_topic_by_id(topic_id).await.unwrap().like_times, 2);
+
+        let page = list_star_topics(2, pagination(1, 10), &repos).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.records[0].pk_id, topic_id);
+
+        let token = user_token(2, Role::User);
+        assert!(unstar(StarType::Topic, topic_id, &token, &repos).await.unwrap());
+        assert!(!unstar(StarType::Topic, topic_id, &token, &repos).await.unwrap());
+        assert_eq!(repos.topics.find_topic_by_id(topic_id).await.unwrap().like_times, 1);
+        assert_eq!(list_star_topics(2, pagination(1, 10), &repos).await.unwrap().total, 0);
+    }
+
+    #[tokio::test]
+    async fn comment_star_keeps_like_amount_in_sync() {
+        let repos = Repositories::memory();
+        let topic_id = create_topic(1, "主题", &repos).await;
+        let comment = NewComment {
+            user_id: 1,
+            topic_id,
+            parent_id: None,
+            root_id: None,
+            depth: 0,
+            content: "评论",
+        };
+        let comment_id = repos.comments.insert_one_comment(comment).await.unwrap();
+        star_without_notice(StarType::Comment, comment_id, 2, &repos)
+            .await
+            .unwrap();
+        star_without_notice(StarType::Comment, comment_id, 2, &repos)
+            .await
+            .unwrap();
+        let comment = repos.comments.find_comment_by_id(comment_id).await.unwrap();
+        assert_eq!(comment.like_amount, 1);
+        unstar(StarType::Comment, comment_id, &user_token(2, Role::User), &repos)
+            .await
+            .unwrap();
+        let comment = repos.comments.find_comment_by_id(comment_id).await.unwrap();
+        assert_eq!(comment.like_amount, 0);
+    }
+
+    #[tokio::test]
+    async fn follow_lists_followers_and_followings() {
+        let repos = Repositories::memory();
+        let alice = create_user("alice", "alice@whatsoo.org", &repos).await.pk_id;
+        let bob = create_user("bob", "bob@whatsoo.org", &repos).await.pk_id;
+        let result = star_without_notice(StarType::User, alice, alice, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::CannotFollowSelf));
+        let result = star_without_notice(StarType::User, bob + 1, alice, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::UserNotFound));
+
+        star_without_notice(StarType::User, bob, alice, &repos).await.unwrap();
+        let followers = list_followers(bob, pagination(1, 10), &repos).await.unwrap();
+        assert_eq!(followers.total, 1);
+        assert_eq!(followers.records[0].uk_username, "alice");
+        let followings = list_followings(alice, pagination(1, 10), &repos).await.unwrap();
+        assert_eq!(followings.records[0].uk_username, "bob");
+        assert_eq!(list_followings(bob, pagination(1, 10), &repos).await.unwrap().total, 0);
+    }
+
+    #[tokio::test]
+    async fn cannot_star_missing_targets() {
+        let repos = Repositories::memory();
+        let result = star_without_notice(StarType::Topic, 1, 2, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TopicNotFound));
+        let result = star_without_notice(StarType::Comment, 1, 2, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::CommentNotFound));
+    }
+}
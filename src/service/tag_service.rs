@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use crate::common::api::{Page, Pagination};
+use crate::common::constant::MAX_TOPIC_TAGS;
+use crate::common::err::{AppError, ErrorCode};
+use crate::model::tag::{Tag, TagFront, TagNode};
+use crate::model::topic::Topic;
+use crate::model::user::{Role, UserToken};
+use crate::repository::Repositories;
+use crate::AppResult;
+
+// 标签由管理员通过 /admin/tag 路由管理
+pub async fn create_tag(tag: TagFront, user_token: &UserToken, repos: &Repositories) -> AppResult<u64> {
+    check_admin(user_token)?;
+    let parent_tag = check_parent_tag(None, tag.parent_tag, repos).await?;
+    repos.tags.insert_one_tag(&tag, parent_tag, user_token.user_id).await
+}
+
+pub async fn update_tag(pk_id: u64, tag: TagFront, user_token: &UserToken, repos: &Repositories) -> AppResult<()> {
+    check_admin(user_token)?;
+    repos.tags.find_tag_by_id(pk_id).await?;
+    let parent_tag = check_parent_tag(Some(pk_id), tag.parent_tag, repos).await?;
+    if repos
+        .tags
+        .update_tag(pk_id, &tag, parent_tag, user_token.user_id)
+        .await?
+    {
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::UpdateConflict))
+    }
+}
+
+pub async fn delete_tag(pk_id: u64, user_token: &UserToken, repos: &Repositories) -> AppResult<()> {
+    check_admin(user_token)?;
+    if repos.tags.count_child_tags(pk_id).await? > 0 {
+        return Err(AppError::BusinessError(ErrorCode::TagHasChildren));
+    }
+    if repos.tags.delete_tag(pk_id).await? {
+        info!("管理员 {} 删除了标签 {}", user_token.user_id, pk_id);
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::TagNotFound))
+    }
+}
+
+// 路由已通过 `RequireRole<Admin>` 校验，这里再检查一次，避免从其他入口调用时绕过权限
+fn check_admin(user_token: &UserToken) -> AppResult<()> {
+    if user_token.role >= Role::Admin {
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::Forbidden))
+    }
+}
+
+// 标签最多两个层级，父标签只能是顶层标签，有子标签的标签不能再成为子标签
+async fn check_parent_tag(pk_id: Option<u64>, parent_tag: Option<u64>, repos: &Repositories) -> AppResult<u64> {
+    let parent_id = match parent_tag {
+        None | Some(0) => return Ok(0),
+        Some(parent_id) => parent_id,
+    };
+    if pk_id == Some(parent_id) {
+        return Err(AppError::BusinessError(ErrorCode::TagParentSelf));
+    }
+    let parent = repos.tags.find_tag_by_id(parent_id).await?;
+    if parent.parent_tag != 0 {
+        return Err(AppError::BusinessError(ErrorCode::TagTooDeep));
+    }
+    if let Some(pk_id) = pk_id {
+        if repos.tags.count_child_tags(pk_id).await? > 0 {
+            return Err(AppError::BusinessError(ErrorCode::TagTooDeep));
+        }
+    }
+    Ok(parent_id)
+}
+
+pub async fn tag_tree(repos: &Repositories) -> AppResult<Vec<TagNode>> {
+    let tags = repos.tags.find_all_tags().await?;
+    let mut children: HashMap<u64, Vec<Tag>> = HashMap::new();
+    let mut roots = Vec::new();
+    for tag in tags {
+        if tag.parent_tag == 0 {
+            roots.push(tag);
+        } else {
+            children.entry(tag.parent_tag).or_default().push(tag);
+        }
+    }
+    Ok(roots
+        .into_iter()
+        .map(|tag| TagNode {
+            children: children.remove(&tag.pk_id).unwrap_or_default(),
+            tag,
+        })
+        .collect())
+}
+
+pub async fn list_tag_topics(tag_id: u64, pagination: Pagination, repos: &Repositories) -> AppResult<Page<Topic>> {
+    repos.tags.find_tag_by_id(tag_id).await?;
+    let total = repos.topics.count_topics_by_tag(tag_id).await?;
+    let records = repos
+        .topics
+        .find_topics_by_tag(tag_id, pagination.offset(), pagination.limit())
+        .await?;
+    Ok(Page::new(total, &pagination, records))
+}
+
+/// 校验主题关联的标签，去除重复的标签
+pub async fn check_topic_tags(tag_ids: &mut Vec<u64>, repos: &Repositories) -> AppResult<()> {
+    let mut seen = Vec::with_capacity(tag_ids.len());
+    tag_ids.retain(|id| {
+        if seen.contains(id) {
+            false
+        } else {
+            seen.push(*id);
+            true
+        }
+    });
+    if tag_ids.is_empty() {
+        return Err(AppError::BusinessError(ErrorCode::TopicTagRequired));
+    }
+    if tag_ids.len() > MAX_TOPIC_TAGS {
+        return Err(AppError::BusinessError(ErrorCode::TopicTooManyTags));
+    }
+    for tag_id in tag_ids.iter() {
+        repos.tags.find_tag_by_id(*tag_id).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::{error_code, pagination, user_token};
+    use crate::model::topic::TopicFront;
+
+    fn front(tag_name: &str, parent_tag: Option<u64>) -> TagFront {
+        TagFront {
+            tag_name: String::from(tag_name),
+            uk_logo: None,
+            parent_tag,
+        }
+    }
+
+    fn admin() -> UserToken {
+        user_token(1, Role::Admin)
+    }
+
+    #[tokio::test]
+    async fn only_admin_manages_tags() {
+        let repos = Repositories::memory();
+        let moderator = user_token(2, Role::Moderator);
+        let result = create_tag(front("Rust", None), &moderator, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::Forbidden));
+        let pk_id = create_tag(front("Rust", None), &admin(), &repos).await.unwrap();
+        let result = update_tag(pk_id, front("Go", None), &moderator, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::Forbidden));
+        let result = delete_tag(pk_id, &moderator, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::Forbidden));
+        // 标签名不能重复
+        let result = create_tag(front("Rust", None), &admin(), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TagNameExists));
+    }
+
+    #[tokio::test]
+    async fn tags_have_at_most_two_levels() {
+        let repos = Repositories::memory();
+        let lang = create_tag(front("编程语言", None), &admin(), &repos).await.unwrap();
+        let rust = create_tag(front("Rust", Some(lang)), &admin(), &repos).await.unwrap();
+        let other = create_tag(front("其他", None), &admin(), &repos).await.unwrap();
+
+        let result = create_tag(front("异步", Some(rust)), &admin(), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TagTooDeep));
+        let result = update_tag(lang, front("编程语言", Some(lang)), &admin(), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TagParentSelf));
+        // 有子标签的标签不能成为子标签，也不能删除
+        let result = update_tag(lang, front("编程语言", Some(other)), &admin(), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TagTooDeep));
+        let result = delete_tag(lang, &admin(), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TagHasChildren));
+
+        let tree = tag_tree(&repos).await.unwrap();
+        let tree: Vec<(&str, Vec<&str>)> = tree
+            .iter()
+            .map(|n| {
+                (
+                    n.tag.tag_name.as_str(),
+                    n.children.iter().map(|c| c.tag_name.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(tree, vec![("编程语言", vec!["Rust"]), ("其他", vec![])]);
+
+        delete_tag(rust, &admin(), &repos).await.unwrap();
+        delete_tag(lang, &admin(), &repos).await.unwrap();
+        let result = delete_tag(lang, &admin(), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TagNotFound));
+    }
+
+    #[tokio::test]
+    async fn topic_tags_are_checked_and_deduplicated() {
+        let repos = Repositories::memory();
+        let rust = create_tag(front("Rust", None), &admin(), &repos).await.unwrap();
+        let mut tag_ids = vec![rust, rust];
+        check_topic_tags(&mut tag_ids, &repos).await.unwrap();
+        assert_eq!(tag_ids, vec![rust]);
+        let result = check_topic_tags(&mut Vec::new(), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TopicTagRequired));
+        let result = check_topic_tags(&mut vec![rust, rust + 1], &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TagNotFound));
+        let mut too_many: Vec<u64> = (1..=MAX_TOPIC_TAGS as u64 + 1).collect();
+        let result = check_topic_tags(&mut too_many, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TopicTooManyTags));
+    }
+
+    #[tokio::test]
+    async fn tag_topics_include_child_tags() {
+        let repos = Repositories::memory();
+        let lang = create_tag(front("编程语言", None), &admin(), &repos).await.unwrap();
+        let rust = create_tag(front("Rust", Some(lang)), &admin(), &repos).await.unwrap();
+        let other = create_tag(front("其他", None), &admin(), &repos).await.unwrap();
+        for &(title, tag_id) in &[("Rust 主题", rust), ("语言主题", lang), ("其他主题", other)] {
+            let topic = TopicFront {
+                user_id: Some(1),
+                title: String::from(title),
+                content: String::from("内容"),
+                tags: vec![tag_id],
+            };
+            repos.topics.insert_one_topic(&topic).await.unwrap();
+        }
+        let page = list_tag_topics(lang, pagination(1, 10), &repos).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(list_tag_topics(rust, pagination(1, 10), &repos).await.unwrap().total, 1);
+        let result = list_tag_topics(other + 1, pagination(1, 10), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TagNotFound));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::common::api::{Page, Pagination};
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::notice_hub::NoticeHub;
+use crate::model::topic::{Topic, TopicFront};
+use crate::model::user::{Role, UserToken};
+use crate::repository::Repositories;
+use crate::service::{notice_service, tag_service};
+use crate::AppResult;
+
+pub async fn create_topic(
+    mut new_topic: TopicFront,
+    user_token: &UserToken,
+    repos: &Repositories,
+    hub: &NoticeHub,
+) -> AppResult<u64> {
+    tag_service::check_topic_tags(&mut new_topic.tags, repos).await?;
+    let pk_id = repos.topics.insert_one_topic(&new_topic).await?;
+    notice_service::notify_mentions(&new_topic.content, pk_id, &new_topic.title, &[], user_token, repos, hub).await;
+    Ok(pk_id)
+}
+
+pub async fn get_topic(pk_id: u64, repos: &Repositories) -> AppResult<Topic> {
+    repos.topics.increase_click_times(pk_id).await?;
+    repos.topics.find_topic_by_id(pk_id).await
+}
+
+pub async fn list_topics(pagination: Pagination, repos: &Repositories) -> AppResult<Page<Topic>> {
+    let total = repos.topics.count_topics().await?;
+    let records = repos
+        .topics
+        .find_topics(pagination.offset(), pagination.limit())
+        .await?;
+    Ok(Page::new(total, &pagination, records))
+}
+
+pub async fn list_user_topics(user_id: u64, pagination: Pagination, repos: &Repositories) -> AppResult<Page<Topic>> {
+    let total = repos.topics.count_topics_by_user(user_id).await?;
+    let records = repos
+        .topics
+        .find_topics_by_user(user_id, pagination.offset(), pagination.limit())
+        .await?;
+    Ok(Page::new(total, &pagination, records))
+}
+
+pub async fn update_topic(
+    pk_id: u64,
+    mut topic: TopicFront,
+    user_token: &UserToken,
+    repos: &Repositories,
+) -> AppResult<()> {
+    check_topic_permission(pk_id, user_token, repos).await?;
+    tag_service::check_topic_tags(&mut topic.tags, repos).await?;
+    if repos.topics.update_topic(pk_id, topic, user_token.user_id).await? {
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::UpdateConflict))
+    }
+}
+
+pub async fn delete_topic(pk_id: u64, user_token: &UserToken, repos: &Repositories) -> AppResult<()> {
+    check_topic_permission(pk_id, user_token, repos).await?;
+    if repos.topics.delete_topic(pk_id, user_token.user_id).await? {
+        Ok(())
+    } else {
+        Err(AppError::BusinessError(ErrorCode::DeleteConflict))
+    }
+}
+
+/// 作者和版主以上的角色可以修改和删除主题，`update_user` 记录实际操作人
+async fn check_topic_permission(pk_id: u64, user_token: &UserToken, repos: &Repositories) -> AppResult<Topic> {
+    let topic = repos.topics.find_topic_by_id(pk_id).await?;
+    if topic.user_id == user_token.user_id || user_token.role >= Role::Moderator {
+        Ok(topic)
+    } else {
+        Err(AppError::BusinessError(ErrorCode::NotTopicAuthor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::{create_topic, error_code, pagination, user_token};
+    use crate::model::tag::TagFront;
+
+    fn titles(page: &Page<Topic>) -> Vec<&str> {
+        page.records.iter().map(|t| t.title.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn get_topic_increases_click_times() {
+        let repos = Repositories::memory();
+        let pk_id = create_topic(1, "主题", &repos).await;
+        assert_eq!(get_topic(pk_id, &repos).await.unwrap().click_times, 1);
+        assert_eq!(get_topic(pk_id, &repos).await.unwrap().click_times, 2);
+        assert_eq!(
+            error_code(get_topic(pk_id + 1, &repos).await),
+            Some(ErrorCode::TopicNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn list_topics_pins_top_first() {
+        let repos = Repositories::memory();
+        let first = create_topic(1, "第一个", &repos).await;
+        create_topic(2, "第二个", &repos).await;
+        create_topic(1, "第三个", &repos).await;
+        repos.topics.update_topic_top(first, true).await.unwrap();
+
+        let page = list_topics(pagination(1, 2), &repos).await.unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(titles(&page), vec!["第一个", "第三个"]);
+        let page = list_topics(pagination(2, 2), &repos).await.unwrap();
+        assert_eq!(titles(&page), vec!["第二个"]);
+    }
+
+    #[tokio::test]
+    async fn list_user_topics_only_returns_own_topics() {
+        let repos = Repositories::memory();
+        create_topic(1, "第一个", &repos).await;
+        create_topic(2, "第二个", &repos).await;
+        create_topic(1, "第三个", &repos).await;
+        let page = list_user_topics(1, pagination(1, 10), &repos).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(titles(&page), vec!["第三个", "第一个"]);
+    }
+
+    fn edited(title: &str, tag_id: u64) -> TopicFront {
+        TopicFront {
+            user_id: None,
+            title: String::from(title),
+            content: String::from("修改后的内容"),
+            tags: vec![tag_id],
+        }
+    }
+
+    async fn create_tag(repos: &Repositories) -> u64 {
+        let tag = TagFront {
+            tag_name: String::from("Rust"),
+            uk_logo: None,
+            parent_tag: None,
+        };
+        repos.tags.insert_one_tag(&tag, 0, 1).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn only_author_or_moderator_can_edit() {
+        let repos = Repositories::memory();
+        let pk_id = create_topic(1, "主题", &repos).await;
+        let tag_id = create_tag(&repos).await;
+
+        let result = update_topic(pk_id, edited("别人改的", tag_id), &user_token(2, Role::User), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::NotTopicAuthor));
+
+        update_topic(pk_id, edited("作者改的", tag_id), &user_token(1, Role::User), &repos)
+            .await
+            .unwrap();
+        update_topic(
+            pk_id,
+            edited("版主改的", tag_id),
+            &user_token(3, Role::Moderator),
+            &repos,
+        )
+        .await
+        .unwrap();
+        let topic = repos.topics.find_topic_by_id(pk_id).await.unwrap();
+        assert_eq!(topic.title, "版主改的");
+        // 作者不变，修改人记录实际操作的版主
+        assert_eq!((topic.user_id, topic.update_user), (1, 3));
+        assert!(topic.update_time >= topic.create_time);
+        assert_eq!(topic.tags, tag_id.to_string());
+    }
+
+    #[tokio::test]
+    async fn delete_is_soft_and_hides_topic() {
+        let repos = Repositories::memory();
+        let pk_id = create_topic(1, "主题", &repos).await;
+        let tag_id = create_tag(&repos).await;
+        let result = delete_topic(pk_id, &user_token(2, Role::User), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::NotTopicAuthor));
+
+        delete_topic(pk_id, &user_token(3, Role::Admin), &repos).await.unwrap();
+        assert_eq!(list_topics(pagination(1, 10), &repos).await.unwrap().total, 0);
+        assert_eq!(
+            error_code(get_topic(pk_id, &repos).await),
+            Some(ErrorCode::TopicNotFound)
+        );
+        // 已删除的主题不能再修改或删除
+        let result = update_topic(pk_id, edited("标题", tag_id), &user_token(1, Role::User), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TopicNotFound));
+        let result = delete_topic(pk_id, &user_token(1, Role::User), &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::TopicNotFound));
+    }
+}
@@ -0,0 +1,316 @@
+use std::io::{self, Cursor};
+
+use chrono::Local;
+use image::imageops::FilterType;
+use image::io::Reader;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageOutputFormat};
+use uuid::Uuid;
+
+use crate::AppResult;
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::settings::UploadSettings;
+use crate::model::upload::{ImageKind, Thumbnail, UploadedImage};
+use crate::storage::Storage;
+
+/// 重新编码为 JPEG 时的质量
+const JPEG_QUALITY: u8 = 85;
+
+/// 编码后的一张图片
+struct EncodedImage {
+    bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// 校验并处理上传的图片，保存原图缩放后的版本和缩略图
+///
+/// 实际格式以文件内容为准，与客户端声明的 `Content-Type` 不一致时拒绝。
+/// 所有图片都会重新编码，去掉 EXIF 等元数据，有透明通道的保存为 PNG，其余保存为 JPEG，GIF 只保留第一帧。
+pub async fn upload_image(
+    kind: ImageKind,
+    bytes: Vec<u8>,
+    content_type: Option<&str>,
+    settings: &UploadSettings,
+    storage: &dyn Storage,
+) -> AppResult<UploadedImage> {
+    let format = sniff_format(&bytes, content_type)?;
+    let (max_dimension, max_pixels) = (settings.max_image_dimension, settings.max_image_pixels);
+    let (image, thumbnails, extension, content_type) = tokio::task::spawn_blocking(move || {
+        let image = decode(&bytes, format, max_dimension, max_pixels)?;
+        let (output, extension, content_type) = if image.color().has_alpha() {
+            (ImageOutputFormat::Png, "png", "image/png")
+        } else {
+            (ImageOutputFormat::Jpeg(JPEG_QUALITY), "jpg", "image/jpeg")
+        };
+        let resized = encode(&resize(&image, kind, kind.max_size()), &output)?;
+        let thumbnails = kind
+            .thumbnail_sizes()
+            .iter()
+            .map(|&size| encode(&resize(&image, kind, size), &output).map(|encoded| (size, encoded)))
+            .collect::<AppResult<Vec<_>>>()?;
+        Ok::<_, AppError>((resized, thumbnails, extension, content_type))
+    })
+    .await
+    .map_err(|e| AppError::IoError(io::Error::new(io::ErrorKind::Other, e)))??;
+
+    let prefix = format!(
+        "{}/{}/{}",
+        kind.dir(),
+        Local::now().format("%Y%m"),
+        Uuid::new_v4().to_simple()
+    );
+    let key = format!("{}_{}.{}", prefix, kind.max_size(), extension);
+    let url = save(storage, &key, image.bytes, content_type).await?;
+    let mut uploaded = UploadedImage {
+        url,
+        width: image.width,
+        height: image.height,
+        thumbnails: Vec::with_capacity(thumbnails.len()),
+    };
+    for (size, thumbnail) in thumbnails {
+        let key = format!("{}_{}.{}", prefix, size, extension);
+        uploaded.thumbnails.push(Thumbnail {
+            url: save(storage, &key, thumbnail.bytes, content_type).await?,
+            width: thumbnail.width,
+            height: thumbnail.height,
+        });
+    }
+    info!("图片上传成功: {}", key);
+    Ok(uploaded)
+}
+
+/// 根据文件头判断图片格式，客户端声明的类型只用于交叉校验
+fn sniff_format(bytes: &[u8], content_type: Option<&str>) -> AppResult<ImageFormat> {
+    let format = image::guess_format(bytes).map_err(|_| AppError::BusinessError(ErrorCode::UnsupportedImageType))?;
+    let expected = match format {
+        ImageFormat::Png => &["image/png"][..],
+        ImageFormat::Jpeg => &["image/jpeg", "image/jpg"][..],
+        ImageFormat::Gif => &["image/gif"][..],
+        ImageFormat::WebP => &["image/webp"][..],
+        _ => return Err(AppError::BusinessError(ErrorCode::UnsupportedImageType)),
+    };
+    match content_type {
+        Some(content_type) if !expected.iter().any(|e| e.eq_ignore_ascii_case(content_type)) => {
+            info!("图片声明的类型 {} 与实际格式 {:?} 不一致", content_type, format);
+            Err(AppError::BusinessError(ErrorCode::UnsupportedImageType))
+        }
+        _ => Ok(format),
+    }
+}
+
+/// 先只读取宽高，边长或总像素数过大的图片不解码
+fn decode(bytes: &[u8], format: ImageFormat, max_dimension: u32, max_pixels: u64) -> AppResult<DynamicImage> {
+    let invalid = |e| {
+        info!("图片解析失败: {}", e);
+        AppError::BusinessError(ErrorCode::InvalidImage)
+    };
+    let (width, height) = Reader::with_format(Cursor::new(bytes), format)
+        .into_dimensions()
+        .map_err(invalid)?;
+    if width == 0 || height == 0 {
+        return Err(AppError::BusinessError(ErrorCode::InvalidImage));
+    }
+    if width > max_dimension || height > max_dimension || u64::from(width) * u64::from(height) > max_pixels {
+        return Err(AppError::BusinessError(ErrorCode::ImageDimensionTooLarge));
+    }
+    image::load_from_memory_with_format(bytes, format).map_err(invalid)
+}
+
+/// 头像裁剪为 `size` 的正方形，其余图片等比缩小到最长边不超过 `size`，不会放大
+fn resize(image: &DynamicImage, kind: ImageKind, size: u32) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    match kind {
+        ImageKind::Avatar => image.resize_to_fill(size, size, FilterType::Lanczos3),
+        ImageKind::Topic if width > size || height > size => image.resize(size, size, FilterType::Lanczos3),
+        ImageKind::Topic => image.clone(),
+    }
+}
+
+fn encode(image: &DynamicImage, output: &ImageOutputFormat) -> AppResult<EncodedImage> {
+    // JPEG 不支持透明通道和16位色深，PNG 统一保存为8位
+    let image = match output {
+        ImageOutputFormat::Png => DynamicImage::ImageRgba8(image.to_rgba8()),
+        _ => DynamicImage::ImageRgb8(image.to_rgb8()),
+    };
+    let mut bytes = Vec::new();
+    image.write_to(&mut bytes, output.clone()).map_err(|e| {
+        error!("图片编码失败: {}", e);
+        AppError::BusinessError(ErrorCode::InternalError)
+    })?;
+    Ok(EncodedImage {
+        bytes,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+async fn save(storage: &dyn Storage, key: &str, bytes: Vec<u8>, content_type: &str) -> AppResult<String> {
+    storage.put(key, bytes, content_type).await?;
+    Ok(storage.url(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    use super::*;
+    use crate::common::settings::StorageBackend;
+    use crate::common::test_util::error_code;
+
+    const PUBLIC_URL: &str = "https://cdn.whatsoo.org/uploads";
+
+    /// 保存在内存中的存储，记录每个 key 的内容类型
+    #[derive(Default)]
+    struct MemoryStorage {
+        objects: Mutex<HashMap<String, (Vec<u8>, String)>>,
+    }
+
+    #[async_trait]
+    impl Storage for MemoryStorage {
+        async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> AppResult<()> {
+            let mut objects = self.objects.lock().unwrap();
+            objects.insert(String::from(key), (bytes, String::from(content_type)));
+            Ok(())
+        }
+
+        fn url(&self, key: &str) -> String {
+            format!("{}/{}", PUBLIC_URL, key)
+        }
+    }
+
+    fn settings() -> UploadSettings {
+        UploadSettings {
+            backend: StorageBackend::Local,
+            dir: PathBuf::from("uploads"),
+            public_url: String::from(PUBLIC_URL),
+            max_file_size: 5 * 1024 * 1024,
+            max_image_dimension: 2000,
+            max_image_pixels: 1_000_000,
+        }
+    }
+
+    fn write(image: DynamicImage, format: ImageOutputFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image.write_to(&mut bytes, format).unwrap();
+        bytes
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_pixel(width, height, Rgb([200, 80, 40]));
+        write(DynamicImage::ImageRgb8(image), ImageOutputFormat::Png)
+    }
+
+    fn transparent_png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, Rgba([200, 80, 40, 128]));
+        write(DynamicImage::ImageRgba8(image), ImageOutputFormat::Png)
+    }
+
+    #[test]
+    fn sniff_format_uses_content() {
+        let bytes = png(8, 8);
+        assert_eq!(sniff_format(&bytes, Some("image/png")).unwrap(), ImageFormat::Png);
+        assert_eq!(sniff_format(&bytes, Some("IMAGE/PNG")).unwrap(), ImageFormat::Png);
+        assert_eq!(sniff_format(&bytes, None).unwrap(), ImageFormat::Png);
+        // 声明的类型与实际格式不一致
+        assert_eq!(
+            error_code(sniff_format(&bytes, Some("image/jpeg"))),
+            Some(ErrorCode::UnsupportedImageType)
+        );
+        // 伪装成图片的 HTML 和不支持的格式
+        let html = b"<html><script>alert(1)</script></html>";
+        assert_eq!(
+            error_code(sniff_format(html, Some("image/png"))),
+            Some(ErrorCode::UnsupportedImageType)
+        );
+        let bmp = write(DynamicImage::new_rgb8(8, 8), ImageOutputFormat::Bmp);
+        assert_eq!(
+            error_code(sniff_format(&bmp, Some("image/bmp"))),
+            Some(ErrorCode::UnsupportedImageType)
+        );
+    }
+
+    #[test]
+    fn decode_caps_dimension_and_pixels() {
+        let bytes = png(100, 20);
+        assert!(decode(&bytes, ImageFormat::Png, 100, 2000).is_ok());
+        assert_eq!(
+            error_code(decode(&bytes, ImageFormat::Png, 99, 2000)),
+            Some(ErrorCode::ImageDimensionTooLarge)
+        );
+        // 宽高都没有超限，总像素数超限
+        assert_eq!(
+            error_code(decode(&bytes, ImageFormat::Png, 100, 1999)),
+            Some(ErrorCode::ImageDimensionTooLarge)
+        );
+    }
+
+    #[test]
+    fn decode_rejects_truncated_image() {
+        let bytes = png(100, 100);
+        assert_eq!(
+            error_code(decode(&bytes[..bytes.len() / 2], ImageFormat::Png, 2000, 1_000_000)),
+            Some(ErrorCode::InvalidImage)
+        );
+        assert_eq!(
+            error_code(decode(&bytes[..8], ImageFormat::Png, 2000, 1_000_000)),
+            Some(ErrorCode::InvalidImage)
+        );
+    }
+
+    #[tokio::test]
+    async fn avatar_is_cropped_to_square_jpeg() {
+        let storage = MemoryStorage::default();
+        let uploaded = upload_image(ImageKind::Avatar, png(400, 300), None, &settings(), &storage)
+            .await
+            .unwrap();
+        assert_eq!((uploaded.width, uploaded.height), (256, 256));
+        assert!(uploaded.url.starts_with(&format!("{}/avatar/", PUBLIC_URL)));
+        assert!(uploaded.url.ends_with("_256.jpg"));
+        assert_eq!(uploaded.thumbnails.len(), 1);
+        assert_eq!((uploaded.thumbnails[0].width, uploaded.thumbnails[0].height), (64, 64));
+
+        let objects = storage.objects.lock().unwrap();
+        assert_eq!(objects.len(), 2);
+        for (bytes, content_type) in objects.values() {
+            assert_eq!(content_type, "image/jpeg");
+            assert_eq!(image::guess_format(bytes).unwrap(), ImageFormat::Jpeg);
+        }
+    }
+
+    #[tokio::test]
+    async fn topic_image_keeps_ratio_and_transparency() {
+        let storage = MemoryStorage::default();
+        let uploaded = upload_image(
+            ImageKind::Topic,
+            transparent_png(640, 200),
+            Some("image/png"),
+            &settings(),
+            &storage,
+        )
+        .await
+        .unwrap();
+        // 不超过最大边长时不放大
+        assert_eq!((uploaded.width, uploaded.height), (640, 200));
+        assert!(uploaded.url.ends_with("_1920.png"));
+        assert_eq!(
+            (uploaded.thumbnails[0].width, uploaded.thumbnails[0].height),
+            (320, 100)
+        );
+        let objects = storage.objects.lock().unwrap();
+        assert!(objects.values().all(|(_, content_type)| content_type == "image/png"));
+    }
+
+    #[tokio::test]
+    async fn oversized_image_is_not_stored() {
+        let storage = MemoryStorage::default();
+        let mut settings = settings();
+        settings.max_image_pixels = 400 * 300 - 1;
+        let result = upload_image(ImageKind::Avatar, png(400, 300), None, &settings, &storage).await;
+        assert_eq!(error_code(result), Some(ErrorCode::ImageDimensionTooLarge));
+        assert!(storage.objects.lock().unwrap().is_empty());
+    }
+}
@@ -1,78 +1,366 @@
-use r2d2::PooledConnection;
-use redis::Client;
-use sqlx::MySqlPool;
-
+use crate::cache::Cache;
 use crate::common::api::ApiResult;
+use crate::common::err::{AppError, ErrorCode};
+use crate::common::locale::Locale;
+use crate::common::settings::RateLimitSettings;
 use crate::common::util;
-use crate::model::user::{RegisterUser, User, VerifyStatus};
+use crate::common::verify_code::{self, VerifyCodeKind};
+use crate::model::star::StarType;
+use crate::model::user::{LoginUser, ProfileFront, RegisterUser, TokenPair, User, UserProfile, VerifyStatus};
 use crate::AppResult;
+use crate::repository::Repositories;
+use crate::service::session_service;
 
-pub async fn check_email_exists(email: String, pool: &MySqlPool) -> ApiResult<VerifyStatus> {
-    let exists = User::count_by_email(&email, pool).await;
-    match exists {
-        Ok(count) => {
-            if count == 0 {
-                ApiResult::ok().data(VerifyStatus::success()).msg("邮箱验证成功")
-            } else {
-                ApiResult::error().data(VerifyStatus::fail()).msg("邮箱已注册，请登录")
-            }
-        }
-        Err(e) => {
-            error!("邮箱验证出错，报错信息: {}", e.to_string());
-            ApiResult::error()
-                .data(VerifyStatus::fail())
-                .msg("邮箱验证，服务器出错")
-        }
+pub async fn check_email_exists(email: String, repos: &Repositories) -> AppResult<ApiResult<VerifyStatus>> {
+    let count = repos.users.count_by_email(&email).await.map_err(|e| {
+        error!("邮箱验证出错，报错信息: {}", e.to_string());
+        e
+    })?;
+    if count == 0 {
+        Ok(ApiResult::ok().data(VerifyStatus::success()).msg("邮箱验证成功"))
+    } else {
+        Ok(ApiResult::error().data(VerifyStatus::fail()).msg("邮箱已注册，请登录"))
     }
 }
 
-pub async fn check_username_exists(username: String, pool: &MySqlPool) -> ApiResult<VerifyStatus> {
-    let exists = User::count_by_username(username, pool).await;
-    match exists {
-        Ok(count) => {
-            if count == 0 {
-                ApiResult::ok().data(VerifyStatus::success()).msg("用户名认证成功")
-            } else {
-                ApiResult::error()
-                    .data(VerifyStatus::fail())
-                    .msg("用户名已存在，请登录")
-            }
-        }
-        Err(e) => {
-            error!("用户名验证出错，报错信息: {}", e.to_string());
-            ApiResult::error()
-                .data(VerifyStatus::fail())
-                .msg("用户名验证，服务器错误")
-        }
+pub async fn check_username_exists(username: String, repos: &Repositories) -> AppResult<ApiResult<VerifyStatus>> {
+    let count = repos.users.count_by_username(username).await.map_err(|e| {
+        error!("用户名验证出错，报错信息: {}", e.to_string());
+        e
+    })?;
+    if count == 0 {
+        Ok(ApiResult::ok().data(VerifyStatus::success()).msg("用户名认证成功"))
+    } else {
+        Ok(ApiResult::error()
+            .data(VerifyStatus::fail())
+            .msg("用户名已存在，请登录"))
     }
 }
 
 pub async fn register_user(
     mut register_user: RegisterUser,
-    connection: &mut PooledConnection<Client>,
-    pool: &MySqlPool,
+    cache: &dyn Cache,
+    repos: &Repositories,
 ) -> AppResult<ApiResult<VerifyStatus>> {
     let email = &register_user.uk_email;
     let verify_code = &register_user.email_verify_code;
-    let value = util::redis_get::<String>(email, connection).await?;
-
-    if value.eq(verify_code) {
-        // 邮箱校验成功即注册成功
-        // 加密密码
-        register_user.user_password = util::encode_pwd(&register_user.user_password).await?;
-        match User::insert_one_user(register_user, pool).await {
-            Ok(id) => {
-                tracing::info!("用户注册成功，用户id: {}", id);
-                Ok(ApiResult::ok()
-                    .msg("邮箱验证码校验正确，注册成功")
-                    .data(VerifyStatus::success()))
-            }
-            Err(e) => {
-                error!("插入用户失败，失败原因:{}", e.to_string());
-                Err(e)
-            }
+    verify_code::verify(VerifyCodeKind::Register, email, verify_code, cache).await?;
+    // 邮箱校验成功即注册成功
+    // 加密密码
+    register_user.user_password = util::encode_pwd(&register_user.user_password).await?;
+    match repos.users.insert_one_user(register_user).await {
+        Ok(id) => {
+            tracing::info!("用户注册成功，用户id: {}", id);
+            Ok(ApiResult::ok()
+                .msg("邮箱验证码校验正确，注册成功")
+                .data(VerifyStatus::success()))
         }
-    } else {
-        Ok(ApiResult::ok().msg("邮箱验证码校验失败").data(VerifyStatus::fail()))
+        Err(e) => {
+            error!("插入用户失败，失败原因:{}", e.to_string());
+            Err(e)
+        }
+    }
+}
+
+/// 邮箱不存在和密码错误统一返回 `InvalidCredentials`，并且都计入登录失败次数，避免借登录接口探测邮箱是否已注册
+pub async fn login(
+    login_user: &LoginUser,
+    locale: Locale,
+    rate_limit: &RateLimitSettings,
+    cache: &dyn Cache,
+    repos: &Repositories,
+) -> AppResult<TokenPair> {
+    session_service::check_login_lock(&login_user.email, cache).await?;
+    verify_code::verify(
+        VerifyCodeKind::Captcha,
+        &login_user.captcha_key,
+        &login_user.captcha_value,
+        cache,
+    )
+    .await?;
+    let user = match repos.users.find_user_by_email(&login_user.email).await {
+        Ok(user) => Some(user),
+        Err(AppError::BusinessError(ErrorCode::UserNotFound)) => None,
+        Err(e) => return Err(e),
+    };
+    let user = match user {
+        Some(user) if util::verify_pwd(&login_user.password, &user.user_password).await? => user,
+        _ => {
+            session_service::record_login_failure(&login_user.email, rate_limit, cache).await?;
+            return Err(AppError::BusinessError(ErrorCode::InvalidCredentials));
+        }
+    };
+    session_service::clear_login_failures(&login_user.email, cache).await?;
+    repos.users.update_last_login(user.pk_id, locale).await?;
+    session_service::create_session(&user, login_user.forever, cache).await
+}
+
+pub async fn get_profile(username: &str, repos: &Repositories) -> AppResult<UserProfile> {
+    let user = repos.users.find_user_by_username(username).await?;
+    build_profile(user, repos).await
+}
+
+pub async fn update_profile(user_id: u64, profile: ProfileFront, repos: &Repositories) -> AppResult<UserProfile> {
+    if !repos.users.update_user_profile(user_id, &profile.normalize()).await? {
+        return Err(AppError::BusinessError(ErrorCode::UpdateConflict));
+    }
+    let user = repos.users.find_user_by_id(user_id).await?;
+    build_profile(user, repos).await
+}
+
+async fn build_profile(user: User, repos: &Repositories) -> AppResult<UserProfile> {
+    let topic_count = repos.topics.count_topics_by_user(user.pk_id).await?;
+    let comment_count = repos.comments.count_comments_by_user(user.pk_id).await?;
+    let follower_count = repos.stars.count_stars_by_target(StarType::User, user.pk_id).await?;
+    Ok(UserProfile::new(user, topic_count, comment_count, follower_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::cache::MemoryCache;
+    use crate::common::jwt;
+    use crate::common::test_util::{create_topic, create_user, error_code, rate_limit_settings, EMAIL};
+    use crate::model::comment::NewComment;
+
+    const PASSWORD: &str = "password123";
+    const CAPTCHA_KEY: &str = "captcha-key";
+
+    fn register_form(username: &str, email: &str, code: &str) -> RegisterUser {
+        RegisterUser {
+            uk_username: String::from(username),
+            uk_email: String::from(email),
+            email_verify_code: String::from(code),
+            user_password: String::from(PASSWORD),
+            locale: Locale::default(),
+        }
+    }
+
+    async fn register(username: &str, email: &str, cache: &dyn Cache, repos: &Repositories) -> AppResult<()> {
+        verify_code::save(VerifyCodeKind::Register, email, "123456", cache).await?;
+        register_user(register_form(username, email, "123456"), cache, repos).await?;
+        Ok(())
+    }
+
+    /// 每次登录前都需要重新获取图形验证码
+    async fn login_with(password: &str, email: &str, cache: &dyn Cache, repos: &Repositories) -> AppResult<TokenPair> {
+        verify_code::save(VerifyCodeKind::Captcha, CAPTCHA_KEY, "abcd", cache).await?;
+        let login_user = LoginUser {
+            captcha_key: String::from(CAPTCHA_KEY),
+            captcha_value: String::from("abcd"),
+            email: String::from(email),
+            forever: false,
+            password: String::from(password),
+        };
+        login(&login_user, Locale::En, &rate_limit_settings(), cache, repos).await
+    }
+
+    #[tokio::test]
+    async fn register_success() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        register("tester", EMAIL, &cache, &repos).await.unwrap();
+        let user = repos.users.find_user_by_email(EMAIL).await.unwrap();
+        assert_eq!(user.uk_username, "tester");
+        // 密码加密后保存
+        assert_ne!(user.user_password, PASSWORD);
+        assert!(util::verify_pwd(PASSWORD, &user.user_password).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn register_with_wrong_code() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        verify_code::save(VerifyCodeKind::Register, EMAIL, "123456", &cache)
+            .await
+            .unwrap();
+        let result = register_user(register_form("tester", EMAIL, "654321"), &cache, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::CaptchaInvalid));
+        assert_eq!(repos.users.count_by_email(EMAIL).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn register_with_expired_code() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        // 验证码过期后缓存中不再有记录
+        let result = register_user(register_form("tester", EMAIL, "123456"), &cache, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::VerifyCodeExpired));
+        assert_eq!(repos.users.count_by_email(EMAIL).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn register_code_cannot_be_reused() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        register("tester", EMAIL, &cache, &repos).await.unwrap();
+        // 校验验证码在写入用户之前，重复使用时返回验证码过期而不是用户已存在
+        let result = register_user(register_form("other", EMAIL, "123456"), &cache, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::VerifyCodeExpired));
+    }
+
+    #[tokio::test]
+    async fn reset_password_code_cannot_register() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        verify_code::save(VerifyCodeKind::ResetPassword, EMAIL, "123456", &cache)
+            .await
+            .unwrap();
+        let result = register_user(register_form("tester", EMAIL, "123456"), &cache, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::VerifyCodeExpired));
+    }
+
+    #[tokio::test]
+    async fn register_duplicate_user() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        register("tester", EMAIL, &cache, &repos).await.unwrap();
+        for &(username, email) in &[("tester", "other@whatsoo.org"), ("other", EMAIL)] {
+            let code = error_code(register(username, email, &cache, &repos).await).unwrap();
+            assert_eq!(code, ErrorCode::UserExists);
+            assert_eq!(code.status(), StatusCode::CONFLICT);
+        }
+    }
+
+    #[tokio::test]
+    async fn login_success_updates_last_login() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        register("tester", EMAIL, &cache, &repos).await.unwrap();
+        let before = repos.users.find_user_by_email(EMAIL).await.unwrap();
+        assert_eq!(before.locale(), Locale::ZhCn);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let token_pair = login_with(PASSWORD, EMAIL, &cache, &repos).await.unwrap();
+        assert!(!token_pair.access_token.is_empty());
+        let after = repos.users.find_user_by_email(EMAIL).await.unwrap();
+        assert!(after.last_login_time > before.last_login_time);
+        // 汇总邮件使用最近一次登录时的语言
+        assert_eq!(after.locale(), Locale::En);
+    }
+
+    #[tokio::test]
+    async fn login_captcha_cannot_be_reused() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        register("tester", EMAIL, &cache, &repos).await.unwrap();
+        login_with(PASSWORD, EMAIL, &cache, &repos).await.unwrap();
+        let login_user = LoginUser {
+            captcha_key: String::from(CAPTCHA_KEY),
+            captcha_value: String::from("abcd"),
+            email: String::from(EMAIL),
+            forever: false,
+            password: String::from(PASSWORD),
+        };
+        let result = login(&login_user, Locale::En, &rate_limit_settings(), &cache, &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::VerifyCodeExpired));
+    }
+
+    #[tokio::test]
+    async fn login_wrong_password_and_unknown_email_look_the_same() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        register("tester", EMAIL, &cache, &repos).await.unwrap();
+        let wrong_password = error_code(login_with("wrong-password1", EMAIL, &cache, &repos).await).unwrap();
+        let unknown_email = error_code(login_with(PASSWORD, "nobody@whatsoo.org", &cache, &repos).await).unwrap();
+        assert_eq!(wrong_password, ErrorCode::InvalidCredentials);
+        assert_eq!(unknown_email, ErrorCode::InvalidCredentials);
+        assert_eq!(wrong_password.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn login_locked_after_max_failures() {
+        jwt::init_for_test();
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        register("tester", EMAIL, &cache, &repos).await.unwrap();
+        let settings = rate_limit_settings();
+        for _ in 0..settings.login_max_failures - 1 {
+            let result = login_with("wrong-password1", EMAIL, &cache, &repos).await;
+            assert_eq!(error_code(result), Some(ErrorCode::InvalidCredentials));
+        }
+        assert!(matches!(
+            login_with("wrong-password1", EMAIL, &cache, &repos).await,
+            Err(AppError::TooManyRequests(_))
+        ));
+        // 锁定期间密码正确也不能登录
+        assert!(matches!(
+            login_with(PASSWORD, EMAIL, &cache, &repos).await,
+            Err(AppError::TooManyRequests(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unknown_email_counts_as_login_failure() {
+        let (repos, cache) = (Repositories::memory(), MemoryCache::new());
+        let email = "nobody@whatsoo.org";
+        for _ in 0..rate_limit_settings().login_max_failures - 1 {
+            let _ = login_with(PASSWORD, email, &cache, &repos).await;
+        }
+        assert!(matches!(
+            login_with(PASSWORD, email, &cache, &repos).await,
+            Err(AppError::TooManyRequests(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn profile_counts_topics_comments_and_followers() {
+        let repos = Repositories::memory();
+        let user = create_user("tester", EMAIL, &repos).await;
+        let follower = create_user("follower", "follower@whatsoo.org", &repos).await;
+        let topic_id = create_topic(user.pk_id, "主题", &repos).await;
+        let comment = NewComment {
+            user_id: user.pk_id,
+            topic_id,
+            parent_id: None,
+            root_id: None,
+            depth: 0,
+            content: "评论",
+        };
+        repos.comments.insert_one_comment(comment).await.unwrap();
+        repos
+            .stars
+            .insert_star(StarType::User, follower.pk_id, user.pk_id)
+            .await
+            .unwrap();
+
+        let profile = get_profile("tester", &repos).await.unwrap();
+        assert_eq!(profile.pk_id, user.pk_id);
+        assert_eq!(
+            (profile.topic_count, profile.comment_count, profile.follower_count),
+            (1, 1, 1)
+        );
+        // 公开主页不包含邮箱和密码
+        let json = serde_json::to_value(&profile).unwrap();
+        assert!(json.get("uk_email").is_none());
+        assert!(json.get("user_password").is_none());
+    }
+
+    #[tokio::test]
+    async fn profile_of_unknown_user() {
+        let repos = Repositories::memory();
+        let result = get_profile("nobody", &repos).await;
+        assert_eq!(error_code(result), Some(ErrorCode::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn update_profile_clears_blank_fields() {
+        let repos = Repositories::memory();
+        let user = create_user("tester", EMAIL, &repos).await;
+        let profile = ProfileFront {
+            avatar: None,
+            blog_url: Some(String::from(" https://whatsoo.org ")),
+            introduce: Some(String::from("你好")),
+            github_uid: Some(String::from("nova-me")),
+        };
+        let updated = update_profile(user.pk_id, profile, &repos).await.unwrap();
+        assert_eq!(updated.blog_url.as_deref(), Some("https://whatsoo.org"));
+        assert_eq!(updated.github_uid.as_deref(), Some("nova-me"));
+
+        let profile = ProfileFront {
+            avatar: None,
+            blog_url: Some(String::from("  ")),
+            introduce: None,
+            github_uid: Some(String::from("nova-me")),
+        };
+        let updated = update_profile(user.pk_id, profile, &repos).await.unwrap();
+        assert_eq!(updated.blog_url, None);
+        assert_eq!(updated.introduce, None);
+        assert_eq!(updated.github_uid.as_deref(), Some("nova-me"));
     }
 }
@@ -0,0 +1,80 @@
+use std::path::{Component, Path, PathBuf};
+
+use tokio::fs;
+
+use crate::AppResult;
+use crate::common::err::AppError;
+use crate::storage::Storage;
+
+/// 保存到本地目录，文件通过 `/uploads` 路由直接提供访问，多实例部署时需要挂载共享目录
+pub struct LocalStorage {
+    dir: PathBuf,
+    public_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(dir: PathBuf, public_url: &str) -> Self {
+        Self {
+            dir,
+            public_url: public_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// key 只能是相对路径，不能跳出存储目录
+    fn path(&self, key: &str) -> AppResult<PathBuf> {
+        let relative = Path::new(key);
+        if key.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(AppError::IoError(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("非法的文件key: {}", key),
+            )));
+        }
+        Ok(self.dir.join(relative))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>, _content_type: &str) -> AppResult<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // 先写临时文件再重命名，避免访问到写了一半的文件
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, bytes).await?;
+        fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_stays_inside_dir() {
+        let storage = LocalStorage::new(PathBuf::from("uploads"), "http://127.0.0.1:8080/uploads/");
+        assert_eq!(
+            storage.path("avatar/202110/a_256.png").unwrap(),
+            PathBuf::from("uploads/avatar/202110/a_256.png")
+        );
+        for key in &[
+            "",
+            "../etc/passwd",
+            "/etc/passwd",
+            "avatar/../../etc/passwd",
+            "./avatar",
+        ] {
+            assert!(storage.path(key).is_err(), "{}", key);
+        }
+        assert_eq!(
+            storage.url("avatar/202110/a_256.png"),
+            "http://127.0.0.1:8080/uploads/avatar/202110/a_256.png"
+        );
+    }
+}
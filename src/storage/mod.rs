@@ -0,0 +1,19 @@
+use crate::AppResult;
+
+pub use local_storage::LocalStorage;
+
+mod local_storage;
+
+/// 上传文件的存储，按 key 保存对象，与 S3 兼容的对象存储使用相同的模型
+///
+/// `key` 为 `/` 分隔的相对路径，由服务端生成，例如 `avatar/202110/{uuid}_256.png`。
+/// 目前只有保存到本地目录的 [`LocalStorage`]，接入 S3 兼容的对象存储时实现该 trait，
+/// 并在 `upload.backend` 中增加对应的配置即可。
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// 保存文件，key 已存在时覆盖
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> AppResult<()>;
+
+    /// 文件的公开访问地址
+    fn url(&self, key: &str) -> String;
+}